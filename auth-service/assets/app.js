@@ -1,10 +1,15 @@
 const loginSection = document.getElementById("login-section");
 const twoFASection = document.getElementById("2fa-section");
 const signupSection = document.getElementById("signup-section");
+const resetRequestSection = document.getElementById("reset-request-section");
+const resetConfirmSection = document.getElementById("reset-confirm-section");
+const consentSection = document.getElementById("consent-section");
 
 const signupLink = document.getElementById("signup-link");
 const twoFALoginLink = document.getElementById("2fa-login-link");
 const signupLoginLink = document.getElementById("signup-login-link");
+const resetRequestLink = document.getElementById("reset-request-link");
+const resetRequestLoginLink = document.getElementById("reset-request-login-link");
 
 signupLink.addEventListener("click", (e) => {
     e.preventDefault();
@@ -30,6 +35,128 @@ signupLoginLink.addEventListener("click", (e) => {
     signupSection.style.display = "none";
 });
 
+resetRequestLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    loginSection.style.display = "none";
+    resetRequestSection.style.display = "block";
+});
+
+resetRequestLoginLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    loginSection.style.display = "block";
+    resetRequestSection.style.display = "none";
+});
+
+// Password reset links from the email point back to this page
+const resetParams = new URLSearchParams(window.location.search);
+if (resetParams.has("resetToken")) {
+    loginSection.style.display = "none";
+    resetConfirmSection.style.display = "block";
+}
+
+// So do email verification links sent on signup
+if (resetParams.has("verifyEmailToken")) {
+    const token = resetParams.get("verifyEmailToken");
+    window.history.replaceState({}, "", "/");
+
+    fetch('/verify-email', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token }),
+    }).then(response => {
+        response.json().then(data => alert(response.ok ? data.message : data.error));
+    });
+}
+
+// And links confirming a new email address
+if (resetParams.has("changeEmailToken")) {
+    const token = resetParams.get("changeEmailToken");
+    window.history.replaceState({}, "", "/");
+
+    fetch('/change-email/confirm', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token }),
+    }).then(response => {
+        response.json().then(data => alert(response.ok ? data.message : data.error));
+    });
+}
+
+// OAuth clients send users here to log in before they are authorized, with the
+// authorization request to continue with once they are
+const authorizeRequest = resetParams.get("authorize");
+
+function continueAuthorization() {
+    window.location.assign("/oauth/authorize?" + authorizeRequest);
+}
+
+if (authorizeRequest !== null) {
+    // Users with a session only need a fresh token to continue
+    fetch('/refresh', { method: 'POST' }).then(response => {
+        if (response.ok) {
+            continueAuthorization();
+        }
+    });
+}
+
+// Or to be asked for their consent to the scopes requested
+const consentRequest = resetParams.get("consent");
+
+if (consentRequest !== null) {
+    const params = Object.fromEntries(new URLSearchParams(consentRequest));
+    loginSection.style.display = "none";
+    consentSection.style.display = "block";
+
+    fetch('/oauth/clients/' + encodeURIComponent(params.client_id ?? "")).then(response => {
+        response.json().then(data => {
+            if (!response.ok) {
+                showConsentError(data.error);
+                return;
+            }
+            document.getElementById("consent-client-name").innerText = data.name;
+            const scopes = params.scope ? params.scope.split(" ").filter(s => s !== "") : data.scopes;
+            const scopeList = document.getElementById("consent-scopes");
+            for (const scope of scopes) {
+                const item = document.createElement("li");
+                item.innerText = scope;
+                scopeList.appendChild(item);
+            }
+        });
+    });
+
+    const decide = (approved) => {
+        fetch('/oauth/authorize', {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({ ...params, approved }),
+        }).then(response => {
+            response.json().then(data => {
+                if (response.ok) {
+                    window.location.assign(data.redirectUri);
+                } else {
+                    showConsentError(data.error);
+                }
+            });
+        });
+    };
+    document.getElementById("consent-allow").addEventListener("click", () => decide(true));
+    document.getElementById("consent-deny").addEventListener("click", () => decide(false));
+}
+
+function showConsentError(error_msg) {
+    const consentErrAlter = document.getElementById("consent-err-alert");
+    consentErrAlter.innerText = "Error: " + error_msg;
+    consentErrAlter.style.display = "block";
+}
+
 // -----------------------------------------------------
 
 const loginForm = document.getElementById("login-form");
@@ -53,6 +180,9 @@ loginButton.addEventListener("click", (e) => {
             TwoFAForm.email.value = email;
             response.json().then(data => {
                 TwoFAForm.login_attempt_id.value = data.loginAttemptId;
+                document.getElementById("2fa-hint").innerText = data.twoFAMethod === "totp"
+                    ? "Enter the code from your authenticator app."
+                    : "Enter the code we sent to your email.";
             });
 
             loginForm.email.value = "";
@@ -66,6 +196,10 @@ loginButton.addEventListener("click", (e) => {
             loginForm.email.value = "";
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
+            if (authorizeRequest !== null) {
+                continueAuthorization();
+                return;
+            }
             alert("You have successfully logged in.");
         } else {
             response.json().then(data => {
@@ -104,7 +238,14 @@ signupButton.addEventListener("click", (e) => {
             signupForm.password.value = "";
             signupForm.twoFA.checked = false;
             signupErrAlter.style.display = "none";
-            alert("You have successfully created a user.");
+            response.json().then(data => {
+                let message = "You have successfully created a user. Check your email to verify your address before logging in.";
+                if (data.recoveryCodes) {
+                    message += "\n\nKeep these recovery codes somewhere safe. Each of them can be used once to log in without your 2FA code:\n"
+                        + data.recoveryCodes.join("\n");
+                }
+                alert(message);
+            });
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
@@ -145,10 +286,25 @@ TwoFAButton.addEventListener("click", (e) => {
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
+            if (authorizeRequest !== null) {
+                continueAuthorization();
+                return;
+            }
             alert("You have successfully logged in.");
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
+        } else if (response.status === 403) {
+            // Too many wrong codes ended the login attempt, so the password is needed again
+            response.json().then(data => {
+                TwoFAForm.email_code.value = "";
+                TwoFAForm.login_attempt_id.value = "";
+                TwoFAErrAlter.style.display = "none";
+                loginErrAlter.innerHTML = `<span><strong>Error: </strong>${data.error}</span>`;
+                loginErrAlter.style.display = "block";
+                loginSection.style.display = "block";
+                twoFASection.style.display = "none";
+            });
         } else {
             response.json().then(data => {
                 let error_msg = data.error;
@@ -161,4 +317,82 @@ TwoFAButton.addEventListener("click", (e) => {
             });
         }
     });
-});
\ No newline at end of file
+});
+
+const resetRequestForm = document.getElementById("reset-request-form");
+const resetRequestButton = document.getElementById("reset-request-form-submit");
+const resetRequestErrAlter = document.getElementById("reset-request-err-alert");
+
+resetRequestButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = resetRequestForm.email.value;
+
+    fetch('/password-reset/request', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email }),
+    }).then(response => {
+        if (response.ok) {
+            resetRequestForm.email.value = "";
+            resetRequestErrAlter.style.display = "none";
+            response.json().then(data => alert(data.message));
+            loginSection.style.display = "block";
+            resetRequestSection.style.display = "none";
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    resetRequestErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    resetRequestErrAlter.style.display = "block";
+                } else {
+                    resetRequestErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+});
+
+const resetConfirmForm = document.getElementById("reset-confirm-form");
+const resetConfirmButton = document.getElementById("reset-confirm-form-submit");
+const resetConfirmErrAlter = document.getElementById("reset-confirm-err-alert");
+
+resetConfirmForm.email.value = resetParams.get("email") ?? "";
+resetConfirmForm.token.value = resetParams.get("resetToken") ?? "";
+
+resetConfirmButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = resetConfirmForm.email.value;
+    const token = resetConfirmForm.token.value;
+    const newPassword = resetConfirmForm.password.value;
+
+    fetch('/password-reset/confirm', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email, token, newPassword }),
+    }).then(response => {
+        if (response.ok) {
+            resetConfirmForm.password.value = "";
+            resetConfirmErrAlter.style.display = "none";
+            alert("Your password has been reset. Please log in again.");
+            window.history.replaceState({}, "", "/");
+            loginSection.style.display = "block";
+            resetConfirmSection.style.display = "none";
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    resetConfirmErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    resetConfirmErrAlter.style.display = "block";
+                } else {
+                    resetConfirmErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+});
@@ -0,0 +1,4 @@
+-- Accounts that existed before email verification was introduced stay usable;
+-- only accounts created from now on start out unverified.
+ALTER TABLE users ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE users ALTER COLUMN email_verified SET DEFAULT FALSE;
@@ -0,0 +1,5 @@
+ALTER TABLE users ADD COLUMN two_fa_method TEXT NOT NULL DEFAULT 'email'
+    CHECK (two_fa_method IN ('email', 'totp'));
+-- TOTP secrets are stored encrypted (AES-256-GCM), base64 encoded with their nonce
+ALTER TABLE users ADD COLUMN totp_secret TEXT;
+ALTER TABLE users ADD COLUMN pending_totp_secret TEXT;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS recovery_codes(
+   id BIGSERIAL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   -- Hex encoded SHA-256 hash of the code
+   code_hash TEXT NOT NULL,
+   used_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS recovery_codes_email_idx ON recovery_codes(email);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS sessions(
+   id TEXT PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   -- Id of the latest auth token issued for the session
+   jti TEXT NOT NULL,
+   device TEXT,
+   ip TEXT,
+   user_agent TEXT,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions(email);
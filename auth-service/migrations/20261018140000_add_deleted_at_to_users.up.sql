@@ -0,0 +1,3 @@
+-- Set while a deleted account waits out its grace period, after which it is purged
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS users_deleted_at_idx ON users(deleted_at) WHERE deleted_at IS NOT NULL;
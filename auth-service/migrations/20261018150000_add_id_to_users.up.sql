@@ -0,0 +1,4 @@
+-- Stable ids, so tokens name users without revealing their email address and stay valid
+-- when it changes. The volatile default gives every existing row an id of its own.
+ALTER TABLE users ADD COLUMN id UUID NOT NULL DEFAULT gen_random_uuid();
+ALTER TABLE users ADD CONSTRAINT users_id_key UNIQUE (id);
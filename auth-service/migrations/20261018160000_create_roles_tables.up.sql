@@ -0,0 +1,34 @@
+-- Roles users can be granted. Every role is known to the code as well, so new ones are
+-- added by a migration along with the code that checks them.
+CREATE TABLE IF NOT EXISTS roles(
+   name TEXT PRIMARY KEY,
+   description TEXT NOT NULL
+);
+INSERT INTO roles (name, description)
+VALUES ('admin', 'Manages users and their roles')
+ON CONFLICT (name) DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS user_roles(
+   user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+   role TEXT NOT NULL REFERENCES roles(name),
+   PRIMARY KEY (user_id, role)
+);
+
+-- What roles allow, which routes check for rather than for the roles themselves. Like
+-- roles, every permission and what grants it is known to the code as well.
+CREATE TABLE IF NOT EXISTS permissions(
+   name TEXT PRIMARY KEY,
+   description TEXT NOT NULL
+);
+INSERT INTO permissions (name, description)
+VALUES ('manage-roles', 'Grants and revokes roles')
+ON CONFLICT (name) DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS role_permissions(
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   permission TEXT NOT NULL REFERENCES permissions(name) ON DELETE CASCADE,
+   PRIMARY KEY (role, permission)
+);
+INSERT INTO role_permissions (role, permission)
+VALUES ('admin', 'manage-roles')
+ON CONFLICT (role, permission) DO NOTHING;
@@ -0,0 +1,15 @@
+-- Set by admins: disabled users can't log in, and users required to reset their password
+-- can't log in until they have
+ALTER TABLE users ADD COLUMN disabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN password_reset_required BOOLEAN NOT NULL DEFAULT FALSE;
+
+INSERT INTO permissions (name, description)
+VALUES
+   ('manage-users', 'Looks up, suspends and reactivates users, and ends their sessions'),
+   ('rotate-signing-keys', 'Rotates the key tokens are signed with')
+ON CONFLICT (name) DO NOTHING;
+INSERT INTO role_permissions (role, permission)
+VALUES
+   ('admin', 'manage-users'),
+   ('admin', 'rotate-signing-keys')
+ON CONFLICT (role, permission) DO NOTHING;
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS disabled BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE users SET email_verified = status <> 'pending-verification', disabled = status = 'suspended';
+ALTER TABLE users DROP COLUMN IF EXISTS status;
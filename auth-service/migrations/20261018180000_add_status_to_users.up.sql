@@ -0,0 +1,10 @@
+-- Whether a user can log in, replacing the email_verified and disabled flags
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'pending-verification'
+    CHECK (status IN ('active', 'suspended', 'pending-verification'));
+UPDATE users SET status = CASE
+    WHEN disabled THEN 'suspended'
+    WHEN email_verified THEN 'active'
+    ELSE 'pending-verification'
+END;
+ALTER TABLE users DROP COLUMN disabled;
+ALTER TABLE users DROP COLUMN email_verified;
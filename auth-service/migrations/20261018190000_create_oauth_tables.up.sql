@@ -0,0 +1,26 @@
+-- Applications that obtain tokens for users through the OAuth 2.0 authorization code flow
+CREATE TABLE IF NOT EXISTS oauth_clients(
+   id UUID PRIMARY KEY,
+   name TEXT NOT NULL,
+   redirect_uris TEXT[] NOT NULL,
+   -- Scopes the client may request
+   scopes TEXT[] NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Scopes users consented to give a client, so they are only asked once
+CREATE TABLE IF NOT EXISTS oauth_consents(
+   user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+   client_id UUID NOT NULL REFERENCES oauth_clients(id) ON DELETE CASCADE,
+   scopes TEXT[] NOT NULL,
+   granted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   PRIMARY KEY (user_id, client_id)
+);
+CREATE INDEX IF NOT EXISTS oauth_consents_client_id_idx ON oauth_consents(client_id);
+
+INSERT INTO permissions (name, description)
+VALUES ('manage-oauth-clients', 'Registers and deletes OAuth clients')
+ON CONFLICT (name) DO NOTHING;
+INSERT INTO role_permissions (role, permission)
+VALUES ('admin', 'manage-oauth-clients')
+ON CONFLICT (role, permission) DO NOTHING;
@@ -0,0 +1,2 @@
+-- Whether the user gave a 2FA code to start the session, for the `amr` claim of ID tokens
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS two_factor BOOLEAN NOT NULL DEFAULT FALSE;
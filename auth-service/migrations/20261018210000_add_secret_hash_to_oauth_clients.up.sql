@@ -0,0 +1,2 @@
+-- SHA-256 hash of the secret of confidential clients, NULL for public ones
+ALTER TABLE oauth_clients ADD COLUMN IF NOT EXISTS secret_hash TEXT;
@@ -0,0 +1,2 @@
+-- Time step of the last TOTP code the user gave, so that codes can't be used twice
+ALTER TABLE users ADD COLUMN totp_last_step BIGINT;
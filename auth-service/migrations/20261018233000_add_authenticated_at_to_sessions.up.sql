@@ -0,0 +1,3 @@
+-- When the user logged in to start the session, for the `auth_time` claim of ID tokens.
+-- Unknown for sessions recorded on first use of a refresh token family.
+ALTER TABLE sessions ADD COLUMN authenticated_at TIMESTAMPTZ;
@@ -1,4 +1,8 @@
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::domain::{
+    AuthorizationCodeStore, BannedTokenStore, EmailClient, FailedLoginStore, OAuthClientStore,
+    PasswordResetTokenStore, RateLimitStore, RecoveryCodeStore, RefreshTokenStore, SessionStore,
+    TwoFACodeStore, UserStore,
+};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -6,6 +10,14 @@ use tokio::sync::RwLock;
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type PasswordResetTokenStoreType = Arc<RwLock<dyn PasswordResetTokenStore + Send + Sync>>;
+pub type RecoveryCodeStoreType = Arc<RwLock<dyn RecoveryCodeStore + Send + Sync>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore + Send + Sync>>;
+pub type FailedLoginStoreType = Arc<RwLock<dyn FailedLoginStore + Send + Sync>>;
+pub type RateLimitStoreType = Arc<RwLock<dyn RateLimitStore + Send + Sync>>;
+pub type OAuthClientStoreType = Arc<RwLock<dyn OAuthClientStore + Send + Sync>>;
+pub type AuthorizationCodeStoreType = Arc<RwLock<dyn AuthorizationCodeStore + Send + Sync>>;
 pub type EmailClientType = Arc<RwLock<dyn EmailClient + Send + Sync>>;
 
 #[derive(Clone)]
@@ -14,20 +26,45 @@ pub struct AppState {
     pub user_store: UserStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
     pub email_client: EmailClientType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub session_store: SessionStoreType,
+    pub failed_login_store: FailedLoginStoreType,
+    pub rate_limit_store: RateLimitStoreType,
+    pub oauth_client_store: OAuthClientStoreType,
+    pub authorization_code_store: AuthorizationCodeStoreType,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
         email_client: EmailClientType,
+        refresh_token_store: RefreshTokenStoreType,
+        password_reset_token_store: PasswordResetTokenStoreType,
+        recovery_code_store: RecoveryCodeStoreType,
+        session_store: SessionStoreType,
+        failed_login_store: FailedLoginStoreType,
+        rate_limit_store: RateLimitStoreType,
+        oauth_client_store: OAuthClientStoreType,
+        authorization_code_store: AuthorizationCodeStoreType,
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
             email_client,
+            refresh_token_store,
+            password_reset_token_store,
+            recovery_code_store,
+            session_store,
+            failed_login_store,
+            rate_limit_store,
+            oauth_client_store,
+            authorization_code_store,
         }
     }
 }
@@ -2,12 +2,16 @@ pub mod data_stores;
 pub mod email;
 pub mod email_client;
 pub mod errors;
+pub mod oauth;
 pub mod password;
+pub mod totp_secret;
 pub mod user;
 
 pub use data_stores::*;
 pub use email::*;
 pub use email_client::*;
 pub use errors::*;
+pub use oauth::*;
 pub use password::*;
+pub use totp_secret::*;
 pub use user::*;
@@ -1,7 +1,11 @@
-use super::{Email, Password, User};
+use super::{
+    ClientId, CodeChallenge, Email, OAuthClient, Password, Permission, Role, TotpSecret, User,
+    UserId, UserStatus,
+};
 use color_eyre::eyre::{Context, Report, Result, eyre};
-use rand::Rng;
+use rand::{Rng, distributions::Alphanumeric};
 use secrecy::{ExposeSecret, Secret};
+use std::time::Duration;
 use thiserror::Error;
 
 #[async_trait::async_trait]
@@ -10,14 +14,83 @@ pub trait UserStore {
     // Make sure all methods are async so we can use async user stores in the future
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError>;
+    // A page of users ordered by email address, optionally only those whose address contains
+    // `search` (ignoring case), along with the number of users matching in total
+    async fn list_users(
+        &self,
+        search: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<User>, u64), UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password)
     -> Result<(), UserStoreError>;
+    // Also lifts a password reset requirement, since the password has been changed
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError>;
+    async fn require_password_reset(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    async fn set_status(&mut self, email: &Email, status: UserStatus)
+    -> Result<(), UserStoreError>;
+    // Activates a user waiting for their address to be verified. Suspended users stay suspended.
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    // Deleted users are kept for a grace period, during which they can be restored
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    async fn restore_user(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    // Removes users deleted before the given Unix timestamp for good, returning their addresses
+    async fn purge_deleted_users(
+        &mut self,
+        deleted_before: i64,
+    ) -> Result<Vec<Email>, UserStoreError>;
+    // Moves the user, and everything stored under their address, to a new email address
+    async fn update_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError>;
+    // A TOTP secret stays pending until the user proves their authenticator app has it
+    async fn set_pending_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError>;
+    async fn get_pending_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError>;
+    // Makes the pending TOTP secret the user's second factor
+    async fn enable_totp(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    async fn get_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError>;
+    // Records the time step of a TOTP code the user gave. Codes of that step or an earlier
+    // one are refused from then on (RFC 6238 section 5.2), with `TotpCodeReused`.
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<(), UserStoreError>;
+    // Turning 2FA off also drops the user's TOTP secrets, so they fall back to emailed
+    // codes if they turn it on again
+    async fn set_requires_2fa(
+        &mut self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError>;
+    // Roles are kept by user id. Granting a role the user has, or revoking one they
+    // don't have, changes nothing.
+    async fn get_roles(&self, id: &UserId) -> Result<Vec<Role>, UserStoreError>;
+    async fn grant_role(&mut self, id: &UserId, role: Role) -> Result<(), UserStoreError>;
+    async fn revoke_role(&mut self, id: &UserId, role: Role) -> Result<(), UserStoreError>;
+    // Permissions the roles of the user grant, each listed once
+    async fn get_permissions(&self, id: &UserId) -> Result<Vec<Permission>, UserStoreError>;
 }
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore {
-    async fn add_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError>;
-    async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError>;
+    // Tokens are banned by their `jti` claim, so the tokens themselves don't have to be kept
+    async fn add_token(&mut self, jti: String) -> Result<(), BannedTokenStoreError>;
+    async fn contains_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError>;
+    // Bans every JWT issued to the user up to now, without having to know the tokens
+    async fn revoke_user_tokens(&mut self, user_id: &UserId) -> Result<(), BannedTokenStoreError>;
+    // Unix timestamp of the last `revoke_user_tokens` call for the user, if still relevant
+    async fn get_user_tokens_revoked_at(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Option<i64>, BannedTokenStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -26,6 +99,591 @@ pub enum BannedTokenStoreError {
     UnexpectedError(#[source] Report),
 }
 
+// Refresh tokens are grouped into families: every rotation adds a new token to the
+// family of the token it replaces. Presenting a token that was already rotated means
+// it was stolen (or replayed), so the whole family gets revoked.
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    // Starts the token family of a new session
+    async fn add_token(
+        &mut self,
+        email: Email,
+        session_id: SessionId,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError>;
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+        new_token: RefreshToken,
+    ) -> Result<(Email, SessionId), RefreshTokenStoreError>;
+    async fn revoke_token(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError>;
+    async fn revoke_session(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<(), RefreshTokenStoreError>;
+    async fn revoke_user_tokens(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RefreshTokenStoreError {
+    #[error("Refresh token not found")]
+    TokenNotFound,
+    #[error("Refresh token reused")]
+    TokenReused,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RefreshTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::TokenReused, Self::TokenReused)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RefreshToken(Secret<String>);
+
+impl PartialEq for RefreshToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl RefreshToken {
+    pub fn parse(token: String) -> Result<Self> {
+        if token.len() == REFRESH_TOKEN_LENGTH && token.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Ok(Self(Secret::new(token)))
+        } else {
+            Err(eyre!("Invalid refresh token"))
+        }
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        // Refresh tokens are opaque: a random alphanumeric string with no embedded claims
+        Self(Secret::new(random_alphanumeric(REFRESH_TOKEN_LENGTH)))
+    }
+}
+
+impl AsRef<Secret<String>> for RefreshToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+const REFRESH_TOKEN_LENGTH: usize = 64;
+
+// This trait represents the interface all concrete password reset token stores should implement
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+    async fn remove_token(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError>;
+    async fn get_token(
+        &self,
+        email: &Email,
+    ) -> Result<PasswordResetToken, PasswordResetTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasswordResetTokenStoreError {
+    #[error("Password reset token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasswordResetTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PasswordResetToken(Secret<String>);
+
+impl PartialEq for PasswordResetToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl PasswordResetToken {
+    pub fn parse(token: String) -> Result<Self> {
+        if token.len() == PASSWORD_RESET_TOKEN_LENGTH
+            && token.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            Ok(Self(Secret::new(token)))
+        } else {
+            Err(eyre!("Invalid password reset token"))
+        }
+    }
+}
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        Self(Secret::new(random_alphanumeric(
+            PASSWORD_RESET_TOKEN_LENGTH,
+        )))
+    }
+}
+
+impl AsRef<Secret<String>> for PasswordResetToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+const PASSWORD_RESET_TOKEN_LENGTH: usize = 32;
+
+// Recovery codes let 2FA users log in when they lost access to their second factor.
+// Each code can only be used once, and only hashes of the codes should be kept.
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore {
+    // Replaces all codes the user had before
+    async fn set_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError>;
+    // Marks the code as used, failing if the user has no such unused code
+    async fn use_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RecoveryCodeStoreError {
+    #[error("Recovery code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RecoveryCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecoveryCode(Secret<String>);
+
+impl PartialEq for RecoveryCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl RecoveryCode {
+    // Codes look like `a1b2-c3d4-e5f6-g7h8`. They are case insensitive, since users may type them in.
+    pub fn parse(code: String) -> Result<Self> {
+        let code = code.trim().to_ascii_lowercase();
+        let groups: Vec<&str> = code.split('-').collect();
+        let valid = groups.len() == RECOVERY_CODE_GROUPS
+            && groups.iter().all(|group| {
+                group.len() == RECOVERY_CODE_GROUP_LENGTH
+                    && group
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+            });
+        if valid {
+            Ok(Self(Secret::new(code)))
+        } else {
+            Err(eyre!("Invalid recovery code"))
+        }
+    }
+
+    // A fresh set of codes, as handed out on 2FA enrollment
+    pub fn generate_set() -> Vec<Self> {
+        (0..RECOVERY_CODE_COUNT).map(|_| Self::default()).collect()
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let groups: Vec<String> = (0..RECOVERY_CODE_GROUPS)
+            .map(|_| random_alphanumeric(RECOVERY_CODE_GROUP_LENGTH).to_ascii_lowercase())
+            .collect();
+        Self(Secret::new(groups.join("-")))
+    }
+}
+
+impl AsRef<Secret<String>> for RecoveryCode {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+// 16 random characters out of 36 give more than 80 bits of entropy
+const RECOVERY_CODE_GROUPS: usize = 4;
+const RECOVERY_CODE_GROUP_LENGTH: usize = 4;
+
+pub(crate) fn random_alphanumeric(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+// Every login starts a session, which lasts as long as its refresh token family. Sessions
+// are listed to their user, who can end them one by one or all at once.
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError>;
+    async fn get_user_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    // Records a new auth token issued for the session, and where it was requested from
+    async fn touch_session(
+        &mut self,
+        id: &SessionId,
+        jti: String,
+        ip: Option<String>,
+    ) -> Result<(), SessionStoreError>;
+    async fn remove_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError>;
+    async fn remove_user_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SessionStoreError {
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for SessionStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SessionNotFound, Self::SessionNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: SessionId,
+    pub email: Email,
+    // Id of the latest auth token issued for the session, which is banned when it ends
+    pub jti: String,
+    pub device: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    // Unix timestamps
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    // Whether the user gave a 2FA code, besides their password, to start the session
+    pub two_factor: bool,
+    // Unix timestamp of the login that started the session, if it is known
+    pub authenticated_at: Option<i64>,
+}
+
+// Session ids are also the ids of the sessions' refresh token families
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SessionId(String);
+
+impl SessionId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = uuid::Uuid::parse_str(&id).wrap_err("Invalid session id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for SessionId {
+    fn default() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for SessionId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Failed logins are counted per email address, whether or not an account exists for it.
+// The count is forgotten once no login has failed for as long as a lockout lasts.
+#[async_trait::async_trait]
+pub trait FailedLoginStore {
+    async fn get_failed_logins(&self, email: &Email)
+    -> Result<FailedLogins, FailedLoginStoreError>;
+    // Counts a failed login and returns the updated count
+    async fn add_failed_login(
+        &mut self,
+        email: &Email,
+    ) -> Result<FailedLogins, FailedLoginStoreError>;
+    async fn reset_failed_logins(&mut self, email: &Email) -> Result<(), FailedLoginStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum FailedLoginStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for FailedLoginStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FailedLogins {
+    pub count: u32,
+    // Unix timestamp of the latest failed login
+    pub last_failed_at: i64,
+}
+
+impl FailedLogins {
+    // A couple of typos don't slow anyone down
+    const FREE_ATTEMPTS: u32 = 2;
+
+    // Unix timestamp before which no other login may be tried. The wait doubles with every
+    // failed login past the free ones, until the lockout threshold locks the account for
+    // the whole lockout duration.
+    pub fn retry_at(&self, lockout_threshold: u32, lockout_seconds: i64) -> Option<i64> {
+        if self.count >= lockout_threshold {
+            return Some(self.last_failed_at + lockout_seconds);
+        }
+        if self.count <= Self::FREE_ATTEMPTS {
+            return None;
+        }
+        let backoff_seconds = 2_i64
+            .saturating_pow(self.count - Self::FREE_ATTEMPTS - 1)
+            .min(lockout_seconds);
+        Some(self.last_failed_at + backoff_seconds)
+    }
+}
+
+// Requests are rate limited with token buckets: every request takes a token out of its
+// bucket, and tokens trickle back in at a steady rate up to the size of the bucket.
+#[async_trait::async_trait]
+pub trait RateLimitStore {
+    // Takes a token out of the bucket under the key. Returns how long it takes for the next
+    // token to come in if the bucket is empty.
+    async fn take_token(
+        &mut self,
+        key: &str,
+        rate_limit: &RateLimit,
+    ) -> Result<Option<Duration>, RateLimitStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RateLimitStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RateLimitStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    // Number of requests that can be made in a burst
+    pub capacity: u32,
+    // Time it takes for one more request to be allowed
+    pub refill_interval: Duration,
+}
+
+impl RateLimit {
+    // Time it takes for an empty bucket to fill up again, after which it can be forgotten
+    pub fn refill_duration(&self) -> Duration {
+        self.refill_interval * self.capacity
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBucket {
+    pub tokens: f64,
+    // Unix timestamp in milliseconds
+    pub updated_at: i64,
+}
+
+impl TokenBucket {
+    pub fn new(rate_limit: &RateLimit, now: i64) -> Self {
+        Self {
+            tokens: rate_limit.capacity as f64,
+            updated_at: now,
+        }
+    }
+
+    // Refills the bucket for the time passed since it was last updated, then takes a token
+    // out of it. Returns how long it takes for the next token to come in if it was empty.
+    pub fn take(&mut self, rate_limit: &RateLimit, now: i64) -> Option<Duration> {
+        let refill_interval = rate_limit.refill_interval.as_millis() as f64;
+        let elapsed = (now - self.updated_at).max(0) as f64;
+        self.tokens = (self.tokens + elapsed / refill_interval).min(rate_limit.capacity as f64);
+        self.updated_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return None;
+        }
+        let wait = ((1.0 - self.tokens) * refill_interval).ceil();
+        Some(Duration::from_millis(wait as u64))
+    }
+}
+
+// OAuth clients, and the scopes users have consented to give them
+#[async_trait::async_trait]
+pub trait OAuthClientStore {
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError>;
+    async fn get_client(&self, id: &ClientId) -> Result<OAuthClient, OAuthClientStoreError>;
+    // Ordered by name
+    async fn list_clients(&self) -> Result<Vec<OAuthClient>, OAuthClientStoreError>;
+    // Consents given to the client go along with it
+    async fn delete_client(&mut self, id: &ClientId) -> Result<(), OAuthClientStoreError>;
+    // Scopes the user consented to give the client, none if they never did
+    async fn get_consent(
+        &self,
+        user_id: &UserId,
+        client_id: &ClientId,
+    ) -> Result<Vec<String>, OAuthClientStoreError>;
+    // Adds to the scopes the user consented to give the client before
+    async fn grant_consent(
+        &mut self,
+        user_id: &UserId,
+        client_id: &ClientId,
+        scopes: &[String],
+    ) -> Result<(), OAuthClientStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum OAuthClientStoreError {
+    #[error("OAuth client not found")]
+    ClientNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for OAuthClientStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ClientNotFound, Self::ClientNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Authorization codes are handed to OAuth clients through the user's browser, and
+// exchanged for a token by the client. Each code can only be exchanged once.
+#[async_trait::async_trait]
+pub trait AuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError>;
+    // Removes the code, returning what it was issued for
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum AuthorizationCodeStoreError {
+    #[error("Authorization code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for AuthorizationCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// What the user authorized the client to do, as recorded with the authorization code
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorizationGrant {
+    pub client_id: ClientId,
+    pub user_id: UserId,
+    // Session the user authorized the client from. Tokens of the client are issued for it.
+    pub session_id: SessionId,
+    // A token request that names a redirect URI has to name this one. It has to name it in
+    // any case if the authorization request did (RFC 6749 section 4.1.3).
+    pub redirect_uri: String,
+    pub redirect_uri_required: bool,
+    pub scopes: Vec<String>,
+    pub code_challenge: CodeChallenge,
+    // OpenID Connect clients' value for the ID token, to tie it to their authorization request
+    pub nonce: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthorizationCode(Secret<String>);
+
+impl PartialEq for AuthorizationCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl AuthorizationCode {
+    pub fn parse(code: String) -> Result<Self> {
+        if code.len() == AUTHORIZATION_CODE_LENGTH
+            && code.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            Ok(Self(Secret::new(code)))
+        } else {
+            Err(eyre!("Invalid authorization code"))
+        }
+    }
+}
+
+impl Default for AuthorizationCode {
+    fn default() -> Self {
+        Self(Secret::new(random_alphanumeric(AUTHORIZATION_CODE_LENGTH)))
+    }
+}
+
+impl AsRef<Secret<String>> for AuthorizationCode {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+const AUTHORIZATION_CODE_LENGTH: usize = 32;
+
 #[derive(Debug, Error)]
 pub enum UserStoreError {
     #[error("User already exists")]
@@ -34,6 +692,10 @@ pub enum UserStoreError {
     UserNotFound,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("TOTP secret not found")]
+    TotpSecretNotFound,
+    #[error("TOTP code reused")]
+    TotpCodeReused,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -45,6 +707,8 @@ impl PartialEq for UserStoreError {
             (Self::UserAlreadyExists, Self::UserAlreadyExists)
                 | (Self::UserNotFound, Self::UserNotFound)
                 | (Self::InvalidCredentials, Self::InvalidCredentials)
+                | (Self::TotpSecretNotFound, Self::TotpSecretNotFound)
+                | (Self::TotpCodeReused, Self::TotpCodeReused)
                 | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
@@ -63,6 +727,8 @@ pub trait TwoFACodeStore {
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    // Counts a wrong code given for the login attempt and returns how many there were so far
+    async fn add_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -130,7 +796,7 @@ impl TwoFACode {
         } else {
             match code.parse::<u64>() {
                 Ok(parsed_code) => {
-                    if parsed_code >= 100000 && parsed_code <= 999_999 {
+                    if (100_000..=999_999).contains(&parsed_code) {
                         Ok(TwoFACode(Secret::new(code)))
                     } else {
                         Err(eyre!("Invalid code".to_string()))
@@ -148,7 +814,7 @@ impl Default for TwoFACode {
         // Use the `rand` crate to generate a random 2FA code.
         // The code should be 6 digits (ex: 834629)
         Self(Secret::new(
-            rand::thread_rng().gen_range(000_000..=999_999).to_string(),
+            rand::thread_rng().gen_range(100_000..=999_999).to_string(),
         ))
     }
 }
@@ -156,6 +822,49 @@ impl Default for TwoFACode {
 // TODO: Implement AsRef<str> for TwoFACode
 impl AsRef<Secret<String>> for TwoFACode {
     fn as_ref(&self) -> &Secret<String> {
-        return &self.0;
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_logins_retry_at() {
+        let failed_logins = |count| FailedLogins {
+            count,
+            last_failed_at: 1000,
+        };
+        assert_eq!(failed_logins(0).retry_at(5, 900), None);
+        assert_eq!(failed_logins(2).retry_at(5, 900), None);
+        assert_eq!(failed_logins(3).retry_at(5, 900), Some(1001));
+        assert_eq!(failed_logins(4).retry_at(5, 900), Some(1002));
+        assert_eq!(failed_logins(5).retry_at(5, 900), Some(1900));
+        assert_eq!(failed_logins(6).retry_at(5, 900), Some(1900));
+        // The backoff never exceeds a lockout
+        assert_eq!(failed_logins(40).retry_at(100, 900), Some(1900));
+    }
+
+    #[test]
+    fn test_token_bucket_take() {
+        let rate_limit = RateLimit {
+            capacity: 2,
+            refill_interval: Duration::from_secs(10),
+        };
+        let mut bucket = TokenBucket::new(&rate_limit, 0);
+        assert_eq!(bucket.take(&rate_limit, 0), None);
+        assert_eq!(bucket.take(&rate_limit, 0), None);
+        assert_eq!(bucket.take(&rate_limit, 0), Some(Duration::from_secs(10)));
+        assert_eq!(
+            bucket.take(&rate_limit, 4_000),
+            Some(Duration::from_secs(6))
+        );
+        assert_eq!(bucket.take(&rate_limit, 10_000), None);
+
+        // The bucket never holds more than its capacity
+        assert_eq!(bucket.take(&rate_limit, 1_000_000), None);
+        assert_eq!(bucket.take(&rate_limit, 1_000_000), None);
+        assert!(bucket.take(&rate_limit, 1_000_000).is_some());
     }
 }
@@ -13,6 +13,112 @@ pub enum AuthAPIError {
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Invalid password reset token")]
+    InvalidPasswordResetToken,
+    #[error("Email not verified")]
+    EmailNotVerified,
+    #[error("Invalid email verification token")]
+    InvalidEmailVerificationToken,
+    #[error("Invalid email change token")]
+    InvalidEmailChangeToken,
+    #[error("2FA not enabled")]
+    TwoFANotEnabled,
+    #[error("2FA already enabled")]
+    TwoFAAlreadyEnabled,
+    #[error("Too many 2FA attempts")]
+    TooManyTwoFAAttempts,
+    #[error("Account locked")]
+    AccountLocked,
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Forbidden")]
+    Forbidden,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Invalid role")]
+    InvalidRole,
+    #[error("Account suspended")]
+    AccountSuspended,
+    #[error("Password reset required")]
+    PasswordResetRequired,
+    #[error("OAuth client not found")]
+    OAuthClientNotFound,
+    #[error("Invalid redirect URI")]
+    InvalidRedirectUri,
+    #[error("Invalid scope")]
+    InvalidScope,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+// Errors of the OAuth 2.0 endpoints, which clients expect in the format of RFC 6749
+// section 5.2 rather than as an `AuthAPIError`
+#[derive(Debug, Error)]
+pub enum OAuthError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(&'static str),
+    #[error("Invalid client")]
+    InvalidClient,
+    #[error("Invalid grant")]
+    InvalidGrant,
+    #[error("Unauthorized client")]
+    UnauthorizedClient,
+    #[error("Unsupported grant type")]
+    UnsupportedGrantType,
+    #[error("Unsupported response type")]
+    UnsupportedResponseType,
+    #[error("Invalid scope")]
+    InvalidScope,
+    #[error("Access denied")]
+    AccessDenied,
+    #[error("Unexpected error")]
+    ServerError(#[source] Report),
+}
+
+impl OAuthError {
+    // The error code clients get in the `error` parameter
+    pub fn code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_) => "invalid_request",
+            OAuthError::InvalidClient => "invalid_client",
+            OAuthError::InvalidGrant => "invalid_grant",
+            OAuthError::UnauthorizedClient => "unauthorized_client",
+            OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthError::UnsupportedResponseType => "unsupported_response_type",
+            OAuthError::InvalidScope => "invalid_scope",
+            OAuthError::AccessDenied => "access_denied",
+            OAuthError::ServerError(_) => "server_error",
+        }
+    }
+
+    // Human readable description for the `error_description` parameter
+    pub fn description(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(description) => description,
+            OAuthError::InvalidClient => "Client authentication failed",
+            OAuthError::InvalidGrant => {
+                "Authorization code is invalid, expired or was issued to another client"
+            }
+            OAuthError::UnauthorizedClient => "Client is not authorized to make this request",
+            OAuthError::UnsupportedGrantType => "Grant type is not supported",
+            OAuthError::UnsupportedResponseType => "Response type is not supported",
+            OAuthError::InvalidScope => "Requested scope is invalid or not allowed",
+            OAuthError::AccessDenied => "The user denied the request",
+            OAuthError::ServerError(_) => "Unexpected error",
+        }
+    }
+}
+
+// Errors of routes that take an OAuth access token in the Authorization header, reported as
+// RFC 6750 section 3.1 describes
+#[derive(Debug, Error)]
+pub enum BearerTokenError {
+    #[error("Missing token")]
+    MissingToken,
+    #[error("Invalid token")]
+    InvalidToken,
+    #[error("Insufficient scope")]
+    InsufficientScope(&'static str),
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
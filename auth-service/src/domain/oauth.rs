@@ -0,0 +1,221 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use color_eyre::eyre::{Context, Result, eyre};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use url::Url;
+
+use super::data_stores::random_alphanumeric;
+
+// An application that obtains tokens through OAuth 2.0. Public clients have no secret, so
+// they can only get tokens for users through the authorization code flow, where every
+// authorization is bound to a PKCE code challenge. Confidential clients authenticate with
+// their secret, and can get tokens for themselves through the client credentials grant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OAuthClient {
+    pub id: ClientId,
+    // Shown to users when they are asked for their consent
+    pub name: String,
+    // Authorization responses are only sent to these, compared as exact strings
+    pub redirect_uris: Vec<String>,
+    // Scopes the client may request
+    pub scopes: Vec<String>,
+    // Hash of the secret of confidential clients. The secret itself is only shown once, when
+    // the client is registered.
+    pub secret_hash: Option<String>,
+}
+
+impl OAuthClient {
+    pub fn is_confidential(&self) -> bool {
+        self.secret_hash.is_some()
+    }
+
+    // Whether the secret is the client's. Public clients have none to match.
+    pub fn verify_secret(&self, secret: &Secret<String>) -> bool {
+        // Compared in constant time, so the response time doesn't leak how much of the hash matched
+        self.secret_hash.as_ref().is_some_and(|hash| {
+            hash.as_bytes()
+                .ct_eq(ClientSecret(secret.clone()).hash().as_bytes())
+                .into()
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientId(String);
+
+impl ClientId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = uuid::Uuid::parse_str(&id).wrap_err("Invalid client id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for ClientId {
+    fn default() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for ClientId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Secret a confidential client authenticates with at the token endpoint
+#[derive(Debug, Clone)]
+pub struct ClientSecret(Secret<String>);
+
+impl ClientSecret {
+    // Unlike passwords, secrets are random with more than 256 bits of entropy, so a fast
+    // unsalted hash is enough to keep them from being recovered
+    pub fn hash(&self) -> String {
+        let hash = Sha256::digest(self.0.expose_secret().as_bytes());
+        hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+impl Default for ClientSecret {
+    fn default() -> Self {
+        Self(Secret::new(random_alphanumeric(CLIENT_SECRET_LENGTH)))
+    }
+}
+
+impl AsRef<Secret<String>> for ClientSecret {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+const CLIENT_SECRET_LENGTH: usize = 48;
+
+// Redirect URIs have to be absolute and can't have a fragment (RFC 6749 section 3.1.2).
+// Authorization codes are only sent over TLS, except to the machine the user is on.
+pub fn parse_redirect_uri(uri: &str) -> Result<Url> {
+    let url = Url::parse(uri).wrap_err("Invalid redirect URI")?;
+    if url.fragment().is_some() {
+        return Err(eyre!("Redirect URI must not have a fragment"));
+    }
+    let is_loopback = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+    match url.scheme() {
+        "https" => Ok(url),
+        "http" if is_loopback => Ok(url),
+        _ => Err(eyre!("Redirect URI must use https")),
+    }
+}
+
+// Splits a space-delimited scope parameter (RFC 6749 section 3.3), dropping duplicates
+pub fn parse_scopes(scope: &str) -> Result<Vec<String>> {
+    let mut scopes: Vec<String> = Vec::new();
+    for token in scope.split(' ').filter(|token| !token.is_empty()) {
+        if !token.chars().all(is_scope_char) {
+            return Err(eyre!("Invalid scope: {}", token));
+        }
+        if !scopes.iter().any(|s| s == token) {
+            scopes.push(token.to_owned());
+        }
+    }
+    Ok(scopes)
+}
+
+fn is_scope_char(c: char) -> bool {
+    matches!(c, '\x21' | '\x23'..='\x5b' | '\x5d'..='\x7e')
+}
+
+// PKCE code challenge of an authorization request. Only the S256 method is supported,
+// so the challenge is the unpadded base64url encoded SHA-256 hash of the code verifier.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodeChallenge(String);
+
+impl CodeChallenge {
+    pub fn parse(challenge: String) -> Result<Self> {
+        match URL_SAFE_NO_PAD.decode(&challenge) {
+            Ok(hash) if hash.len() == 32 => Ok(Self(challenge)),
+            _ => Err(eyre!("Invalid code challenge")),
+        }
+    }
+
+    // Whether the code verifier is the one the challenge was derived from (RFC 7636 section 4.6)
+    pub fn verify(&self, verifier: &str) -> bool {
+        let valid_verifier = (MIN_CODE_VERIFIER_LENGTH..=MAX_CODE_VERIFIER_LENGTH)
+            .contains(&verifier.len())
+            && verifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'));
+        valid_verifier && URL_SAFE_NO_PAD.encode(Sha256::digest(verifier)) == self.0
+    }
+}
+
+impl AsRef<str> for CodeChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+const MIN_CODE_VERIFIER_LENGTH: usize = 43;
+const MAX_CODE_VERIFIER_LENGTH: usize = 128;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_redirect_uri() {
+        assert!(parse_redirect_uri("https://app.example.com/callback").is_ok());
+        assert!(parse_redirect_uri("https://app.example.com/callback?tenant=1").is_ok());
+        assert!(parse_redirect_uri("http://localhost:3000/callback").is_ok());
+        assert!(parse_redirect_uri("http://127.0.0.1/callback").is_ok());
+
+        assert!(parse_redirect_uri("/callback").is_err());
+        assert!(parse_redirect_uri("http://app.example.com/callback").is_err());
+        assert!(parse_redirect_uri("https://app.example.com/callback#token").is_err());
+        assert!(parse_redirect_uri("javascript:alert(1)").is_err());
+    }
+
+    #[test]
+    fn test_parse_scopes() {
+        assert_eq!(
+            parse_scopes("read  write read").unwrap(),
+            vec!["read".to_owned(), "write".to_owned()]
+        );
+        assert!(parse_scopes("").unwrap().is_empty());
+        assert!(parse_scopes("read \"write\"").is_err());
+        assert!(parse_scopes("read\\write").is_err());
+    }
+
+    #[test]
+    fn test_verify_secret() {
+        let secret = ClientSecret::default();
+        let mut client = OAuthClient {
+            id: ClientId::default(),
+            name: "Backend job".to_owned(),
+            redirect_uris: vec![],
+            scopes: vec![],
+            secret_hash: Some(secret.hash()),
+        };
+        assert!(client.is_confidential());
+        assert!(client.verify_secret(secret.as_ref()));
+        assert!(!client.verify_secret(ClientSecret::default().as_ref()));
+
+        client.secret_hash = None;
+        assert!(!client.is_confidential());
+        assert!(!client.verify_secret(secret.as_ref()));
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        // Example from RFC 7636 appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge =
+            CodeChallenge::parse("E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_owned()).unwrap();
+        assert!(challenge.verify(verifier));
+        assert!(!challenge.verify("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXj"));
+        assert!(!challenge.verify("too-short"));
+
+        assert!(CodeChallenge::parse("not a challenge".to_owned()).is_err());
+        assert!(
+            CodeChallenge::parse("E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw".to_owned()).is_err()
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use color_eyre::eyre::{Result, eyre};
+use secrecy::{ExposeSecret, Secret};
+
+// Base32 encoded shared secret of an authenticator app, as shown to the user on enrollment
+#[derive(Clone, Debug)]
+pub struct TotpSecret(Secret<String>);
+
+impl PartialEq for TotpSecret {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl TotpSecret {
+    pub fn parse(s: Secret<String>) -> Result<Self> {
+        // RFC 4226 requires shared secrets of at least 128 bits
+        match totp_rs::Secret::Encoded(s.expose_secret().to_owned()).to_bytes() {
+            Ok(bytes) if bytes.len() >= MIN_TOTP_SECRET_BYTES => Ok(Self(s)),
+            _ => Err(eyre!("Invalid TOTP secret")),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        totp_rs::Secret::Encoded(self.0.expose_secret().to_owned())
+            .to_bytes()
+            .map_err(|e| eyre!("Invalid TOTP secret: {:?}", e))
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        Self(Secret::new(
+            totp_rs::Secret::generate_secret().to_encoded().to_string(),
+        ))
+    }
+}
+
+impl AsRef<Secret<String>> for TotpSecret {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+const MIN_TOTP_SECRET_BYTES: usize = 16;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_secret_can_be_parsed() {
+        let secret = TotpSecret::default();
+        let parsed = TotpSecret::parse(secret.as_ref().clone()).unwrap();
+        assert_eq!(parsed, secret);
+        assert!(parsed.to_bytes().unwrap().len() >= MIN_TOTP_SECRET_BYTES);
+    }
+
+    #[test]
+    fn invalid_secrets_are_rejected() {
+        // Not base32, and too short respectively
+        for s in ["not base32!", "JBSWY3DPEHPK3PXP"] {
+            assert!(TotpSecret::parse(Secret::new(s.to_owned())).is_err());
+        }
+    }
+}
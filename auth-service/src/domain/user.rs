@@ -1,20 +1,188 @@
+use color_eyre::eyre::{Context, Result, eyre};
+use serde::{Deserialize, Serialize};
+
 use super::{Email, Password};
 
-// The User struct should contain 3 fields. email, which is a String;
-// password, which is also a String; and requires_2fa, which is a boolean.
+// A registered account together with its credentials and 2FA settings.
 #[derive(Clone, Debug, PartialEq)]
 pub struct User {
+    // Tokens name users by their id, which unlike the email address never changes
+    pub id: UserId,
     pub email: Email,
     pub password: Password,
     pub requires_2fa: bool,
+    pub status: UserStatus,
+    pub two_fa_method: TwoFAMethod,
+    // Unix timestamp of when the user deleted their account, while it can still be restored
+    pub deleted_at: Option<i64>,
+    // Set by admins. The user can't log in until they reset their password.
+    pub password_reset_required: bool,
 }
 
 impl User {
     pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
         Self {
+            id: UserId::default(),
             email,
             password,
             requires_2fa,
+            // New users have to confirm their address before they can log in
+            status: UserStatus::PendingVerification,
+            two_fa_method: TwoFAMethod::default(),
+            deleted_at: None,
+            password_reset_required: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UserId(String);
+
+impl UserId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = uuid::Uuid::parse_str(&id).wrap_err("Invalid user id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for UserId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// How the second factor is checked for users that require 2FA
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TwoFAMethod {
+    // A one-time code is emailed on every login
+    #[default]
+    Email,
+    // The code comes from an RFC 6238 authenticator app
+    Totp,
+}
+
+impl TwoFAMethod {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "email" => Ok(Self::Email),
+            "totp" => Ok(Self::Totp),
+            _ => Err(eyre!("Invalid 2FA method: {}", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Totp => "totp",
+        }
+    }
+}
+
+// Whether a user can log in. Only active users can.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UserStatus {
+    Active,
+    // Set by admins. Tokens of suspended users are rejected as well.
+    Suspended,
+    // The user hasn't confirmed their email address yet
+    PendingVerification,
+}
+
+impl UserStatus {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "active" => Ok(Self::Active),
+            "suspended" => Ok(Self::Suspended),
+            "pending-verification" => Ok(Self::PendingVerification),
+            _ => Err(eyre!("Invalid user status: {}", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Suspended => "suspended",
+            Self::PendingVerification => "pending-verification",
+        }
+    }
+}
+
+// What a user is allowed to do beyond managing their own account. Roles are listed in
+// the `roles` claim of their tokens, so other services can check them too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // Manages users and their roles
+    Admin,
+}
+
+impl Role {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "admin" => Ok(Self::Admin),
+            _ => Err(eyre!("Invalid role: {}", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+        }
+    }
+
+    // Permissions the role grants, which match the `role_permissions` table
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            Self::Admin => &[
+                Permission::ManageUsers,
+                Permission::ManageRoles,
+                Permission::ManageOAuthClients,
+                Permission::RotateSigningKeys,
+            ],
+        }
+    }
+}
+
+// A single thing roles allow, which routes check for rather than for the roles
+// themselves. Permissions are listed in the `permissions` claim of tokens as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Permission {
+    // Looks up, suspends and reactivates users, and ends their sessions
+    ManageUsers,
+    // Grants and revokes roles
+    ManageRoles,
+    // Registers and deletes OAuth clients
+    #[serde(rename = "manage-oauth-clients")]
+    ManageOAuthClients,
+    RotateSigningKeys,
+}
+
+impl Permission {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "manage-users" => Ok(Self::ManageUsers),
+            "manage-roles" => Ok(Self::ManageRoles),
+            "manage-oauth-clients" => Ok(Self::ManageOAuthClients),
+            "rotate-signing-keys" => Ok(Self::RotateSigningKeys),
+            _ => Err(eyre!("Invalid permission: {}", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ManageUsers => "manage-users",
+            Self::ManageRoles => "manage-roles",
+            Self::ManageOAuthClients => "manage-oauth-clients",
+            Self::RotateSigningKeys => "rotate-signing-keys",
         }
     }
 }
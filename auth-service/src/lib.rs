@@ -1,23 +1,29 @@
 use app_state::AppState;
-use axum::routing::post;
+use axum::routing::{delete, get, post};
 use axum::{
     Json, Router,
     http::Method,
     http::StatusCode,
+    http::header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
+    middleware,
     response::{IntoResponse, Response},
-    serve::Serve,
 };
-use domain::AuthAPIError;
+use domain::{AuthAPIError, BearerTokenError, OAuthError};
 use redis::{Client, RedisResult};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
 use std::error::Error;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
 use tower_http::trace::TraceLayer;
-use tower_http::{cors::CorsLayer, services::ServeDir};
+use tower_http::{
+    cors::{Any, CorsLayer},
+    services::ServeDir,
+};
 
-use crate::utils::{make_span_with_request_id, on_request, on_response};
+use crate::utils::{make_span_with_request_id, on_request, on_response, rate_limit};
 
 pub mod app_state;
 pub mod domain;
@@ -44,6 +50,43 @@ impl IntoResponse for AuthAPIError {
             }
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "JTW is missing"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "JWT is invalid"),
+            AuthAPIError::InvalidPasswordResetToken => (
+                StatusCode::UNAUTHORIZED,
+                "Password reset link is invalid or has expired",
+            ),
+            AuthAPIError::EmailNotVerified => {
+                (StatusCode::FORBIDDEN, "Email address has not been verified")
+            }
+            AuthAPIError::InvalidEmailVerificationToken => (
+                StatusCode::UNAUTHORIZED,
+                "Email verification link is invalid or has expired",
+            ),
+            AuthAPIError::InvalidEmailChangeToken => (
+                StatusCode::UNAUTHORIZED,
+                "Email change link is invalid or has expired",
+            ),
+            AuthAPIError::TwoFANotEnabled => (StatusCode::BAD_REQUEST, "2FA is not enabled"),
+            AuthAPIError::TwoFAAlreadyEnabled => (StatusCode::CONFLICT, "2FA is already enabled"),
+            AuthAPIError::TooManyTwoFAAttempts => (
+                StatusCode::FORBIDDEN,
+                "Too many incorrect 2FA codes, log in again",
+            ),
+            AuthAPIError::AccountLocked => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many failed login attempts, try again later",
+            ),
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::Forbidden => (StatusCode::FORBIDDEN, "Insufficient permissions"),
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
+            AuthAPIError::InvalidRole => (StatusCode::BAD_REQUEST, "Role does not exist"),
+            AuthAPIError::AccountSuspended => (StatusCode::FORBIDDEN, "Account has been suspended"),
+            AuthAPIError::PasswordResetRequired => (
+                StatusCode::FORBIDDEN,
+                "Password has to be reset, a reset link has been emailed",
+            ),
+            AuthAPIError::OAuthClientNotFound => (StatusCode::NOT_FOUND, "OAuth client not found"),
+            AuthAPIError::InvalidRedirectUri => (StatusCode::BAD_REQUEST, "Invalid redirect URI"),
+            AuthAPIError::InvalidScope => (StatusCode::BAD_REQUEST, "Invalid scope"),
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
@@ -52,6 +95,64 @@ impl IntoResponse for AuthAPIError {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+    pub error_description: String,
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        log_error_chain(&self);
+        let status = match self {
+            OAuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthError::ServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let body = Json(OAuthErrorResponse {
+            error: self.code().to_owned(),
+            error_description: self.description().to_owned(),
+        });
+        // Clients that failed to authenticate are told how to (RFC 6749 section 5.2)
+        if let OAuthError::InvalidClient = self {
+            return (status, [(WWW_AUTHENTICATE, r#"Basic realm="oauth""#)], body).into_response();
+        }
+        (status, body).into_response()
+    }
+}
+
+impl IntoResponse for BearerTokenError {
+    fn into_response(self) -> Response {
+        log_error_chain(&self);
+        let (status, challenge, error_message) = match self {
+            BearerTokenError::MissingToken => (
+                StatusCode::UNAUTHORIZED,
+                "Bearer".to_owned(),
+                "Access token is missing",
+            ),
+            BearerTokenError::InvalidToken => (
+                StatusCode::UNAUTHORIZED,
+                r#"Bearer error="invalid_token""#.to_owned(),
+                "Access token is invalid",
+            ),
+            BearerTokenError::InsufficientScope(scope) => (
+                StatusCode::FORBIDDEN,
+                format!(r#"Bearer error="insufficient_scope", scope="{}""#, scope),
+                "Access token lacks the required scope",
+            ),
+            BearerTokenError::UnexpectedError(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Bearer".to_owned(),
+                "Unexpected error",
+            ),
+        };
+        let body = Json(ErrorResponse {
+            error: error_message.to_string(),
+        });
+        (status, [(WWW_AUTHENTICATE, challenge)], body).into_response()
+    }
+}
+
 pub async fn get_postgres_pool(url: &Secret<String>) -> Result<PgPool, sqlx::Error> {
     // Create a new PostgreSQL connection pool
     PgPoolOptions::new()
@@ -60,7 +161,8 @@ pub async fn get_postgres_pool(url: &Secret<String>) -> Result<PgPool, sqlx::Err
         .await
 }
 pub struct Application {
-    server: Serve<Router, Router>,
+    listener: TcpListener,
+    router: Router,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
@@ -79,20 +181,108 @@ impl Application {
         ];
 
         let cors = CorsLayer::new()
-            // Allow GET and POST requests
-            .allow_methods([Method::GET, Method::POST])
+            // Allow GET, POST and DELETE requests
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             // Allow cookies to be included in requests
             .allow_credentials(true)
             .allow_origin(allowed_origins);
+        // OAuth and OpenID Connect clients call these from their own origins, and
+        // authenticate with code verifiers and access tokens rather than cookies
+        let oauth_cors = CorsLayer::new()
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([CONTENT_TYPE, AUTHORIZATION])
+            .allow_origin(Any);
+        let oauth_router = Router::new()
+            .route("/oauth/token", post(routes::token))
+            .route("/introspect", post(routes::introspect))
+            .route("/revoke", post(routes::revoke))
+            .route("/userinfo", get(routes::userinfo).post(routes::userinfo))
+            .route(
+                "/.well-known/openid-configuration",
+                get(routes::openid_configuration),
+            )
+            .route("/.well-known/jwks.json", get(routes::jwks))
+            .route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                rate_limit,
+            ))
+            .with_state(app_state.clone())
+            .layer(oauth_cors);
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(routes::signup))
             .route("/login", post(routes::login))
             .route("/logout", post(routes::logout))
+            .route("/logout-all", post(routes::logout_all))
+            .route("/change-password", post(routes::change_password))
+            .route("/change-email", post(routes::request_email_change))
+            .route("/change-email/confirm", post(routes::confirm_email_change))
+            .route("/account", delete(routes::delete_account))
+            .route("/sessions", get(routes::list_sessions))
+            .route("/sessions/:id", delete(routes::delete_session))
+            .route("/admin/users", get(routes::list_users))
+            .route("/admin/users/:id", get(routes::get_user))
+            .route("/admin/users/:id/suspend", post(routes::suspend_user))
+            .route("/admin/users/:id/reactivate", post(routes::reactivate_user))
+            .route(
+                "/admin/users/:id/password-reset",
+                post(routes::force_password_reset),
+            )
+            .route("/admin/users/:id/2fa/reset", post(routes::reset_2fa))
+            .route(
+                "/admin/users/:id/sessions",
+                delete(routes::revoke_user_sessions),
+            )
+            .route("/admin/users/:id/roles", post(routes::grant_role))
+            .route("/admin/users/:id/roles/:role", delete(routes::revoke_role))
+            .route(
+                "/admin/oauth/clients",
+                get(routes::list_oauth_clients).post(routes::register_oauth_client),
+            )
+            .route(
+                "/admin/oauth/clients/:id",
+                delete(routes::delete_oauth_client),
+            )
+            .route(
+                "/admin/signing-key/rotate",
+                post(routes::rotate_jwt_signing_key),
+            )
             .route("/verify-token", post(routes::verify_token))
             .route("/verify-2fa", post(routes::verify_2fa))
+            .route("/refresh", post(routes::refresh))
+            .route(
+                "/password-reset/request",
+                post(routes::request_password_reset),
+            )
+            .route(
+                "/password-reset/confirm",
+                post(routes::confirm_password_reset),
+            )
+            .route("/verify-email", post(routes::verify_email))
+            .route(
+                "/verify-email/resend",
+                post(routes::resend_verification_email),
+            )
+            .route("/2fa/enable", post(routes::enable_2fa))
+            .route("/2fa/disable", post(routes::disable_2fa))
+            .route("/2fa/totp/enroll", post(routes::enroll_totp))
+            .route("/2fa/totp/confirm", post(routes::confirm_totp))
+            .route(
+                "/2fa/recovery-codes",
+                post(routes::regenerate_recovery_codes),
+            )
+            .route(
+                "/oauth/authorize",
+                get(routes::authorize).post(routes::decide_authorization),
+            )
+            .route("/oauth/clients/:id", get(routes::get_oauth_client))
+            .route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                rate_limit,
+            ))
             .with_state(app_state)
             .layer(cors)
+            .merge(oauth_router)
             // Add a TraceLayer for HTTP requests to enable detailed tracing
             // This layer will create spans for each request using the make_span_with_request_id function,
             // and log events at the start and end of each request using on_request and on_response functions.
@@ -103,18 +293,27 @@ impl Application {
                     .on_response(on_response),
             );
 
-        let listener = tokio::net::TcpListener::bind(address).await?;
+        let listener = TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
 
         // Create a new Application instance and return it
         // DONE
-        Ok(Application { server, address })
+        Ok(Application {
+            listener,
+            router,
+            address,
+        })
     }
 
     pub async fn run(self) -> Result<(), std::io::Error> {
         tracing::info!("listening on {}", &self.address);
-        self.server.await
+        // Connection info gives the routes the client's IP address, which sessions record
+        axum::serve(
+            self.listener,
+            self.router
+                .into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
     }
 }
 
@@ -3,7 +3,11 @@ use std::sync::Arc;
 use auth_service::domain::Email;
 use auth_service::services::PostmarkEmailClient;
 use auth_service::utils::constants::prod;
-use auth_service::utils::{DATABASE_URL, POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME, init_tracing};
+use auth_service::utils::{
+    ACCOUNT_DELETION_GRACE_PERIOD, DATABASE_URL, JWT_KEY_ROTATION_INTERVAL, JWT_SIGNING_KEYS_DIR,
+    POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME, init_tracing, manage_deleted_accounts,
+    manage_signing_keys,
+};
 use auth_service::{Application, get_postgres_pool, get_redis_client};
 use reqwest::Client;
 use secrecy::Secret;
@@ -16,17 +20,49 @@ async fn main() {
     init_tracing().expect("Failed to initialize tracing");
     let pg_pool = configure_postgresql().await;
     let redis_connection = Arc::new(RwLock::new(configure_redis()));
-    let user_store = auth_service::services::PostgresUserStore::new(pg_pool);
+    let user_store = auth_service::services::PostgresUserStore::new(pg_pool.clone());
+    let recovery_code_store =
+        auth_service::services::PostgresRecoveryCodeStore::new(pg_pool.clone());
+    let session_store = auth_service::services::PostgresSessionStore::new(pg_pool.clone());
+    let oauth_client_store = auth_service::services::PostgresOAuthClientStore::new(pg_pool);
     let banned_token_store =
         auth_service::services::RedisBannedTokenStore::new(redis_connection.clone());
-    let two_fa_code_store = auth_service::services::RedisTwoFACodeStore::new(redis_connection);
+    let two_fa_code_store =
+        auth_service::services::RedisTwoFACodeStore::new(redis_connection.clone());
+    let refresh_token_store =
+        auth_service::services::RedisRefreshTokenStore::new(redis_connection.clone());
+    let failed_login_store =
+        auth_service::services::RedisFailedLoginStore::new(redis_connection.clone());
+    let rate_limit_store =
+        auth_service::services::RedisRateLimitStore::new(redis_connection.clone());
+    let authorization_code_store =
+        auth_service::services::RedisAuthorizationCodeStore::new(redis_connection.clone());
+    let password_reset_token_store =
+        auth_service::services::RedisPasswordResetTokenStore::new(redis_connection);
     let email_client = configure_postmark_email_client();
     let app_state = auth_service::app_state::AppState::new(
         Arc::new(RwLock::new(user_store)),
         Arc::new(RwLock::new(banned_token_store)),
         Arc::new(RwLock::new(two_fa_code_store)),
         Arc::new(RwLock::new(email_client)),
+        Arc::new(RwLock::new(refresh_token_store)),
+        Arc::new(RwLock::new(password_reset_token_store)),
+        Arc::new(RwLock::new(recovery_code_store)),
+        Arc::new(RwLock::new(session_store)),
+        Arc::new(RwLock::new(failed_login_store)),
+        Arc::new(RwLock::new(rate_limit_store)),
+        Arc::new(RwLock::new(oauth_client_store)),
+        Arc::new(RwLock::new(authorization_code_store)),
     );
+    if JWT_SIGNING_KEYS_DIR.is_some() {
+        tokio::spawn(manage_signing_keys(*JWT_KEY_ROTATION_INTERVAL));
+    } else if JWT_KEY_ROTATION_INTERVAL.is_some() {
+        panic!("JWT_SIGNING_KEYS_DIR must be set to rotate signing keys.");
+    }
+    tokio::spawn(manage_deleted_accounts(
+        app_state.clone(),
+        *ACCOUNT_DELETION_GRACE_PERIOD,
+    ));
     let app = Application::build(app_state, prod::APP_ADDRESS)
         .await
         .expect("Failed to build app");
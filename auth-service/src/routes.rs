@@ -1,11 +1,43 @@
+mod account;
+mod admin;
+mod change_email;
+mod change_password;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod oauth;
+mod oidc;
+mod password_reset;
+mod recovery_codes;
+mod refresh;
+mod revoke;
+mod sessions;
 mod signup;
+mod totp;
+mod two_fa;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
 
+pub use account::*;
+pub use admin::*;
+pub use change_email::*;
+pub use change_password::*;
+pub use introspect::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use oauth::*;
+pub use oidc::*;
+pub use password_reset::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use revoke::*;
+pub use sessions::*;
 pub use signup::*;
+pub use totp::*;
+pub use two_fa::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
@@ -0,0 +1,88 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use super::login::reauthenticate;
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Password, User},
+    utils::{
+        ACCOUNT_DELETION_GRACE_PERIOD, clear_auth_cookies, get_authenticated_user,
+        revoke_all_sessions,
+    },
+};
+
+// Deletes the logged in user's account, after checking their password. Until the grace
+// period is over, logging in restores the account; after that it is purged.
+#[tracing::instrument(name = "Delete account", skip_all)]
+pub async fn delete_account(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<DeleteAccountRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let user = match authenticate(&jar, request, &state).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(e)),
+    };
+    let email = &user.email;
+
+    if let Err(e) = state.user_store.write().await.delete_user(email).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    let jar = clear_auth_cookies(jar);
+    if let Err(e) = revoke_all_sessions(&user, &state).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e)));
+    }
+
+    let grace_days = ACCOUNT_DELETION_GRACE_PERIOD.as_secs() / (60 * 60 * 24);
+    if let Err(e) = state
+        .email_client
+        .read()
+        .await
+        .send_email(
+            email,
+            "Account deleted",
+            &format!(
+                "Your account has been deleted, and will be removed for good in {} days. \
+                Log in before then to restore it.",
+                grace_days
+            ),
+        )
+        .await
+    {
+        tracing::error!(error = ?e, "failed to send account deletion notice");
+    }
+
+    let response = Json(DeleteAccountResponse {
+        message: format!(
+            "Account has been deleted. Log in within {} days to restore it.",
+            grace_days
+        ),
+    });
+    (jar, Ok((StatusCode::OK, response)))
+}
+
+async fn authenticate(
+    jar: &CookieJar,
+    request: DeleteAccountRequest,
+    state: &AppState,
+) -> Result<User, AuthAPIError> {
+    let user = get_authenticated_user(jar, state).await?;
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    reauthenticate(&user.email, &password, state).await?;
+    Ok(user)
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: Secret<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct DeleteAccountResponse {
+    pub message: String,
+}
@@ -0,0 +1,514 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use super::{password_reset::send_password_reset_link, two_fa::send_security_notice};
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, ClientId, ClientSecret, Email, OAuthClient, OAuthClientStoreError, Role,
+        TwoFACodeStoreError, TwoFAMethod, User, UserId, UserStatus, UserStoreError,
+        parse_redirect_uri, parse_scopes,
+    },
+    utils::{
+        ManageOAuthClients, ManageRoles, ManageUsers, RequirePermission, RotateSigningKeys,
+        revoke_all_sessions, rotate_signing_key,
+    },
+};
+
+const DEFAULT_USERS_PER_PAGE: u64 = 20;
+const MAX_USERS_PER_PAGE: u64 = 100;
+
+// Grants a role to a user. Their tokens only carry it from their next login or refresh.
+#[tracing::instrument(name = "Grant role", skip_all)]
+pub async fn grant_role(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageRoles>,
+    Path(id): Path<String>,
+    Json(request): Json<GrantRoleRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user_id = parse_user_id(id)?;
+    let role = Role::parse(&request.role).map_err(|_| AuthAPIError::InvalidRole)?;
+
+    let mut user_store = state.user_store.write().await;
+    match user_store.grant_role(&user_id, role).await {
+        Ok(()) => {}
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::UserNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+    let roles = user_store
+        .get_roles(&user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(RolesResponse { roles })))
+}
+
+// Revokes a role from a user. Their tokens are revoked as well, since those still carry
+// the role; the user's sessions stay, so a refresh gets them a token without it.
+#[tracing::instrument(name = "Revoke role", skip_all)]
+pub async fn revoke_role(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageRoles>,
+    Path((id, role)): Path<(String, String)>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user_id = parse_user_id(id)?;
+    let role = Role::parse(&role).map_err(|_| AuthAPIError::InvalidRole)?;
+
+    let roles = {
+        let mut user_store = state.user_store.write().await;
+        match user_store.revoke_role(&user_id, role).await {
+            Ok(()) => {}
+            Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::UserNotFound),
+            Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+        }
+        user_store
+            .get_roles(&user_id)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+    };
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .revoke_user_tokens(&user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(RolesResponse { roles })))
+}
+
+// Lists users a page at a time, optionally only those whose email address contains `search`
+#[tracing::instrument(name = "List users", skip_all)]
+pub async fn list_users(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageUsers>,
+    Query(query): Query<ListUsersQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_USERS_PER_PAGE)
+        .clamp(1, MAX_USERS_PER_PAGE);
+    let search = query.search.as_deref().filter(|search| !search.is_empty());
+
+    let (users, total) = state
+        .user_store
+        .read()
+        .await
+        .list_users(search, (page - 1) * per_page, per_page)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(UsersResponse {
+        users: users.iter().map(AdminUserResponse::from).collect(),
+        page,
+        per_page,
+        total,
+    });
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Get user", skip_all)]
+pub async fn get_user(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageUsers>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user_id = parse_user_id(id)?;
+    Ok((StatusCode::OK, Json(user_details(&user_id, &state).await?)))
+}
+
+// Keeps the user from logging in, and ends the sessions they have. Tokens the user still
+// holds are rejected from now on.
+#[tracing::instrument(name = "Suspend user", skip_all)]
+pub async fn suspend_user(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageUsers>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&parse_user_id(id)?, &state).await?;
+    state
+        .user_store
+        .write()
+        .await
+        .set_status(&user.email, UserStatus::Suspended)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    end_user_sessions(&user, &state).await?;
+
+    Ok((StatusCode::OK, Json(user_details(&user.id, &state).await?)))
+}
+
+// Lifts a suspension. Users still waiting for their address to be verified are activated
+// as well, so admins can let in users whose verification email doesn't reach them.
+#[tracing::instrument(name = "Reactivate user", skip_all)]
+pub async fn reactivate_user(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageUsers>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&parse_user_id(id)?, &state).await?;
+    state
+        .user_store
+        .write()
+        .await
+        .set_status(&user.email, UserStatus::Active)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(user_details(&user.id, &state).await?)))
+}
+
+// Ends the user's sessions and emails them a password reset link. They can't log in
+// until they have reset their password.
+#[tracing::instrument(name = "Force password reset", skip_all)]
+pub async fn force_password_reset(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageUsers>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&parse_user_id(id)?, &state).await?;
+    state
+        .user_store
+        .write()
+        .await
+        .require_password_reset(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    end_user_sessions(&user, &state).await?;
+    send_password_reset_link(&user.email, &state).await?;
+
+    Ok((StatusCode::OK, Json(user_details(&user.id, &state).await?)))
+}
+
+// Turns off 2FA for a user that lost their second factor, along with their recovery
+// codes. They can turn it on again once they have logged in.
+#[tracing::instrument(name = "Reset 2FA", skip_all)]
+pub async fn reset_2fa(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageUsers>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&parse_user_id(id)?, &state).await?;
+    if !user.requires_2fa {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_requires_2fa(&user.email, false)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .set_codes(&user.email, Vec::new())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    remove_two_fa_code(&user.email, &state).await?;
+
+    send_security_notice(
+        &user.email,
+        "2FA reset",
+        "Two-factor authentication was turned off for your account by an administrator. \
+        Turn it on again once you have logged in.",
+        &state,
+    )
+    .await;
+
+    Ok((StatusCode::OK, Json(user_details(&user.id, &state).await?)))
+}
+
+// Logs the user out everywhere
+#[tracing::instrument(name = "Revoke user sessions", skip_all)]
+pub async fn revoke_user_sessions(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageUsers>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&parse_user_id(id)?, &state).await?;
+    end_user_sessions(&user, &state).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Starts signing tokens with a new key, ahead of the scheduled rotation
+#[tracing::instrument(name = "Rotate signing key", skip_all)]
+pub async fn rotate_jwt_signing_key(
+    _admin: RequirePermission<RotateSigningKeys>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let kid = rotate_signing_key().map_err(AuthAPIError::UnexpectedError)?;
+    Ok((StatusCode::OK, Json(SigningKeyResponse { kid })))
+}
+
+// Registers an application that can ask users for tokens through `/oauth/authorize`.
+// Confidential clients get a secret as well, which is only ever shown in this response.
+// With it they can get tokens for themselves, so they don't need a redirect URI.
+#[tracing::instrument(name = "Register OAuth client", skip_all)]
+pub async fn register_oauth_client(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageOAuthClients>,
+    Json(request): Json<RegisterOAuthClientRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let name = request.name.trim();
+    if name.is_empty() || (request.redirect_uris.is_empty() && !request.confidential) {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+    if request
+        .redirect_uris
+        .iter()
+        .any(|uri| parse_redirect_uri(uri).is_err())
+    {
+        return Err(AuthAPIError::InvalidRedirectUri);
+    }
+    // Every scope has to be a single valid scope token
+    if request
+        .scopes
+        .iter()
+        .any(|scope| parse_scopes(scope).ok().as_deref() != Some(std::slice::from_ref(scope)))
+    {
+        return Err(AuthAPIError::InvalidScope);
+    }
+
+    let secret = request.confidential.then(ClientSecret::default);
+    let client = OAuthClient {
+        id: ClientId::default(),
+        name: name.to_owned(),
+        redirect_uris: request.redirect_uris,
+        scopes: request.scopes,
+        secret_hash: secret.as_ref().map(ClientSecret::hash),
+    };
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .add_client(client.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(RegisteredOAuthClientResponse {
+            client: OAuthClientResponse::from(client),
+            client_secret: secret.map(|secret| secret.as_ref().expose_secret().to_owned()),
+        }),
+    ))
+}
+
+#[tracing::instrument(name = "List OAuth clients", skip_all)]
+pub async fn list_oauth_clients(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageOAuthClients>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let clients = state
+        .oauth_client_store
+        .read()
+        .await
+        .list_clients()
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    let clients: Vec<OAuthClientResponse> =
+        clients.into_iter().map(OAuthClientResponse::from).collect();
+    Ok((StatusCode::OK, Json(clients)))
+}
+
+// Removes a client along with the consents users gave it. Codes it was issued can no longer
+// be exchanged, and the tokens it got for itself are rejected, but access tokens it already
+// has for users stay valid until they expire.
+#[tracing::instrument(name = "Delete OAuth client", skip_all)]
+pub async fn delete_oauth_client(
+    State(state): State<AppState>,
+    _admin: RequirePermission<ManageOAuthClients>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client_id = ClientId::parse(id).map_err(|_| AuthAPIError::OAuthClientNotFound)?;
+    match state
+        .oauth_client_store
+        .write()
+        .await
+        .delete_client(&client_id)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(OAuthClientStoreError::ClientNotFound) => Err(AuthAPIError::OAuthClientNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Ids that aren't even well formed are reported like unknown ones
+fn parse_user_id(id: String) -> Result<UserId, AuthAPIError> {
+    UserId::parse(id).map_err(|_| AuthAPIError::UserNotFound)
+}
+
+async fn find_user(user_id: &UserId, state: &AppState) -> Result<User, AuthAPIError> {
+    match state.user_store.read().await.get_user_by_id(user_id).await {
+        Ok(user) => Ok(user),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::UserNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// The user as they are stored now, with their roles
+async fn user_details(
+    user_id: &UserId,
+    state: &AppState,
+) -> Result<AdminUserDetailsResponse, AuthAPIError> {
+    let user = find_user(user_id, state).await?;
+    let roles = state
+        .user_store
+        .read()
+        .await
+        .get_roles(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(AdminUserDetailsResponse {
+        user: AdminUserResponse::from(&user),
+        roles,
+    })
+}
+
+// Ends all of the user's sessions, and any login waiting for a 2FA code
+async fn end_user_sessions(user: &User, state: &AppState) -> Result<(), AuthAPIError> {
+    revoke_all_sessions(user, state)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+    remove_two_fa_code(&user.email, state).await
+}
+
+async fn remove_two_fa_code(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    match state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_code(email)
+        .await
+    {
+        Ok(()) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => Ok(()),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GrantRoleRequest {
+    pub role: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct RolesResponse {
+    pub roles: Vec<Role>,
+}
+
+#[derive(Deserialize)]
+pub struct ListUsersQuery {
+    // Pages are numbered from 1
+    pub page: Option<u64>,
+    #[serde(rename = "perPage")]
+    pub per_page: Option<u64>,
+    pub search: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct UsersResponse {
+    pub users: Vec<AdminUserResponse>,
+    pub page: u64,
+    #[serde(rename = "perPage")]
+    pub per_page: u64,
+    // Number of users on all pages
+    pub total: u64,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct AdminUserResponse {
+    pub id: String,
+    pub email: String,
+    pub status: UserStatus,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: TwoFAMethod,
+    #[serde(rename = "passwordResetRequired")]
+    pub password_reset_required: bool,
+    // Unix timestamp of when the user deleted their account, during the grace period
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: Option<i64>,
+}
+
+impl From<&User> for AdminUserResponse {
+    fn from(user: &User) -> Self {
+        Self {
+            id: user.id.as_ref().to_owned(),
+            email: user.email.as_ref().expose_secret().to_owned(),
+            status: user.status,
+            requires_2fa: user.requires_2fa,
+            two_fa_method: user.two_fa_method,
+            password_reset_required: user.password_reset_required,
+            deleted_at: user.deleted_at,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct AdminUserDetailsResponse {
+    #[serde(flatten)]
+    pub user: AdminUserResponse,
+    pub roles: Vec<Role>,
+}
+
+#[derive(Deserialize)]
+pub struct RegisterOAuthClientRequest {
+    pub name: String,
+    #[serde(rename = "redirectUris")]
+    pub redirect_uris: Vec<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // Whether the client gets a secret to authenticate with
+    #[serde(default)]
+    pub confidential: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Deserialize)]
+pub struct OAuthClientResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub name: String,
+    #[serde(rename = "redirectUris")]
+    pub redirect_uris: Vec<String>,
+    pub scopes: Vec<String>,
+    pub confidential: bool,
+}
+
+impl From<OAuthClient> for OAuthClientResponse {
+    fn from(client: OAuthClient) -> Self {
+        Self {
+            confidential: client.is_confidential(),
+            client_id: client.id.as_ref().to_owned(),
+            name: client.name,
+            redirect_uris: client.redirect_uris,
+            scopes: client.scopes,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct RegisteredOAuthClientResponse {
+    #[serde(flatten)]
+    pub client: OAuthClientResponse,
+    // Only set for confidential clients
+    #[serde(rename = "clientSecret", skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct SigningKeyResponse {
+    // Key id of the new signing key
+    pub kid: String,
+}
@@ -0,0 +1,144 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use color_eyre::eyre::Context;
+use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use super::login::reauthenticate;
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Password, UserId, UserStoreError},
+    utils::{
+        AUTH_SERVICE_URL, EMAIL_CHANGE_TOKEN_TTL_SECONDS, generate_email_change_token,
+        get_authenticated_user, revoke_all_sessions, validate_email_change_token,
+    },
+};
+
+// Starts moving the logged in user's account to a new email address. The change only
+// happens once the user follows the link sent to the new address, and the old address
+// is told about it, so a stolen session alone can't take over the account.
+#[tracing::instrument(name = "Request email change", skip_all)]
+pub async fn request_email_change(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = get_authenticated_user(&jar, &state).await?;
+    let email = user.email;
+
+    let new_email = Email::parse(Secret::new(request.new_email))
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    if new_email == email {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    reauthenticate(&email, &password, &state).await?;
+
+    match state.user_store.read().await.get_user(&new_email).await {
+        Ok(_) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(UserStoreError::UserNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let confirm_link =
+        create_email_change_link(&user.id, &new_email).map_err(AuthAPIError::UnexpectedError)?;
+    let content = format!(
+        "Use the following link to confirm {} as the new email address of your account: {}\nThe link expires in {} minutes.",
+        new_email.as_ref().expose_secret(),
+        confirm_link,
+        EMAIL_CHANGE_TOKEN_TTL_SECONDS / 60
+    );
+    let notification = format!(
+        "A change of the email address of your account to {} was requested. It takes effect once confirmed from the new address.\nIf this wasn't you, change your password right away.",
+        new_email.as_ref().expose_secret()
+    );
+    let email_client = state.email_client.read().await;
+    email_client
+        .send_email(&new_email, "Confirm your new email address", &content)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+    email_client
+        .send_email(&email, "Email address change requested", &notification)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(ChangeEmailResponse {
+        message: "A confirmation link has been sent to the new email address.".to_owned(),
+    });
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Confirm email change", skip_all)]
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    Json(request): Json<ChangeEmailConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (user_id, new_email) =
+        validate_email_change_token(&request.token, state.banned_token_store.clone())
+            .await
+            .map_err(|_| AuthAPIError::InvalidEmailChangeToken)?;
+
+    let user = match state.user_store.read().await.get_user_by_id(&user_id).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidEmailChangeToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+    match state.user_store.read().await.get_user(&new_email).await {
+        Ok(_) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(UserStoreError::UserNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // Sessions are kept under the old address, so end every one of them before moving the
+    // account. This also keeps the link from being used again.
+    revoke_all_sessions(&user, &state)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    match state
+        .user_store
+        .write()
+        .await
+        .update_email(&user.email, &new_email)
+        .await
+    {
+        Ok(()) => {}
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidEmailChangeToken),
+        Err(UserStoreError::UserAlreadyExists) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let response = Json(ChangeEmailResponse {
+        message: "Email address has been changed. You can now log in with the new address."
+            .to_owned(),
+    });
+    Ok((StatusCode::OK, response))
+}
+
+fn create_email_change_link(user_id: &UserId, new_email: &Email) -> color_eyre::eyre::Result<Url> {
+    let token = generate_email_change_token(user_id, new_email)?;
+    let mut url = Url::parse(&AUTH_SERVICE_URL).wrap_err("failed to parse AUTH_SERVICE_URL")?;
+    url.query_pairs_mut()
+        .append_pair("changeEmailToken", &token);
+    Ok(url)
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "newEmail")]
+    pub new_email: String,
+    pub password: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailConfirmRequest {
+    pub token: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct ChangeEmailResponse {
+    pub message: String,
+}
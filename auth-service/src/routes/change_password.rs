@@ -0,0 +1,61 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use super::login::reauthenticate;
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Password, SessionId},
+    utils::{get_authenticated_claims, get_claims_user, revoke_other_sessions},
+};
+
+// Changes the logged in user's password, after checking the current one
+#[tracing::instrument(name = "Change password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = get_authenticated_claims(&jar, &state).await?;
+    let email = get_claims_user(&claims, &state).await?.email;
+    let session_id = SessionId::parse(claims.sid).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let current_password =
+        Password::parse(request.current_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let new_password =
+        Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    reauthenticate(&email, &current_password, &state).await?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .update_password(&email, new_password)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // Whoever knew the old password may hold another session, so only this one is kept
+    revoke_other_sessions(&email, &session_id, &state)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(ChangePasswordResponse {
+        message: "Password has been changed.".to_owned(),
+    });
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct ChangePasswordResponse {
+    pub message: String,
+}
@@ -0,0 +1,111 @@
+use axum::{
+    Form, Json,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, OAuthError},
+    utils::{TokenClaims, authenticate_client, validate_token},
+};
+
+// Token introspection (RFC 7662), with which resource servers learn whether a token is
+// active, and who and what it was issued for. Callers have to authenticate as a
+// confidential client. Tokens that aren't valid access tokens, including refresh tokens,
+// are reported as inactive, without saying why.
+#[tracing::instrument(name = "Introspect token", skip_all)]
+pub async fn introspect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<IntrospectionRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let client =
+        authenticate_client(&headers, request.client_id, request.client_secret, &state).await?;
+    // Public clients have no secret, so anyone could claim to be them
+    if !client.is_confidential() {
+        return Err(OAuthError::InvalidClient);
+    }
+    let token = request
+        .token
+        .ok_or(OAuthError::InvalidRequest("token is required"))?;
+
+    // Banned tokens, and tokens of suspended users or deleted clients, are rejected by
+    // `validate_token` as well
+    let response = match validate_token(
+        &token,
+        state.banned_token_store.clone(),
+        state.user_store.clone(),
+        state.session_store.clone(),
+        state.oauth_client_store.clone(),
+    )
+    .await
+    {
+        Ok(TokenClaims::User(claims)) => IntrospectionResponse {
+            active: true,
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            scope: claims.scope,
+            client_id: claims.client_id,
+        },
+        Ok(TokenClaims::Client(claims)) => IntrospectionResponse {
+            active: true,
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            scope: Some(claims.scope),
+            client_id: Some(claims.client_id),
+        },
+        Err(AuthAPIError::UnexpectedError(e)) => return Err(OAuthError::ServerError(e)),
+        Err(_) => IntrospectionResponse::inactive(),
+    };
+
+    Ok((
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(response),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct IntrospectionRequest {
+    // Clients may send a `token_type_hint` along (RFC 7662 section 2.1), which is ignored:
+    // only access tokens can be active, so it isn't needed to find the token
+    pub token: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<Secret<String>>,
+}
+
+// Only `active` is set for inactive tokens (RFC 7662 section 2.2). User tokens from their own
+// login have no scope or client id.
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
+
+impl IntrospectionResponse {
+    fn inactive() -> Self {
+        Self {
+            active: false,
+            sub: None,
+            exp: None,
+            iat: None,
+            scope: None,
+            client_id: None,
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use axum::Json;
+use jsonwebtoken::jwk::JwkSet;
+
+use crate::utils::read_key_ring;
+
+// Public keys other services can validate tokens with, without calling /verify-token.
+// Retired keys are listed until the tokens they signed have expired. Tokens signed with
+// the shared JWT_SECRET can't be validated by others, so it is never listed.
+#[tracing::instrument(name = "JWKS", skip_all)]
+pub async fn jwks() -> Json<JwkSet> {
+    Json(read_key_ring().jwks())
+}
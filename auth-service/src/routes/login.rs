@@ -1,17 +1,21 @@
 use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
 use axum_extra::extract::CookieJar;
+use chrono::Utc;
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, Password, TwoFACode, TwoFAMethod, User, UserStatus,
+    },
+    utils::{ClientInfo, LOGIN_LOCKOUT_DURATION, LOGIN_LOCKOUT_THRESHOLD, start_session},
 };
 
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
+    client_info: ClientInfo,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -24,34 +28,150 @@ pub async fn login(
         _ => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    let user_store = &state.user_store.read().await;
-    if user_store.validate_user(&email, &password).await.is_err() {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    // Passwords can't be guessed faster than failed logins allow. Retrying early is
+    // refused without checking the password, so a correct guess can't be noticed either.
+    if let Err(e) = check_failed_logins(&email, &state).await {
+        return (jar, Err(e));
     }
 
-    let user = match user_store.get_user(&email).await {
+    let validation = state
+        .user_store
+        .read()
+        .await
+        .validate_user(&email, &password)
+        .await;
+    if validation.is_err() {
+        return (jar, Err(add_failed_login(&email, &state).await));
+    }
+    if let Err(e) = state
+        .failed_login_store
+        .write()
+        .await
+        .reset_failed_logins(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    let user = match state.user_store.read().await.get_user(&email).await {
         Ok(user) => user,
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
+    // Only checked once the password is known to be right, so the error doesn't
+    // reveal anything about the account to someone who doesn't own it
+    match user.status {
+        UserStatus::Active => {}
+        UserStatus::Suspended => return (jar, Err(AuthAPIError::AccountSuspended)),
+        UserStatus::PendingVerification => return (jar, Err(AuthAPIError::EmailNotVerified)),
+    }
+    if user.password_reset_required {
+        return (jar, Err(AuthAPIError::PasswordResetRequired));
+    }
+
     // Handle request based on user's 2FA configuration
     match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+        true => handle_2fa(&user.email, user.two_fa_method, &state, jar).await,
+        false => handle_no_2fa(&user, &client_info, &state, jar).await,
+    }
+}
+
+// Checks the password of a logged in user before a sensitive change, so that whoever gets
+// hold of a session can't make it. Wrong passwords count as failed logins, so they can't
+// be guessed this way either.
+#[tracing::instrument(name = "Reauthenticating", skip_all)]
+pub(crate) async fn reauthenticate(
+    email: &Email,
+    password: &Password,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    check_failed_logins(email, state).await?;
+    let validation = state
+        .user_store
+        .read()
+        .await
+        .validate_user(email, password)
+        .await;
+    if validation.is_err() {
+        return Err(add_failed_login(email, state).await);
     }
+    Ok(())
+}
+
+#[tracing::instrument(name = "Checking failed logins", skip_all)]
+async fn check_failed_logins(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    let failed_logins = state
+        .failed_login_store
+        .read()
+        .await
+        .get_failed_logins(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    let retry_at = failed_logins.retry_at(
+        *LOGIN_LOCKOUT_THRESHOLD,
+        LOGIN_LOCKOUT_DURATION.as_secs() as i64,
+    );
+    match retry_at {
+        Some(retry_at) if retry_at > Utc::now().timestamp() => Err(AuthAPIError::AccountLocked),
+        _ => Ok(()),
+    }
+}
+
+// Counts a failed login, locking the account once there were too many in a row.
+// Returns the error to respond with.
+#[tracing::instrument(name = "Adding failed login", skip_all)]
+async fn add_failed_login(email: &Email, state: &AppState) -> AuthAPIError {
+    let failed_logins = match state
+        .failed_login_store
+        .write()
+        .await
+        .add_failed_login(email)
+        .await
+    {
+        Ok(failed_logins) => failed_logins,
+        Err(e) => return AuthAPIError::UnexpectedError(e.into()),
+    };
+    if failed_logins.count != *LOGIN_LOCKOUT_THRESHOLD {
+        return AuthAPIError::IncorrectCredentials;
+    }
+
+    // Failed logins are counted for unknown email addresses too, but only owners of
+    // an account are told about the lockout
+    if state.user_store.read().await.get_user(email).await.is_ok()
+        && let Err(e) = state
+            .email_client
+            .read()
+            .await
+            .send_email(
+                email,
+                "Account locked",
+                &format!(
+                    "Your account was locked for {} minutes after {} failed login attempts. \
+                    If this wasn't you, someone may be trying to guess your password.",
+                    LOGIN_LOCKOUT_DURATION.as_secs() / 60,
+                    failed_logins.count
+                ),
+            )
+            .await
+    {
+        tracing::error!(error = ?e, "failed to send lockout notice");
+    }
+    AuthAPIError::AccountLocked
 }
 
-// New!
 #[tracing::instrument(name = "handle_2fa", skip_all)]
-async fn handle_2fa(
+pub(crate) async fn handle_2fa(
     email: &Email,
+    two_fa_method: TwoFAMethod,
     state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    // First, we must generate a new random login attempt ID and 2FA code
+    // First, we must generate a new random login attempt ID and 2FA code.
+    // Users of an authenticator app never see the code; it is only stored
+    // to keep track of the login attempt.
     let login_attempt_id = LoginAttemptId::default();
     let two_fa_code = TwoFACode::default();
 
@@ -65,16 +185,13 @@ async fn handle_2fa(
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
-    if let Err(e) = state
-        .email_client
-        .write()
-        .await
-        .send_email(
-            &email,
-            "2FA code",
-            &two_fa_code.as_ref().expose_secret().to_string(),
-        )
-        .await
+    if two_fa_method == TwoFAMethod::Email
+        && let Err(e) = state
+            .email_client
+            .write()
+            .await
+            .send_email(email, "2FA code", two_fa_code.as_ref().expose_secret())
+            .await
     {
         return (jar, Err(AuthAPIError::UnexpectedError(e)));
     }
@@ -82,27 +199,27 @@ async fn handle_2fa(
     let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
         message: "2FA required".to_owned(),
         login_attempt_id: login_attempt_id.as_ref().expose_secret().to_owned(), // Add the generated login attempt ID
+        two_fa_method,
     }));
-    // TODO: Return a TwoFactorAuthResponse. The message should be "2FA required".
-    // The login attempt ID should be "123456". We will replace this hard-coded login attempt ID soon!
     (jar, Ok((StatusCode::PARTIAL_CONTENT, response)))
 }
 
-// New!
 #[tracing::instrument(name = "handle_no_2fa", skip_all)]
 async fn handle_no_2fa(
-    email: &Email,
+    user: &User,
+    client_info: &ClientInfo,
+    state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let auth_cookie = match generate_auth_cookie(&email) {
-        Ok(cookie) => cookie,
+    let (auth_cookie, refresh_cookie) = match start_session(user, client_info, false, state).await {
+        Ok(cookies) => cookies,
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
     (
         updated_jar,
         Ok((StatusCode::OK, Json(LoginResponse::RegularAuth))),
@@ -129,4 +246,6 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: TwoFAMethod,
 }
@@ -1,11 +1,13 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use axum_extra::extract::{CookieJar, cookie};
-use secrecy::Secret;
+use axum_extra::extract::CookieJar;
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::{JWT_COOKIE_NAME, validate_token},
+    domain::{AuthAPIError, SessionId},
+    utils::{
+        clear_auth_cookies, get_authenticated_claims, get_authenticated_user, revoke_all_sessions,
+        revoke_session,
+    },
 };
 
 #[tracing::instrument(name = "logout", skip_all)]
@@ -13,29 +15,37 @@ pub async fn logout(
     State(state): State<AppState>,
     jar: CookieJar,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
-    // Retrieve JWT cookie from the `CookieJar`
-    // Return AuthAPIError::MissingToken is the cookie is not found
-    let cookie = match jar.get(JWT_COOKIE_NAME) {
-        Some(cookie) => cookie,
-        _ => return (jar, Err(AuthAPIError::MissingToken)),
+    let claims = match get_authenticated_claims(&jar, &state).await {
+        Ok(claims) => claims,
+        Err(e) => return (jar, Err(e)),
+    };
+    let session_id = match SessionId::parse(claims.sid) {
+        Ok(session_id) => session_id,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
     };
 
-    let token = cookie.value().to_owned();
-
-    // TODO: Validate JWT token by calling `validate_token` from the auth service.
-    // If the token is valid you can ignore the returned claims for now.
-    // Return AuthAPIError::InvalidToken is validation fails.
-    if validate_token(&token, state.banned_token_store.clone())
-        .await
-        .is_err()
-    {
-        return (jar, Err(AuthAPIError::InvalidToken));
+    // Ends the session the token was issued for, including its refresh token family
+    let jar = clear_auth_cookies(jar);
+    match revoke_session(&session_id, claims.jti, &state).await {
+        Ok(()) => (jar, Ok(StatusCode::OK)),
+        Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e))),
     }
+}
+
+#[tracing::instrument(name = "Logout all", skip_all)]
+pub async fn logout_all(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let user = match get_authenticated_user(&jar, &state).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(e)),
+    };
 
-    let jar = jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME));
-    let mut banned_token_store = state.banned_token_store.write().await;
-    match banned_token_store.add_token(Secret::new(token)).await {
+    // Ends every session of the user, on this device and all others
+    let jar = clear_auth_cookies(jar);
+    match revoke_all_sessions(&user, &state).await {
         Ok(()) => (jar, Ok(StatusCode::OK)),
-        Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e))),
     }
 }
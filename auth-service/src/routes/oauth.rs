@@ -0,0 +1,554 @@
+use axum::{
+    Form, Json,
+    extract::{Path, Query, RawQuery, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use url::{Url, form_urlencoded};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthorizationCode, AuthorizationCodeStoreError, AuthorizationGrant, ClientId,
+        CodeChallenge, OAuthClient, OAuthClientStoreError, OAuthError, SessionId,
+        SessionStoreError, UserId, UserStatus, UserStoreError, parse_scopes,
+    },
+    utils::{
+        Claims, OPENID_SCOPE, TOKEN_TTL_SECONDS, authenticate_client, generate_access_token,
+        generate_client_access_token, generate_id_token, get_authenticated_claims,
+        id_tokens_supported,
+    },
+};
+
+// Start of the authorization code flow (RFC 6749 section 4.1.1). Users who aren't logged in
+// are sent to the login page first, and users who haven't agreed to give the client the
+// requested scopes yet to the consent page. Both come back here once they are done.
+#[tracing::instrument(name = "OAuth authorize", skip_all)]
+pub async fn authorize(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    RawQuery(query): RawQuery,
+    Query(params): Query<AuthorizeParams>,
+) -> Result<Response, AuthAPIError> {
+    let request = match check_authorization_request(&params, &state).await {
+        Ok(request) => request,
+        Err(e) => return e.into_redirect(),
+    };
+    let query = query.unwrap_or_default();
+
+    let claims = match get_authenticated_claims(&jar, &state).await {
+        Ok(claims) => claims,
+        Err(AuthAPIError::MissingToken | AuthAPIError::InvalidToken) => {
+            return Ok(Redirect::to(&assets_page("authorize", &query)).into_response());
+        }
+        Err(AuthAPIError::AccountSuspended) => {
+            return request.error(OAuthError::AccessDenied).into_redirect();
+        }
+        Err(e) => return Err(e),
+    };
+
+    let user_id = UserId::parse(claims.sub.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    let consent = state
+        .oauth_client_store
+        .read()
+        .await
+        .get_consent(&user_id, &request.client.id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    if !request.scopes.iter().all(|scope| consent.contains(scope)) {
+        return Ok(Redirect::to(&assets_page("consent", &query)).into_response());
+    }
+
+    let redirect_uri = issue_code(request, &claims, &state).await?;
+    Ok(Redirect::to(&redirect_uri).into_response())
+}
+
+// The user's answer on the consent page. Approving it records the consent, so the user isn't
+// asked again for the same scopes. The page navigates to the returned URI either way.
+#[tracing::instrument(name = "OAuth authorize decision", skip_all)]
+pub async fn decide_authorization(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<AuthorizationDecisionRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = get_authenticated_claims(&jar, &state).await?;
+    let authorization = match check_authorization_request(&request.params, &state).await {
+        Ok(authorization) => authorization,
+        Err(AuthorizationRequestError::Invalid(e)) => return Err(e),
+        Err(AuthorizationRequestError::Redirect(redirect_uri)) => {
+            return Ok((StatusCode::OK, Json(RedirectResponse { redirect_uri })));
+        }
+    };
+
+    if !request.approved {
+        let redirect_uri = match authorization.error(OAuthError::AccessDenied) {
+            AuthorizationRequestError::Redirect(redirect_uri) => redirect_uri,
+            AuthorizationRequestError::Invalid(e) => return Err(e),
+        };
+        return Ok((StatusCode::OK, Json(RedirectResponse { redirect_uri })));
+    }
+
+    let user_id = UserId::parse(claims.sub.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    match state
+        .oauth_client_store
+        .write()
+        .await
+        .grant_consent(&user_id, &authorization.client.id, &authorization.scopes)
+        .await
+    {
+        Ok(()) => {}
+        Err(OAuthClientStoreError::ClientNotFound) => {
+            return Err(AuthAPIError::OAuthClientNotFound);
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let redirect_uri = issue_code(authorization, &claims, &state).await?;
+    Ok((StatusCode::OK, Json(RedirectResponse { redirect_uri })))
+}
+
+// What the consent page shows users about the client asking for their consent
+#[tracing::instrument(name = "Get OAuth client", skip_all)]
+pub async fn get_oauth_client(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client_id = ClientId::parse(id).map_err(|_| AuthAPIError::OAuthClientNotFound)?;
+    let client = find_client(&client_id, &state).await?;
+    Ok((
+        StatusCode::OK,
+        Json(OAuthClientInfoResponse {
+            client_id: client.id.as_ref().to_owned(),
+            name: client.name,
+            scopes: client.scopes,
+        }),
+    ))
+}
+
+// Issues access tokens (RFC 6749 section 3.2). Public clients exchange an authorization code,
+// with the PKCE code verifier proving it was issued to them. Confidential clients
+// authenticate with their secret, and can also get tokens for themselves.
+#[tracing::instrument(name = "OAuth token", skip_all)]
+pub async fn token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<TokenRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let token = match request.grant_type.clone().as_deref() {
+        Some("authorization_code") => {
+            exchange_authorization_code(&headers, request, &state).await?
+        }
+        Some("client_credentials") => issue_client_token(&headers, request, &state).await?,
+        Some(_) => return Err(OAuthError::UnsupportedGrantType),
+        None => return Err(OAuthError::InvalidRequest("grant_type is required")),
+    };
+
+    // Responses with tokens must not be cached (RFC 6749 section 5.1)
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::PRAGMA, "no-cache"),
+        ],
+        Json(token),
+    ))
+}
+
+// Authorization code grant (RFC 6749 section 4.1.3)
+async fn exchange_authorization_code(
+    headers: &HeaderMap,
+    request: TokenRequest,
+    state: &AppState,
+) -> Result<TokenResponse, OAuthError> {
+    let code = request
+        .code
+        .clone()
+        .ok_or(OAuthError::InvalidRequest("code is required"))?;
+    let client = authenticate_client(
+        headers,
+        request.client_id.clone(),
+        request.client_secret.clone(),
+        state,
+    )
+    .await?;
+    let client_id = client.id;
+    let code_verifier = request
+        .code_verifier
+        .ok_or(OAuthError::InvalidRequest("code_verifier is required"))?;
+
+    // Codes are taken before anything else about them is checked, so each can only be tried once
+    let code = AuthorizationCode::parse(code).map_err(|_| OAuthError::InvalidGrant)?;
+    let grant = match state
+        .authorization_code_store
+        .write()
+        .await
+        .take_code(&code)
+        .await
+    {
+        Ok(grant) => grant,
+        Err(AuthorizationCodeStoreError::CodeNotFound) => return Err(OAuthError::InvalidGrant),
+        Err(e) => return Err(OAuthError::ServerError(e.into())),
+    };
+    // The redirect URI has to be repeated if the authorization request named it (RFC 6749
+    // section 4.1.3). Otherwise it's only checked if the client sends it.
+    let redirect_uri_matches = match request.redirect_uri {
+        Some(redirect_uri) => redirect_uri == grant.redirect_uri,
+        None => !grant.redirect_uri_required,
+    };
+    if grant.client_id != client_id
+        || !redirect_uri_matches
+        || !grant.code_challenge.verify(&code_verifier)
+    {
+        return Err(OAuthError::InvalidGrant);
+    }
+
+    // Users who were suspended, or logged out of the session they authorized the client
+    // from, since the code was issued no longer grant access
+    let user = match state
+        .user_store
+        .read()
+        .await
+        .get_user_by_id(&grant.user_id)
+        .await
+    {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(OAuthError::InvalidGrant),
+        Err(e) => return Err(OAuthError::ServerError(e.into())),
+    };
+    if user.status != UserStatus::Active {
+        return Err(OAuthError::InvalidGrant);
+    }
+    let session = match state
+        .session_store
+        .read()
+        .await
+        .get_session(&grant.session_id)
+        .await
+    {
+        Ok(session) if session.email == user.email => session,
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => return Err(OAuthError::InvalidGrant),
+        Err(e) => return Err(OAuthError::ServerError(e.into())),
+    };
+
+    let access_token =
+        generate_access_token(&user.id, &grant.session_id, &client_id, &grant.scopes)
+            .map_err(OAuthError::ServerError)?;
+    // OpenID Connect clients get an ID token as well
+    let id_token = if grant.scopes.iter().any(|scope| scope == OPENID_SCOPE) {
+        let id_token = generate_id_token(&user.id, &client_id, &session, grant.nonce)
+            .map_err(OAuthError::ServerError)?;
+        Some(id_token)
+    } else {
+        None
+    };
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        scope: grant.scopes.join(" "),
+        id_token,
+    })
+}
+
+// Client credentials grant (RFC 6749 section 4.4), with which confidential clients get tokens
+// for themselves rather than for a user
+async fn issue_client_token(
+    headers: &HeaderMap,
+    request: TokenRequest,
+    state: &AppState,
+) -> Result<TokenResponse, OAuthError> {
+    let client = authenticate_client(
+        headers,
+        request.client_id.clone(),
+        request.client_secret.clone(),
+        state,
+    )
+    .await?;
+    if !client.is_confidential() {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+    let scopes = requested_scopes(request.scope.as_deref(), &client)?;
+
+    let access_token =
+        generate_client_access_token(&client.id, &scopes).map_err(OAuthError::ServerError)?;
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        scope: scopes.join(" "),
+        id_token: None,
+    })
+}
+
+// Scopes a client asked for, out of the ones it may request. Clients that don't ask for
+// particular scopes get all of theirs.
+fn requested_scopes(scope: Option<&str>, client: &OAuthClient) -> Result<Vec<String>, OAuthError> {
+    match parse_scopes(scope.unwrap_or_default()) {
+        Ok(scopes) if scopes.is_empty() => Ok(client.scopes.clone()),
+        Ok(scopes) if scopes.iter().all(|scope| client.scopes.contains(scope)) => Ok(scopes),
+        _ => Err(OAuthError::InvalidScope),
+    }
+}
+
+// An authorization request of a known client, with a redirect URI registered for it
+struct AuthorizationRequest {
+    client: OAuthClient,
+    redirect_uri: String,
+    // Whether the client named the redirect URI, rather than leaving it to the default
+    redirect_uri_given: bool,
+    scopes: Vec<String>,
+    state: Option<String>,
+    code_challenge: CodeChallenge,
+    nonce: Option<String>,
+}
+
+impl AuthorizationRequest {
+    fn error(&self, error: OAuthError) -> AuthorizationRequestError {
+        error_redirect(&self.redirect_uri, self.state.as_deref(), error)
+    }
+}
+
+// Reports an error to the client at its redirect URI (RFC 6749 section 4.1.2.1)
+fn error_redirect(
+    redirect_uri: &str,
+    state: Option<&str>,
+    error: OAuthError,
+) -> AuthorizationRequestError {
+    let mut params = vec![
+        ("error", error.code()),
+        ("error_description", error.description()),
+    ];
+    if let Some(state) = state {
+        params.push(("state", state));
+    }
+    match redirect_uri_with(redirect_uri, &params) {
+        Ok(redirect_uri) => AuthorizationRequestError::Redirect(redirect_uri),
+        Err(e) => AuthorizationRequestError::Invalid(e),
+    }
+}
+
+// Requests with an unknown client or redirect URI can't be sent back to where they came
+// from, as that could be anywhere. The user is shown what's wrong with them instead.
+enum AuthorizationRequestError {
+    Invalid(AuthAPIError),
+    Redirect(String),
+}
+
+impl AuthorizationRequestError {
+    fn into_redirect(self) -> Result<Response, AuthAPIError> {
+        match self {
+            AuthorizationRequestError::Invalid(e) => Err(e),
+            AuthorizationRequestError::Redirect(redirect_uri) => {
+                Ok(Redirect::to(&redirect_uri).into_response())
+            }
+        }
+    }
+}
+
+impl From<AuthAPIError> for AuthorizationRequestError {
+    fn from(e: AuthAPIError) -> Self {
+        AuthorizationRequestError::Invalid(e)
+    }
+}
+
+async fn check_authorization_request(
+    params: &AuthorizeParams,
+    state: &AppState,
+) -> Result<AuthorizationRequest, AuthorizationRequestError> {
+    let client_id = params
+        .client_id
+        .clone()
+        .and_then(|id| ClientId::parse(id).ok())
+        .ok_or(AuthAPIError::OAuthClientNotFound)?;
+    let client = find_client(&client_id, state).await?;
+
+    // Clients with a single redirect URI don't have to name it
+    let redirect_uri = match (&params.redirect_uri, client.redirect_uris.as_slice()) {
+        (Some(redirect_uri), registered) if registered.contains(redirect_uri) => {
+            redirect_uri.clone()
+        }
+        (None, [registered]) => registered.clone(),
+        _ => return Err(AuthAPIError::InvalidRedirectUri.into()),
+    };
+
+    let state = params.state.as_deref();
+    match params.response_type.as_deref() {
+        Some("code") => {}
+        Some(_) => {
+            return Err(error_redirect(
+                &redirect_uri,
+                state,
+                OAuthError::UnsupportedResponseType,
+            ));
+        }
+        None => {
+            return Err(error_redirect(
+                &redirect_uri,
+                state,
+                OAuthError::InvalidRequest("response_type is required"),
+            ));
+        }
+    }
+    let code_challenge = match params.code_challenge.clone().map(CodeChallenge::parse) {
+        Some(Ok(code_challenge)) if params.code_challenge_method.as_deref() == Some("S256") => {
+            code_challenge
+        }
+        _ => {
+            return Err(error_redirect(
+                &redirect_uri,
+                state,
+                OAuthError::InvalidRequest("code_challenge is missing or not S256"),
+            ));
+        }
+    };
+
+    let scopes = requested_scopes(params.scope.as_deref(), &client)
+        .map_err(|e| error_redirect(&redirect_uri, state, e))?;
+    if scopes.iter().any(|scope| scope == OPENID_SCOPE) && !id_tokens_supported() {
+        return Err(error_redirect(
+            &redirect_uri,
+            state,
+            OAuthError::InvalidScope,
+        ));
+    }
+
+    Ok(AuthorizationRequest {
+        client,
+        redirect_uri,
+        redirect_uri_given: params.redirect_uri.is_some(),
+        scopes,
+        state: params.state.clone(),
+        code_challenge,
+        nonce: params.nonce.clone(),
+    })
+}
+
+// Issues a code for the authorized request, returning where the user has to take it
+async fn issue_code(
+    request: AuthorizationRequest,
+    claims: &Claims,
+    state: &AppState,
+) -> Result<String, AuthAPIError> {
+    let user_id = UserId::parse(claims.sub.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    let session_id =
+        SessionId::parse(claims.sid.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    let code = AuthorizationCode::default();
+    state
+        .authorization_code_store
+        .write()
+        .await
+        .add_code(
+            code.clone(),
+            AuthorizationGrant {
+                client_id: request.client.id,
+                user_id,
+                session_id,
+                redirect_uri: request.redirect_uri.clone(),
+                redirect_uri_required: request.redirect_uri_given,
+                scopes: request.scopes,
+                code_challenge: request.code_challenge,
+                nonce: request.nonce,
+            },
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let mut params = vec![("code", code.as_ref().expose_secret().as_str())];
+    if let Some(state) = &request.state {
+        params.push(("state", state));
+    }
+    redirect_uri_with(&request.redirect_uri, &params)
+}
+
+async fn find_client(client_id: &ClientId, state: &AppState) -> Result<OAuthClient, AuthAPIError> {
+    match state
+        .oauth_client_store
+        .read()
+        .await
+        .get_client(client_id)
+        .await
+    {
+        Ok(client) => Ok(client),
+        Err(OAuthClientStoreError::ClientNotFound) => Err(AuthAPIError::OAuthClientNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// The redirect URI with the parameters added to its query, keeping the ones it already has
+fn redirect_uri_with(redirect_uri: &str, params: &[(&str, &str)]) -> Result<String, AuthAPIError> {
+    let mut url = Url::parse(redirect_uri).map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    url.query_pairs_mut().extend_pairs(params);
+    Ok(url.into())
+}
+
+// A page of the UI in `assets/`, which gets the authorization request to continue with
+fn assets_page(name: &str, authorization_request: &str) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair(name, authorization_request)
+        .finish();
+    format!("/?{}", query)
+}
+
+#[derive(Deserialize)]
+pub struct AuthorizeParams {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    // OpenID Connect clients' value for the ID token
+    pub nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AuthorizationDecisionRequest {
+    // Parameters of the authorization request the user was asked to consent to
+    #[serde(flatten)]
+    pub params: AuthorizeParams,
+    pub approved: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct RedirectResponse {
+    #[serde(rename = "redirectUri")]
+    pub redirect_uri: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct OAuthClientInfoResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub client_id: Option<String>,
+    // Confidential clients may send their secret here rather than in the Authorization header
+    pub client_secret: Option<Secret<String>>,
+    pub code_verifier: Option<String>,
+    // Scopes a client asks for in the client credentials grant
+    pub scope: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    // Seconds until the access token expires
+    pub expires_in: i64,
+    pub scope: String,
+    // Only issued when the `openid` scope was granted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
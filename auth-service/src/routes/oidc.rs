@@ -0,0 +1,167 @@
+use axum::{Json, extract::State, http::HeaderMap, http::StatusCode, response::IntoResponse};
+use jsonwebtoken::Algorithm;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, BearerTokenError, UserStatus},
+    utils::{
+        AUTH_SERVICE_URL, Claims, JWT_ISSUER, OPENID_SCOPE, TokenClaims, get_bearer_token,
+        get_claims_user, id_tokens_supported, read_key_ring, validate_token,
+    },
+};
+
+// Scope that adds the user's email address to their user info
+const EMAIL_SCOPE: &str = "email";
+
+// OpenID Connect discovery document (OpenID Connect Discovery section 4), which OIDC client
+// libraries configure themselves with
+#[tracing::instrument(name = "OpenID configuration", skip_all)]
+pub async fn openid_configuration() -> Json<OpenIdConfiguration> {
+    let endpoint = |path: &str| format!("{}{}", AUTH_SERVICE_URL.trim_end_matches('/'), path);
+    Json(OpenIdConfiguration {
+        issuer: JWT_ISSUER.to_owned(),
+        authorization_endpoint: endpoint("/oauth/authorize"),
+        token_endpoint: endpoint("/oauth/token"),
+        userinfo_endpoint: endpoint("/userinfo"),
+        introspection_endpoint: endpoint("/introspect"),
+        revocation_endpoint: endpoint("/revoke"),
+        jwks_uri: endpoint("/.well-known/jwks.json"),
+        scopes_supported: match id_tokens_supported() {
+            true => vec![OPENID_SCOPE.to_owned(), EMAIL_SCOPE.to_owned()],
+            false => vec![EMAIL_SCOPE.to_owned()],
+        },
+        response_types_supported: vec!["code".to_owned()],
+        grant_types_supported: vec![
+            "authorization_code".to_owned(),
+            "client_credentials".to_owned(),
+        ],
+        subject_types_supported: vec!["public".to_owned()],
+        id_token_signing_alg_values_supported: vec![read_key_ring().active_key().algorithm()],
+        token_endpoint_auth_methods_supported: vec![
+            "none".to_owned(),
+            "client_secret_basic".to_owned(),
+            "client_secret_post".to_owned(),
+        ],
+        introspection_endpoint_auth_methods_supported: vec![
+            "client_secret_basic".to_owned(),
+            "client_secret_post".to_owned(),
+        ],
+        revocation_endpoint_auth_methods_supported: vec![
+            "none".to_owned(),
+            "client_secret_basic".to_owned(),
+            "client_secret_post".to_owned(),
+        ],
+        code_challenge_methods_supported: vec!["S256".to_owned()],
+        claims_supported: [
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "auth_time",
+            "nonce",
+            "amr",
+            "sid",
+            "email",
+            "email_verified",
+        ]
+        .map(str::to_owned)
+        .to_vec(),
+    })
+}
+
+// Claims about the user an access token was issued for (OpenID Connect Core section 5.3).
+// The token has to be one of an OAuth client that was granted the `openid` scope.
+#[tracing::instrument(name = "User info", skip_all)]
+pub async fn userinfo(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, BearerTokenError> {
+    let token = get_bearer_token(&headers).ok_or(BearerTokenError::MissingToken)?;
+    let claims = validate_token(
+        token,
+        state.banned_token_store.clone(),
+        state.user_store.clone(),
+        state.session_store.clone(),
+        state.oauth_client_store.clone(),
+    )
+    .await
+    .map_err(into_bearer_token_error)?;
+
+    // The user's own auth tokens carry no scopes, and are no access tokens of a client.
+    // Clients' tokens for themselves have no user to tell about.
+    let (claims, scopes): (_, Vec<&str>) = match &claims {
+        TokenClaims::User(
+            claims @ Claims {
+                client_id: Some(_),
+                scope: Some(scope),
+                ..
+            },
+        ) => (claims, scope.split(' ').collect()),
+        _ => return Err(BearerTokenError::InvalidToken),
+    };
+    if !scopes.contains(&OPENID_SCOPE) {
+        return Err(BearerTokenError::InsufficientScope(OPENID_SCOPE));
+    }
+
+    let user = get_claims_user(claims, &state)
+        .await
+        .map_err(into_bearer_token_error)?;
+    let (email, email_verified) = if scopes.contains(&EMAIL_SCOPE) {
+        (
+            Some(user.email.as_ref().expose_secret().to_owned()),
+            Some(user.status != UserStatus::PendingVerification),
+        )
+    } else {
+        (None, None)
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(UserInfoResponse {
+            sub: user.id.as_ref().to_owned(),
+            email,
+            email_verified,
+        }),
+    ))
+}
+
+fn into_bearer_token_error(e: AuthAPIError) -> BearerTokenError {
+    match e {
+        AuthAPIError::UnexpectedError(e) => BearerTokenError::UnexpectedError(e),
+        _ => BearerTokenError::InvalidToken,
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    // Token introspection and revocation endpoints (RFC 8414 section 2)
+    pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub jwks_uri: String,
+    pub scopes_supported: Vec<String>,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<Algorithm>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub introspection_endpoint_auth_methods_supported: Vec<String>,
+    pub revocation_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct UserInfoResponse {
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+}
@@ -0,0 +1,158 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use color_eyre::eyre::Context;
+use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Password, PasswordResetToken, UserStoreError},
+    utils::{AUTH_SERVICE_URL, PASSWORD_RESET_TOKEN_TTL_SECONDS, revoke_all_sessions},
+};
+
+#[tracing::instrument(name = "Request password reset", skip_all)]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = match Email::parse(Secret::new(request.email)) {
+        Ok(email) => email,
+        Err(_) => return Err(AuthAPIError::InvalidCredentials),
+    };
+
+    // The response is the same whether or not an account exists for the email,
+    // so this route can't be used to find out which addresses are registered.
+    let response = Json(PasswordResetResponse {
+        message: "If an account exists for this email, a password reset link has been sent."
+            .to_owned(),
+    });
+
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Ok((StatusCode::OK, response)),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    send_password_reset_link(&email, &state).await?;
+    Ok((StatusCode::OK, response))
+}
+
+// Emails the user a link to reset their password with, replacing any earlier link
+#[tracing::instrument(name = "Sending password reset link", skip_all)]
+pub(crate) async fn send_password_reset_link(
+    email: &Email,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let token = PasswordResetToken::default();
+    if let Err(e) = state
+        .password_reset_token_store
+        .write()
+        .await
+        .add_token(email.clone(), token.clone())
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    let reset_link =
+        create_password_reset_link(email, &token).map_err(AuthAPIError::UnexpectedError)?;
+    let content = format!(
+        "Use the following link to reset your password: {}\nThe link expires in {} minutes.",
+        reset_link,
+        PASSWORD_RESET_TOKEN_TTL_SECONDS / 60
+    );
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(email, "Password reset", &content)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+#[tracing::instrument(name = "Confirm password reset", skip_all)]
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = match Email::parse(Secret::new(request.email)) {
+        Ok(email) => email,
+        Err(_) => return Err(AuthAPIError::InvalidCredentials),
+    };
+    let token = match PasswordResetToken::parse(request.token) {
+        Ok(token) => token,
+        Err(_) => return Err(AuthAPIError::InvalidCredentials),
+    };
+    let new_password = match Password::parse(request.new_password) {
+        Ok(password) => password,
+        Err(_) => return Err(AuthAPIError::InvalidCredentials),
+    };
+
+    {
+        let mut password_reset_token_store = state.password_reset_token_store.write().await;
+        match password_reset_token_store.get_token(&email).await {
+            Ok(stored_token) if stored_token == token => {}
+            _ => return Err(AuthAPIError::InvalidPasswordResetToken),
+        }
+        // The link is single-use: consume the token before touching the password
+        if let Err(e) = password_reset_token_store.remove_token(&email).await {
+            return Err(AuthAPIError::UnexpectedError(e.into()));
+        }
+    }
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidPasswordResetToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+    match state
+        .user_store
+        .write()
+        .await
+        .update_password(&email, new_password)
+        .await
+    {
+        Ok(()) => {}
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidPasswordResetToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // Whoever knew the old password may still hold a session, so end all of them
+    revoke_all_sessions(&user, &state)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(PasswordResetResponse {
+        message: "Password has been reset.".to_owned(),
+    });
+    Ok((StatusCode::OK, response))
+}
+
+fn create_password_reset_link(
+    email: &Email,
+    token: &PasswordResetToken,
+) -> color_eyre::eyre::Result<Url> {
+    let mut url = Url::parse(&AUTH_SERVICE_URL).wrap_err("failed to parse AUTH_SERVICE_URL")?;
+    url.query_pairs_mut()
+        .append_pair("email", email.as_ref().expose_secret())
+        .append_pair("resetToken", token.as_ref().expose_secret());
+    Ok(url)
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub email: String,
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct PasswordResetResponse {
+    pub message: String,
+}
@@ -0,0 +1,47 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use super::login::reauthenticate;
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Password},
+    utils::{generate_recovery_codes, get_authenticated_user},
+};
+
+// Hands the logged in user a new set of recovery codes, invalidating the old ones, after
+// checking their password. Recovery codes stand in for the second factor, so whoever got
+// hold of a session must not be able to get a set of their own.
+#[tracing::instrument(name = "Regenerate recovery codes", skip_all)]
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<RegenerateRecoveryCodesRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = get_authenticated_user(&jar, &state).await?;
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    reauthenticate(&user.email, &password, &state).await?;
+    if !user.requires_2fa {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let recovery_codes = generate_recovery_codes(&user.email, state.recovery_code_store.clone())
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(RecoveryCodesResponse { recovery_codes });
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct RegenerateRecoveryCodesRequest {
+    pub password: Secret<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
@@ -0,0 +1,116 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, RefreshToken, RefreshTokenStoreError, SessionStoreError, UserStatus,
+        UserStoreError,
+    },
+    utils::{
+        ClientInfo, REFRESH_TOKEN_COOKIE_NAME, clear_auth_cookies, create_refresh_cookie,
+        generate_auth_cookie,
+    },
+};
+
+#[tracing::instrument(name = "Refresh", skip_all)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    client_info: ClientInfo,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let cookie = match jar.get(REFRESH_TOKEN_COOKIE_NAME) {
+        Some(cookie) => cookie,
+        None => return (jar, Err(AuthAPIError::MissingToken)),
+    };
+
+    let refresh_token = match RefreshToken::parse(cookie.value().to_owned()) {
+        Ok(refresh_token) => refresh_token,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // Every refresh rotates the token: the presented token is retired and a new one
+    // from the same family is handed back together with a fresh JWT.
+    let new_refresh_token = RefreshToken::default();
+    let rotation_result = state
+        .refresh_token_store
+        .write()
+        .await
+        .rotate_token(&refresh_token, new_refresh_token.clone())
+        .await;
+
+    let (email, session_id) = match rotation_result {
+        Ok(rotated) => rotated,
+        Err(RefreshTokenStoreError::TokenReused) => {
+            tracing::warn!("Rotated refresh token was reused, its token family has been revoked");
+            return (clear_auth_cookies(jar), Err(AuthAPIError::InvalidToken));
+        }
+        Err(RefreshTokenStoreError::TokenNotFound) => {
+            return (clear_auth_cookies(jar), Err(AuthAPIError::InvalidToken));
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => {
+            return (clear_auth_cookies(jar), Err(AuthAPIError::InvalidToken));
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+    if user.status == UserStatus::Suspended {
+        return (clear_auth_cookies(jar), Err(AuthAPIError::AccountSuspended));
+    }
+
+    // Roles and their permissions are looked up again, so the new token reflects grants and
+    // revocations since the last one was issued
+    let roles = match state.user_store.read().await.get_roles(&user.id).await {
+        Ok(roles) => roles,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+    let permissions = match state
+        .user_store
+        .read()
+        .await
+        .get_permissions(&user.id)
+        .await
+    {
+        Ok(permissions) => permissions,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    let (auth_cookie, jti) = match generate_auth_cookie(&user.id, &roles, &permissions, &session_id)
+    {
+        Ok(cookie) => cookie,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let touch_result = state
+        .session_store
+        .write()
+        .await
+        .touch_session(&session_id, jti, client_info.ip)
+        .await;
+    match touch_result {
+        Ok(()) => {}
+        // The session has ended, so its token family goes with it
+        Err(SessionStoreError::SessionNotFound) => {
+            if let Err(e) = state
+                .refresh_token_store
+                .write()
+                .await
+                .revoke_session(&session_id)
+                .await
+            {
+                return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+            }
+            return (clear_auth_cookies(jar), Err(AuthAPIError::InvalidToken));
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    let updated_jar = jar
+        .add(auth_cookie)
+        .add(create_refresh_cookie(&new_refresh_token));
+    (updated_jar, Ok(StatusCode::OK))
+}
@@ -0,0 +1,86 @@
+use axum::{
+    Form,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, OAuthError, RefreshToken, RefreshTokenStoreError},
+    utils::{TokenClaims, authenticate_client, validate_token},
+};
+
+// Token revocation (RFC 7009). Access tokens are banned, and refresh tokens revoked along with
+// the rest of their family. Tokens that are already invalid are reported as revoked, as there
+// is nothing left to do about them (RFC 7009 section 2.2).
+#[tracing::instrument(name = "Revoke token", skip_all)]
+pub async fn revoke(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<RevocationRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let client =
+        authenticate_client(&headers, request.client_id, request.client_secret, &state).await?;
+    let token = request
+        .token
+        .ok_or(OAuthError::InvalidRequest("token is required"))?;
+
+    // Refresh tokens are opaque, so they can't be taken for a JWT or the other way around.
+    // They are only issued for users' own logins, so whoever holds one may revoke it.
+    if let Ok(refresh_token) = RefreshToken::parse(token.clone()) {
+        return match state
+            .refresh_token_store
+            .write()
+            .await
+            .revoke_token(&refresh_token)
+            .await
+        {
+            Ok(()) | Err(RefreshTokenStoreError::TokenNotFound) => Ok(StatusCode::OK),
+            Err(e) => Err(OAuthError::ServerError(e.into())),
+        };
+    }
+
+    let claims = match validate_token(
+        &token,
+        state.banned_token_store.clone(),
+        state.user_store.clone(),
+        state.session_store.clone(),
+        state.oauth_client_store.clone(),
+    )
+    .await
+    {
+        Ok(claims) => claims,
+        Err(AuthAPIError::UnexpectedError(e)) => return Err(OAuthError::ServerError(e)),
+        Err(_) => return Ok(StatusCode::OK),
+    };
+    // Clients can only revoke the tokens issued to them (RFC 7009 section 2.1). Users' own
+    // tokens can be revoked by whoever holds them, as they could log out with them too.
+    let issued_to = match &claims {
+        TokenClaims::User(claims) => claims.client_id.as_deref(),
+        TokenClaims::Client(claims) => Some(claims.client_id.as_str()),
+    };
+    if issued_to.is_some_and(|client_id| client_id != client.id.as_ref()) {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .add_token(claims.jti().to_owned())
+        .await
+        .map_err(|e| OAuthError::ServerError(e.into()))?;
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct RevocationRequest {
+    // Clients may send a `token_type_hint` along (RFC 7009 section 2.1), which is ignored:
+    // access and refresh tokens are told apart by their format
+    pub token: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<Secret<String>>,
+}
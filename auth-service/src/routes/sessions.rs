@@ -0,0 +1,117 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, SessionId, SessionStoreError},
+    utils::{clear_auth_cookies, get_authenticated_claims, get_claims_user, revoke_session},
+};
+
+// Lists the logged in user's sessions, marking the one the request was made from
+#[tracing::instrument(name = "List sessions", skip_all)]
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = get_authenticated_claims(&jar, &state).await?;
+    let user = get_claims_user(&claims, &state).await?;
+
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_user_sessions(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionResponse {
+            current: session.id.as_ref() == claims.sid,
+            id: session.id.as_ref().to_owned(),
+            device: session.device,
+            ip: session.ip,
+            user_agent: session.user_agent,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(SessionsResponse { sessions })))
+}
+
+// Ends one of the logged in user's sessions. Ending the current session logs the user out.
+#[tracing::instrument(name = "Delete session", skip_all)]
+pub async fn delete_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let claims = match get_authenticated_claims(&jar, &state).await {
+        Ok(claims) => claims,
+        Err(e) => return (jar, Err(e)),
+    };
+    let user = match get_claims_user(&claims, &state).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(e)),
+    };
+    let session_id = match SessionId::parse(id) {
+        Ok(session_id) => session_id,
+        Err(_) => return (jar, Err(AuthAPIError::SessionNotFound)),
+    };
+
+    let session = match state
+        .session_store
+        .read()
+        .await
+        .get_session(&session_id)
+        .await
+    {
+        Ok(session) => session,
+        Err(SessionStoreError::SessionNotFound) => {
+            return (jar, Err(AuthAPIError::SessionNotFound));
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+    // Other users' sessions are reported as missing, so their ids can't be probed
+    if session.email != user.email {
+        return (jar, Err(AuthAPIError::SessionNotFound));
+    }
+
+    if let Err(e) = revoke_session(&session.id, session.jti, &state).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e)));
+    }
+
+    let jar = match session.id.as_ref() == claims.sid {
+        true => clear_auth_cookies(jar),
+        false => jar,
+    };
+    (jar, Ok(StatusCode::NO_CONTENT))
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct SessionResponse {
+    pub id: String,
+    pub device: Option<String>,
+    pub ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    // Unix timestamps
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: i64,
+    // Whether this is the session the request was made from
+    pub current: bool,
+}
@@ -1,10 +1,16 @@
 use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use color_eyre::eyre::Context;
+use reqwest::Url;
 use secrecy::Secret;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
     domain::{AuthAPIError, Email, Password, user},
+    utils::{
+        AUTH_SERVICE_URL, EMAIL_VERIFICATION_TOKEN_TTL_SECONDS, generate_email_verification_token,
+        generate_recovery_codes,
+    },
 };
 
 #[tracing::instrument(name = "Signup", skip_all)]
@@ -21,19 +27,71 @@ pub async fn signup(
         _ => return Err(AuthAPIError::InvalidCredentials),
     };
 
-    let user = user::User::new(email, password, request.requires_2fa);
+    let user = user::User::new(email.clone(), password, request.requires_2fa);
     let mut user_store = state.user_store.write().await;
     if let Err(e) = user_store.add_user(user).await {
         return Err(AuthAPIError::UnexpectedError(e.into()));
     }
+
+    send_email_verification_link(&email, &state).await?;
+
+    // Users signing up with 2FA get their recovery codes right away
+    let recovery_codes = match request.requires_2fa {
+        true => Some(
+            generate_recovery_codes(&email, state.recovery_code_store.clone())
+                .await
+                .map_err(AuthAPIError::UnexpectedError)?,
+        ),
+        false => None,
+    };
+
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
+        recovery_codes,
     });
     Ok((StatusCode::CREATED, response))
 }
+
+// Emails the user a link to verify their email address with. Links sent earlier stay valid
+// until they expire.
+#[tracing::instrument(name = "Sending email verification link", skip_all)]
+pub(crate) async fn send_email_verification_link(
+    email: &Email,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let verification_link =
+        create_email_verification_link(email).map_err(AuthAPIError::UnexpectedError)?;
+    let content = format!(
+        "Use the following link to verify your email address: {}\nThe link expires in {} hours.",
+        verification_link,
+        EMAIL_VERIFICATION_TOKEN_TTL_SECONDS / 3600
+    );
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(email, "Verify your email address", &content)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+fn create_email_verification_link(email: &Email) -> color_eyre::eyre::Result<Url> {
+    let token = generate_email_verification_token(email)?;
+    let mut url = Url::parse(&AUTH_SERVICE_URL).wrap_err("failed to parse AUTH_SERVICE_URL")?;
+    url.query_pairs_mut()
+        .append_pair("verifyEmailToken", &token);
+    Ok(url)
+}
+
 #[derive(Serialize, Debug, PartialEq, Deserialize)]
 pub struct SignupResponse {
     pub message: String,
+    #[serde(
+        rename = "recoveryCodes",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub recovery_codes: Option<Vec<String>>,
 }
 #[derive(Deserialize)]
 pub struct SignupRequest {
@@ -0,0 +1,121 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use super::login::reauthenticate;
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Password, TotpSecret, UserStoreError},
+    utils::{
+        create_totp_uri, generate_recovery_codes, get_authenticated_user,
+        is_valid_totp_code_format, verify_totp_code,
+    },
+};
+
+// Starts TOTP enrollment for the logged in user, after checking their password. The secret
+// only becomes their second factor once a code from the authenticator app is confirmed.
+// Without the password check, whoever got hold of a session could swap in an authenticator
+// app of their own.
+#[tracing::instrument(name = "Enroll TOTP", skip_all)]
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<EnrollTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_user(&jar, &state).await?.email;
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    reauthenticate(&email, &password, &state).await?;
+
+    let secret = TotpSecret::default();
+    let otpauth_uri = create_totp_uri(&secret, &email).map_err(AuthAPIError::UnexpectedError)?;
+
+    if let Err(e) = state
+        .user_store
+        .write()
+        .await
+        .set_pending_totp_secret(&email, secret.clone())
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    let response = Json(EnrollTotpResponse {
+        otpauth_uri,
+        secret: secret.as_ref().expose_secret().to_owned(),
+    });
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Confirm TOTP", skip_all)]
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_user(&jar, &state).await?.email;
+
+    if !is_valid_totp_code_format(&request.code) {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let mut user_store = state.user_store.write().await;
+    let secret = match user_store.get_pending_totp_secret(&email).await {
+        Ok(secret) => secret,
+        Err(UserStoreError::TotpSecretNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let step = match verify_totp_code(&secret, &email, &request.code) {
+        Ok(Some(step)) => step,
+        Ok(None) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e)),
+    };
+    // The code confirming the app can't be used to log in as well
+    match user_store.use_totp_step(&email, step).await {
+        Ok(()) => {}
+        Err(UserStoreError::TotpCodeReused) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    if let Err(e) = user_store.enable_totp(&email).await {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    let recovery_codes = generate_recovery_codes(&email, state.recovery_code_store.clone())
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(ConfirmTotpResponse {
+        message: "Authenticator app enabled for 2FA.".to_owned(),
+        recovery_codes,
+    });
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct EnrollTotpRequest {
+    pub password: Secret<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct EnrollTotpResponse {
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+    pub secret: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpRequest {
+    pub code: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct ConfirmTotpResponse {
+    pub message: String,
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
@@ -0,0 +1,185 @@
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    login::{handle_2fa, reauthenticate},
+    verify_2fa::{add_failed_two_fa_attempt, check_two_fa_code, is_valid_two_fa_code_format},
+};
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Password},
+    utils::{generate_recovery_codes, get_authenticated_user},
+};
+
+// Turns on 2FA for the logged in user, after checking their password. Codes are emailed
+// to them from then on, unless they enroll an authenticator app.
+#[tracing::instrument(name = "Enable 2FA", skip_all)]
+pub async fn enable_2fa(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<Enable2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = get_authenticated_user(&jar, &state).await?;
+    let email = &user.email;
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    reauthenticate(email, &password, &state).await?;
+    if user.requires_2fa {
+        return Err(AuthAPIError::TwoFAAlreadyEnabled);
+    }
+
+    if let Err(e) = state
+        .user_store
+        .write()
+        .await
+        .set_requires_2fa(email, true)
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    let recovery_codes = generate_recovery_codes(email, state.recovery_code_store.clone())
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    send_security_notice(
+        email,
+        "2FA enabled",
+        "Two-factor authentication was turned on for your account. \
+        If this wasn't you, reset your password right away.",
+        &state,
+    )
+    .await;
+
+    let response = Json(Enable2FAResponse {
+        message: "2FA enabled.".to_owned(),
+        recovery_codes,
+    });
+    Ok((StatusCode::OK, response))
+}
+
+// Turns off 2FA for the logged in user, after checking their password and a code from
+// their second factor. Without a code, the check is started like a login would: the
+// response is the same as login's 206, and users of emailed codes get one sent.
+#[tracing::instrument(name = "Disable 2FA", skip_all)]
+pub async fn disable_2fa(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<Disable2FARequest>,
+) -> Result<Response, AuthAPIError> {
+    let user = get_authenticated_user(&jar, &state).await?;
+    let email = &user.email;
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    if let Some(code) = &request.two_fa_code
+        && !is_valid_two_fa_code_format(code)
+    {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+    reauthenticate(email, &password, &state).await?;
+    if !user.requires_2fa {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let Some(code) = request.two_fa_code else {
+        let (_, result) = handle_2fa(email, user.two_fa_method, &state, jar).await;
+        return result.map(IntoResponse::into_response);
+    };
+
+    {
+        let mut two_fa_code_store = state.two_fa_code_store.write().await;
+        let (_, stored_two_fa_code) = two_fa_code_store
+            .get_code(email)
+            .await
+            .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+        if !check_two_fa_code(&user, code, &stored_two_fa_code, &state).await? {
+            return Err(add_failed_two_fa_attempt(email, &mut *two_fa_code_store).await);
+        }
+        if let Err(e) = two_fa_code_store.remove_code(email).await {
+            return Err(AuthAPIError::UnexpectedError(e.into()));
+        }
+    }
+
+    if let Err(e) = state
+        .user_store
+        .write()
+        .await
+        .set_requires_2fa(email, false)
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+    // Recovery codes stand in for a second factor the user no longer has
+    if let Err(e) = state
+        .recovery_code_store
+        .write()
+        .await
+        .set_codes(email, Vec::new())
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    send_security_notice(
+        email,
+        "2FA disabled",
+        "Two-factor authentication was turned off for your account, so only your password \
+        is needed to log in. If this wasn't you, reset your password right away.",
+        &state,
+    )
+    .await;
+
+    let response = Json(Disable2FAResponse {
+        message: "2FA disabled.".to_owned(),
+    });
+    Ok((StatusCode::OK, response).into_response())
+}
+
+// The change is made at this point, so failing to send the notice must not fail the request
+pub(crate) async fn send_security_notice(
+    email: &Email,
+    subject: &str,
+    content: &str,
+    state: &AppState,
+) {
+    if let Err(e) = state
+        .email_client
+        .read()
+        .await
+        .send_email(email, subject, content)
+        .await
+    {
+        tracing::error!(error = ?e, "failed to send security notice");
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Enable2FARequest {
+    pub password: Secret<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct Enable2FAResponse {
+    pub message: String,
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Disable2FARequest {
+    pub password: Secret<String>,
+    #[serde(rename = "2FACode", default)]
+    pub two_fa_code: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct Disable2FAResponse {
+    pub message: String,
+}
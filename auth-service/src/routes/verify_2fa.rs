@@ -5,14 +5,18 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::generate_auth_cookie,
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, RecoveryCode, RecoveryCodeStoreError, TwoFACode,
+        TwoFACodeStore, TwoFACodeStoreError, TwoFAMethod, User, UserStatus, UserStoreError,
+    },
+    utils::{ClientInfo, is_valid_totp_code_format, start_session, verify_totp_code},
 };
 
 #[tracing::instrument(name = "verify_2fa", skip_all)]
 pub async fn verify_2fa(
     jar: CookieJar,
-    State(state): State<AppState>, // New!
+    State(state): State<AppState>,
+    client_info: ClientInfo,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let email = match Email::parse(Secret::new(request.email)) {
@@ -26,42 +30,188 @@ pub async fn verify_2fa(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     }; // Validate the login attempt ID in `request`
 
-    let two_fa_code = match TwoFACode::parse(request.two_fa_code) {
-        Ok(two_fa_code) => two_fa_code,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
-    };
+    // Codes of both 2FA methods are 6 digits, so malformed ones can be rejected
+    // before looking up which method the user has
+    if !is_valid_two_fa_code_format(&request.two_fa_code) {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    }
 
-    // New!
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+    // The account may have been suspended since the password was checked
+    if user.status == UserStatus::Suspended {
+        return (jar, Err(AuthAPIError::AccountSuspended));
+    }
 
     // Call `two_fa_code_store.get_code`. If the call fails
     // return a `AuthAPIError::IncorrectCredentials`.
-    let (stored_login_attempt_id, stored_two_fa_cde) =
-        match two_fa_code_store.get_code(&email).await {
+    let (stored_login_attempt_id, stored_two_fa_code) =
+        match state.two_fa_code_store.read().await.get_code(&email).await {
             Ok(code_tuple) => code_tuple,
             Err(_) => {
                 return (jar, Err(AuthAPIError::IncorrectCredentials));
             }
         };
 
-    // TODO: Validate that the `login_attempt_id` and `two_fa_code`
-    // in the request body matches values in the `code_tuple`.
-    // If not, return a `AuthAPIError::IncorrectCredentials`.
-    if two_fa_code == stored_two_fa_cde && login_attempt_id == stored_login_attempt_id {
-        let auth_cookie = match generate_auth_cookie(&email) {
-            Ok(cookie) => cookie,
-            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    // The login attempt is checked first, so a recovery code isn't used up by a stale attempt.
+    if login_attempt_id != stored_login_attempt_id {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    }
+
+    // Checking the code goes to the user and recovery code stores, so the 2FA code store
+    // isn't held meanwhile
+    let code_matches =
+        match check_two_fa_code(&user, request.two_fa_code, &stored_two_fa_code, &state).await {
+            Ok(code_matches) => code_matches,
+            Err(e) => return (jar, Err(e)),
         };
 
-        match two_fa_code_store.remove_code(&email).await {
-            Ok(()) => {}
-            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    // The login attempt may have ended, or a new one started, while the code was checked
+    match two_fa_code_store.get_code(&email).await {
+        Ok((current_login_attempt_id, _)) if current_login_attempt_id == login_attempt_id => {}
+        Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return (jar, Err(AuthAPIError::IncorrectCredentials));
         }
-        let updated_jar = jar.add(auth_cookie);
-        (updated_jar, Ok(StatusCode::OK.into_response()))
-    } else {
-        (jar, Err(AuthAPIError::IncorrectCredentials))
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    if !code_matches {
+        return (
+            jar,
+            Err(add_failed_two_fa_attempt(&email, &mut *two_fa_code_store).await),
+        );
+    }
+    if let Err(e) = two_fa_code_store.remove_code(&email).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+    drop(two_fa_code_store);
+
+    let (auth_cookie, refresh_cookie) = match start_session(&user, &client_info, true, &state).await
+    {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
+    (updated_jar, Ok(StatusCode::OK.into_response()))
+}
+
+// A recovery code can be given in place of the 2FA code
+pub(crate) fn is_valid_two_fa_code_format(code: &str) -> bool {
+    RecoveryCode::parse(code.to_owned()).is_ok() || is_valid_totp_code_format(code)
+}
+
+// Returns whether the code proves the user has their second factor: the code emailed to
+// them, a code from their authenticator app, or one of their recovery codes
+#[tracing::instrument(name = "Checking 2FA code", skip_all)]
+pub(crate) async fn check_two_fa_code(
+    user: &User,
+    code: String,
+    stored_two_fa_code: &TwoFACode,
+    state: &AppState,
+) -> Result<bool, AuthAPIError> {
+    if let Ok(recovery_code) = RecoveryCode::parse(code.clone()) {
+        return use_recovery_code(&user.email, &recovery_code, state).await;
+    }
+    match user.two_fa_method {
+        TwoFAMethod::Email => {
+            let two_fa_code =
+                TwoFACode::parse(code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+            Ok(&two_fa_code == stored_two_fa_code)
+        }
+        TwoFAMethod::Totp => {
+            let secret = state
+                .user_store
+                .read()
+                .await
+                .get_totp_secret(&user.email)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+            let Some(step) = verify_totp_code(&secret, &user.email, &code)
+                .map_err(AuthAPIError::UnexpectedError)?
+            else {
+                return Ok(false);
+            };
+            // A code someone saw the user type can't be used again within its skew window
+            match state
+                .user_store
+                .write()
+                .await
+                .use_totp_step(&user.email, step)
+                .await
+            {
+                Ok(()) => Ok(true),
+                Err(UserStoreError::TotpCodeReused) => Ok(false),
+                Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+            }
+        }
+    }
+}
+
+// Counts a wrong code given for the pending 2FA check, ending the check once there were
+// too many. Returns the error to respond with.
+#[tracing::instrument(name = "Adding failed 2FA attempt", skip_all)]
+pub(crate) async fn add_failed_two_fa_attempt(
+    email: &Email,
+    two_fa_code_store: &mut (dyn TwoFACodeStore + Send + Sync),
+) -> AuthAPIError {
+    let failed_attempts = match two_fa_code_store.add_failed_attempt(email).await {
+        Ok(failed_attempts) => failed_attempts,
+        Err(e) => return AuthAPIError::UnexpectedError(e.into()),
+    };
+    if failed_attempts < MAX_TWO_FA_ATTEMPTS {
+        return AuthAPIError::IncorrectCredentials;
+    }
+
+    // The code can't be guessed within the check anymore, so the password is needed
+    // again before any other code is accepted
+    if let Err(e) = two_fa_code_store.remove_code(email).await {
+        return AuthAPIError::UnexpectedError(e.into());
+    }
+    AuthAPIError::TooManyTwoFAAttempts
+}
+
+// Number of wrong codes after which a login attempt, or other 2FA check, ends
+pub const MAX_TWO_FA_ATTEMPTS: u32 = 5;
+
+// Returns whether the code was one of the user's unused recovery codes, using it up if so
+#[tracing::instrument(name = "Using recovery code", skip_all)]
+async fn use_recovery_code(
+    email: &Email,
+    recovery_code: &RecoveryCode,
+    state: &AppState,
+) -> Result<bool, AuthAPIError> {
+    match state
+        .recovery_code_store
+        .write()
+        .await
+        .use_code(email, recovery_code)
+        .await
+    {
+        Ok(()) => {}
+        Err(RecoveryCodeStoreError::CodeNotFound) => return Ok(false),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // The code is already used up at this point, so failing to send the notice
+    // must not fail the login as well
+    if let Err(e) = state
+        .email_client
+        .read()
+        .await
+        .send_email(
+            email,
+            "Recovery code used",
+            "One of your recovery codes was just used to log in to your account. \
+            If this wasn't you, reset your password and generate new recovery codes.",
+        )
+        .await
+    {
+        tracing::error!(error = ?e, "failed to send recovery code notice");
     }
+    Ok(true)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
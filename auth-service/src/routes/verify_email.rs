@@ -0,0 +1,86 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, UserStatus, UserStoreError},
+    utils::validate_email_verification_token,
+};
+
+use super::signup::send_email_verification_link;
+
+#[tracing::instrument(name = "Verify email", skip_all)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = match validate_email_verification_token(&request.token) {
+        Ok(email) => email,
+        Err(_) => return Err(AuthAPIError::InvalidEmailVerificationToken),
+    };
+
+    match state
+        .user_store
+        .write()
+        .await
+        .mark_email_verified(&email)
+        .await
+    {
+        Ok(()) => {}
+        Err(UserStoreError::UserNotFound) => {
+            return Err(AuthAPIError::InvalidEmailVerificationToken);
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let response = Json(VerifyEmailResponse {
+        message: "Email address verified. You can now log in.".to_owned(),
+    });
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Deserialize)]
+pub struct VerifyEmailResponse {
+    pub message: String,
+}
+
+// Sends a new verification link to users whose link got lost or expired before they
+// verified their email address
+#[tracing::instrument(name = "Resend verification email", skip_all)]
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = match Email::parse(Secret::new(request.email)) {
+        Ok(email) => email,
+        Err(_) => return Err(AuthAPIError::InvalidCredentials),
+    };
+
+    // The response is the same whether or not there is an unverified account for the email,
+    // so this route can't be used to find out which addresses are registered.
+    let response = Json(VerifyEmailResponse {
+        message:
+            "If an unverified account exists for this email, a verification link has been sent."
+                .to_owned(),
+    });
+
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(user) if user.status == UserStatus::PendingVerification => {}
+        Ok(_) | Err(UserStoreError::UserNotFound) => return Ok((StatusCode::OK, response)),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    send_email_verification_link(&email, &state).await?;
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: String,
+}
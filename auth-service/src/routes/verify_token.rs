@@ -1,32 +1,20 @@
 use axum::{Json, extract::State, http::StatusCode};
-use secrecy::Secret;
 use serde::Deserialize;
 
-use crate::{app_state::AppState, domain::AuthAPIError, utils::validate_token};
+use crate::{app_state::AppState, domain::AuthAPIError, utils::validate_login_token};
 
 #[tracing::instrument(name = "Verify token", skip_all)]
 pub async fn verify_token(
     State(state): State<AppState>,
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<StatusCode, AuthAPIError> {
-    let token = request.token;
-
-    let token_not_valid = validate_token(&token, state.banned_token_store.clone())
-        .await
-        .is_err();
-    if token_not_valid {
-        return Err(AuthAPIError::InvalidToken);
-    }
-    let banned_token_store = state.banned_token_store.read().await;
-    match banned_token_store.contains_token(&Secret::new(token)).await {
-        Ok(contains_banned_token) => {
-            if contains_banned_token {
-                Err(AuthAPIError::InvalidToken)
-            } else {
-                Ok(StatusCode::OK)
-            }
-        }
-        _ => Ok(StatusCode::OK),
+    // Only tokens of users' own logins are valid, as they are to the service's own routes.
+    // Tokens OAuth clients got are checked at `/introspect`.
+    match validate_login_token(&request.token, &state).await {
+        Ok(_) => Ok(StatusCode::OK),
+        // To the services asking, the token of a suspended user is just no longer valid
+        Err(AuthAPIError::AccountSuspended) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(e),
     }
 }
 
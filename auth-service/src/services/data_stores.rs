@@ -1,13 +1,45 @@
 mod hash_map_user_store;
 mod hash_set_banned_token_store;
+mod hashmap_authorization_code_store;
+mod hashmap_failed_login_store;
+mod hashmap_oauth_client_store;
+mod hashmap_password_reset_token_store;
+mod hashmap_rate_limit_store;
+mod hashmap_recovery_code_store;
+mod hashmap_refresh_token_store;
+mod hashmap_session_store;
 mod hashmap_two_fa_code_store;
+mod postgres_oauth_client_store;
+mod postgres_recovery_code_store;
+mod postgres_session_store;
 mod postgres_user_store;
+mod redis_authorization_code_store;
 mod redis_banned_token_store;
+mod redis_failed_login_store;
+mod redis_password_reset_token_store;
+mod redis_rate_limit_store;
+mod redis_refresh_token_store;
 mod redis_two_fa_code_store;
 
 pub use hash_map_user_store::*;
 pub use hash_set_banned_token_store::*;
+pub use hashmap_authorization_code_store::*;
+pub use hashmap_failed_login_store::*;
+pub use hashmap_oauth_client_store::*;
+pub use hashmap_password_reset_token_store::*;
+pub use hashmap_rate_limit_store::*;
+pub use hashmap_recovery_code_store::*;
+pub use hashmap_refresh_token_store::*;
+pub use hashmap_session_store::*;
 pub use hashmap_two_fa_code_store::*;
+pub use postgres_oauth_client_store::*;
+pub use postgres_recovery_code_store::*;
+pub use postgres_session_store::*;
 pub use postgres_user_store::*;
+pub use redis_authorization_code_store::*;
 pub use redis_banned_token_store::*;
+pub use redis_failed_login_store::*;
+pub use redis_password_reset_token_store::*;
+pub use redis_rate_limit_store::*;
+pub use redis_refresh_token_store::*;
 pub use redis_two_fa_code_store::*;
@@ -1,6 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
-use crate::domain::{Email, Password, UserStore, UserStoreError, user::User};
+use chrono::Utc;
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    Email, Password, Permission, Role, TotpSecret, TwoFAMethod, UserId, UserStatus, UserStore,
+    UserStoreError, user::User,
+};
 
 // TODO: Create a new struct called `HashmapUserStore` containing a `users` field
 // which stores a `HashMap`` of email `String`s mapped to `User` objects.
@@ -8,13 +14,15 @@ use crate::domain::{Email, Password, UserStore, UserStoreError, user::User};
 #[derive(Default)]
 pub struct HashmapUserStore {
     users: HashMap<Email, User>,
+    pending_totp_secrets: HashMap<Email, TotpSecret>,
+    totp_secrets: HashMap<Email, TotpSecret>,
+    totp_last_steps: HashMap<Email, u64>,
+    roles: HashMap<UserId, BTreeSet<Role>>,
 }
 
 impl HashmapUserStore {
     pub fn new() -> Self {
-        Self {
-            users: HashMap::new(),
-        }
+        Self::default()
     }
 }
 
@@ -44,6 +52,51 @@ impl UserStore for HashmapUserStore {
             .unwrap_or(Err(UserStoreError::UserNotFound))
     }
 
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        self.users
+            .values()
+            .find(|user| &user.id == id)
+            .cloned()
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
+    async fn list_users(
+        &self,
+        search: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<User>, u64), UserStoreError> {
+        let search = search.map(str::to_lowercase);
+        let mut users = self
+            .users
+            .values()
+            .filter(|user| match &search {
+                Some(search) => user
+                    .email
+                    .as_ref()
+                    .expose_secret()
+                    .to_lowercase()
+                    .contains(search),
+                None => true,
+            })
+            .collect::<Vec<_>>();
+        users.sort_by(|a, b| {
+            a.email
+                .as_ref()
+                .expose_secret()
+                .cmp(b.email.as_ref().expose_secret())
+        });
+
+        let total = users.len() as u64;
+        let users = users
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect();
+        Ok((users, total))
+    }
+
     // TODO: Implement a public method called `validate_user`, which takes an
     // immutable reference to self, an email string slice, and a password string slice
     // as arguments. `validate_user` should return a `Result` type containing either a
@@ -61,6 +114,230 @@ impl UserStore for HashmapUserStore {
         }
         Ok(())
     }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.password = password;
+        user.password_reset_required = false;
+        Ok(())
+    }
+
+    async fn require_password_reset(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.password_reset_required = true;
+        Ok(())
+    }
+
+    async fn set_status(
+        &mut self,
+        email: &Email,
+        status: UserStatus,
+    ) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.status = status;
+        Ok(())
+    }
+
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        if user.status == UserStatus::PendingVerification {
+            user.status = UserStatus::Active;
+        }
+        Ok(())
+    }
+
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.deleted_at = Some(Utc::now().timestamp());
+        Ok(())
+    }
+
+    async fn restore_user(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.deleted_at = None;
+        Ok(())
+    }
+
+    async fn purge_deleted_users(
+        &mut self,
+        deleted_before: i64,
+    ) -> Result<Vec<Email>, UserStoreError> {
+        let purged = self
+            .users
+            .values()
+            .filter(|user| {
+                user.deleted_at
+                    .is_some_and(|deleted_at| deleted_at < deleted_before)
+            })
+            .map(|user| user.email.clone())
+            .collect::<Vec<_>>();
+        for email in &purged {
+            if let Some(user) = self.users.remove(email) {
+                self.roles.remove(&user.id);
+            }
+            self.pending_totp_secrets.remove(email);
+            self.totp_secrets.remove(email);
+            self.totp_last_steps.remove(email);
+        }
+        Ok(purged)
+    }
+
+    async fn update_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError> {
+        if self.users.contains_key(new_email) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+        let mut user = self
+            .users
+            .remove(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.email = new_email.clone();
+        self.users.insert(new_email.clone(), user);
+        if let Some(secret) = self.pending_totp_secrets.remove(email) {
+            self.pending_totp_secrets.insert(new_email.clone(), secret);
+        }
+        if let Some(secret) = self.totp_secrets.remove(email) {
+            self.totp_secrets.insert(new_email.clone(), secret);
+        }
+        if let Some(step) = self.totp_last_steps.remove(email) {
+            self.totp_last_steps.insert(new_email.clone(), step);
+        }
+        Ok(())
+    }
+
+    async fn set_pending_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        self.pending_totp_secrets.insert(email.clone(), secret);
+        Ok(())
+    }
+
+    async fn get_pending_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        self.pending_totp_secrets
+            .get(email)
+            .cloned()
+            .ok_or(UserStoreError::TotpSecretNotFound)
+    }
+
+    async fn enable_totp(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        let secret = self
+            .pending_totp_secrets
+            .remove(email)
+            .ok_or(UserStoreError::TotpSecretNotFound)?;
+        user.requires_2fa = true;
+        user.two_fa_method = TwoFAMethod::Totp;
+        self.totp_secrets.insert(email.clone(), secret);
+        Ok(())
+    }
+
+    async fn get_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        self.totp_secrets
+            .get(email)
+            .cloned()
+            .ok_or(UserStoreError::TotpSecretNotFound)
+    }
+
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<(), UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        match self.totp_last_steps.get(email) {
+            Some(&last_step) if step <= last_step => Err(UserStoreError::TotpCodeReused),
+            _ => {
+                self.totp_last_steps.insert(email.clone(), step);
+                Ok(())
+            }
+        }
+    }
+
+    async fn set_requires_2fa(
+        &mut self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.requires_2fa = requires_2fa;
+        if !requires_2fa {
+            user.two_fa_method = TwoFAMethod::Email;
+            self.pending_totp_secrets.remove(email);
+            self.totp_secrets.remove(email);
+        }
+        Ok(())
+    }
+
+    async fn get_roles(&self, id: &UserId) -> Result<Vec<Role>, UserStoreError> {
+        Ok(self
+            .roles
+            .get(id)
+            .map(|roles| roles.iter().copied().collect())
+            .unwrap_or_default())
+    }
+
+    async fn grant_role(&mut self, id: &UserId, role: Role) -> Result<(), UserStoreError> {
+        if !self.users.values().any(|user| &user.id == id) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        self.roles.entry(id.clone()).or_default().insert(role);
+        Ok(())
+    }
+
+    async fn revoke_role(&mut self, id: &UserId, role: Role) -> Result<(), UserStoreError> {
+        if !self.users.values().any(|user| &user.id == id) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        if let Some(roles) = self.roles.get_mut(id) {
+            roles.remove(&role);
+        }
+        Ok(())
+    }
+
+    async fn get_permissions(&self, id: &UserId) -> Result<Vec<Permission>, UserStoreError> {
+        let permissions: BTreeSet<Permission> = self
+            .roles
+            .get(id)
+            .into_iter()
+            .flatten()
+            .flat_map(|role| role.permissions().iter().copied())
+            .collect();
+        Ok(permissions.into_iter().collect())
+    }
 }
 
 // TODO: Add unit tests for your `HashmapUserStore` implementation
@@ -74,9 +351,14 @@ mod tests {
     async fn test_add_user() {
         let mut user_store = HashmapUserStore::new();
         let user = User {
+            id: UserId::default(),
             email: Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
             password: Password::parse(String::from("some-password-1").into()).unwrap(),
             requires_2fa: false,
+            status: UserStatus::PendingVerification,
+            two_fa_method: TwoFAMethod::Email,
+            deleted_at: None,
+            password_reset_required: false,
         };
         let initial_insert_result = user_store.add_user(user.clone()).await;
         assert_eq!(initial_insert_result, Ok(()));
@@ -90,31 +372,52 @@ mod tests {
     #[tokio::test]
     async fn test_get_user() {
         let user = User {
+            id: UserId::default(),
             email: Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
             password: Password::parse(String::from("some-password-1").into()).unwrap(),
             requires_2fa: false,
+            status: UserStatus::PendingVerification,
+            two_fa_method: TwoFAMethod::Email,
+            deleted_at: None,
+            password_reset_required: false,
         };
         let mut users = HashMap::new();
         users.insert(user.email.clone(), user.clone());
-        let user_store = HashmapUserStore { users };
+        let user_store = HashmapUserStore {
+            users,
+            ..Default::default()
+        };
         let no_matching_user_result = user_store
             .get_user(&Email::parse(Secret::new("b@test.com".to_owned())).unwrap())
             .await;
         assert_eq!(no_matching_user_result, Err(UserStoreError::UserNotFound));
         let user_exists_result = user_store.get_user(&user.email).await;
-        assert_eq!(user_exists_result, Ok(user))
+        assert_eq!(user_exists_result, Ok(user.clone()));
+
+        let no_matching_id_result = user_store.get_user_by_id(&UserId::default()).await;
+        assert_eq!(no_matching_id_result, Err(UserStoreError::UserNotFound));
+        let id_exists_result = user_store.get_user_by_id(&user.id).await;
+        assert_eq!(id_exists_result, Ok(user))
     }
 
     #[tokio::test]
     async fn test_validate_user() {
         let user = User {
-            email: Email::parse(Secret::new(String::from("a@test.com").into())).unwrap(),
+            id: UserId::default(),
+            email: Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
             password: Password::parse(String::from("some-password-1").into()).unwrap(),
             requires_2fa: false,
+            status: UserStatus::PendingVerification,
+            two_fa_method: TwoFAMethod::Email,
+            deleted_at: None,
+            password_reset_required: false,
         };
         let mut users = HashMap::new();
         users.insert(user.email.clone(), user.clone());
-        let user_store = HashmapUserStore { users };
+        let user_store = HashmapUserStore {
+            users,
+            ..Default::default()
+        };
         let result_invalid = user_store
             .validate_user(
                 &user.email,
@@ -135,4 +438,410 @@ mod tests {
         let result_valid = user_store.validate_user(&user.email, &user.password).await;
         assert_eq!(result_valid, Ok(()))
     }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let mut user_store = HashmapUserStore::new();
+        let user = User {
+            id: UserId::default(),
+            email: Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
+            password: Password::parse(String::from("some-password-1").into()).unwrap(),
+            requires_2fa: false,
+            status: UserStatus::PendingVerification,
+            two_fa_method: TwoFAMethod::Email,
+            deleted_at: None,
+            password_reset_required: false,
+        };
+        let new_password = Password::parse(String::from("some-password-2").into()).unwrap();
+        let result_user_does_not_exist = user_store
+            .update_password(&user.email, new_password.clone())
+            .await;
+        assert_eq!(
+            result_user_does_not_exist,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store.add_user(user.clone()).await.unwrap();
+        let result = user_store
+            .update_password(&user.email, new_password.clone())
+            .await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            user_store.validate_user(&user.email, &user.password).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        assert_eq!(
+            user_store.validate_user(&user.email, &new_password).await,
+            Ok(())
+        );
+
+        // Changing the password lifts a password reset requirement
+        assert_eq!(user_store.require_password_reset(&user.email).await, Ok(()));
+        assert!(
+            user_store
+                .get_user(&user.email)
+                .await
+                .unwrap()
+                .password_reset_required
+        );
+        user_store
+            .update_password(&user.email, user.password.clone())
+            .await
+            .unwrap();
+        assert!(
+            !user_store
+                .get_user(&user.email)
+                .await
+                .unwrap()
+                .password_reset_required
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_users() {
+        let mut user_store = HashmapUserStore::new();
+        for email in ["c@test.com", "a@test.com", "B@other.com"] {
+            let user = User::new(
+                Email::parse(Secret::new(String::from(email))).unwrap(),
+                Password::parse(String::from("some-password-1").into()).unwrap(),
+                false,
+            );
+            user_store.add_user(user).await.unwrap();
+        }
+        let emails = |users: Vec<User>| {
+            users
+                .into_iter()
+                .map(|user| user.email.as_ref().expose_secret().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let (users, total) = user_store.list_users(None, 0, 2).await.unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(emails(users), vec!["B@other.com", "a@test.com"]);
+        let (users, total) = user_store.list_users(None, 2, 2).await.unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(emails(users), vec!["c@test.com"]);
+
+        let (users, total) = user_store.list_users(Some("b@OTHER"), 0, 10).await.unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(emails(users), vec!["B@other.com"]);
+        let (users, total) = user_store.list_users(Some("test"), 5, 10).await.unwrap();
+        assert_eq!(total, 2);
+        assert!(users.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mark_email_verified() {
+        let mut user_store = HashmapUserStore::new();
+        let user = User::new(
+            Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-1").into()).unwrap(),
+            false,
+        );
+        assert_eq!(
+            user_store.mark_email_verified(&user.email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store.add_user(user.clone()).await.unwrap();
+        assert_eq!(
+            user_store.get_user(&user.email).await.unwrap().status,
+            UserStatus::PendingVerification
+        );
+        assert_eq!(user_store.mark_email_verified(&user.email).await, Ok(()));
+        assert_eq!(
+            user_store.get_user(&user.email).await.unwrap().status,
+            UserStatus::Active
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_status() {
+        let mut user_store = HashmapUserStore::new();
+        let user = User::new(
+            Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-1").into()).unwrap(),
+            false,
+        );
+        assert_eq!(
+            user_store
+                .set_status(&user.email, UserStatus::Suspended)
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store.add_user(user.clone()).await.unwrap();
+        assert_eq!(
+            user_store
+                .set_status(&user.email, UserStatus::Suspended)
+                .await,
+            Ok(())
+        );
+        // Verifying the address doesn't lift a suspension
+        assert_eq!(user_store.mark_email_verified(&user.email).await, Ok(()));
+        assert_eq!(
+            user_store.get_user(&user.email).await.unwrap().status,
+            UserStatus::Suspended
+        );
+
+        assert_eq!(
+            user_store.set_status(&user.email, UserStatus::Active).await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store.get_user(&user.email).await.unwrap().status,
+            UserStatus::Active
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let mut user_store = HashmapUserStore::new();
+        let user = User::new(
+            Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-1").into()).unwrap(),
+            false,
+        );
+        let other_user = User::new(
+            Email::parse(Secret::new(String::from("b@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-2").into()).unwrap(),
+            false,
+        );
+        assert_eq!(
+            user_store.delete_user(&user.email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store.add_user(user.clone()).await.unwrap();
+        user_store.add_user(other_user.clone()).await.unwrap();
+        assert_eq!(user_store.delete_user(&user.email).await, Ok(()));
+        assert!(
+            user_store
+                .get_user(&user.email)
+                .await
+                .unwrap()
+                .deleted_at
+                .is_some()
+        );
+        assert_eq!(user_store.restore_user(&user.email).await, Ok(()));
+        assert_eq!(user_store.get_user(&user.email).await, Ok(user.clone()));
+
+        user_store.delete_user(&user.email).await.unwrap();
+        let now = Utc::now().timestamp();
+        assert_eq!(user_store.purge_deleted_users(now - 60).await, Ok(vec![]));
+        assert_eq!(
+            user_store.purge_deleted_users(now + 60).await,
+            Ok(vec![user.email.clone()])
+        );
+        assert_eq!(
+            user_store.get_user(&user.email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        assert_eq!(user_store.get_user(&other_user.email).await, Ok(other_user));
+    }
+
+    #[tokio::test]
+    async fn test_update_email() {
+        let mut user_store = HashmapUserStore::new();
+        let user = User::new(
+            Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-1").into()).unwrap(),
+            false,
+        );
+        let other_user = User::new(
+            Email::parse(Secret::new(String::from("b@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-2").into()).unwrap(),
+            false,
+        );
+        let new_email = Email::parse(Secret::new(String::from("c@test.com"))).unwrap();
+        assert_eq!(
+            user_store.update_email(&user.email, &new_email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store.add_user(user.clone()).await.unwrap();
+        user_store.add_user(other_user.clone()).await.unwrap();
+        assert_eq!(
+            user_store
+                .update_email(&user.email, &other_user.email)
+                .await,
+            Err(UserStoreError::UserAlreadyExists)
+        );
+
+        let secret = TotpSecret::default();
+        user_store
+            .set_pending_totp_secret(&user.email, secret.clone())
+            .await
+            .unwrap();
+        user_store.enable_totp(&user.email).await.unwrap();
+        assert_eq!(
+            user_store.update_email(&user.email, &new_email).await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store.get_user(&user.email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        assert_eq!(
+            user_store.validate_user(&new_email, &user.password).await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store.get_user(&new_email).await.unwrap().email,
+            new_email
+        );
+        assert_eq!(user_store.get_totp_secret(&new_email).await, Ok(secret));
+    }
+
+    #[tokio::test]
+    async fn test_enable_totp() {
+        let mut user_store = HashmapUserStore::new();
+        let user = User::new(
+            Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-1").into()).unwrap(),
+            false,
+        );
+        let secret = TotpSecret::default();
+        assert_eq!(
+            user_store
+                .set_pending_totp_secret(&user.email, secret.clone())
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store.add_user(user.clone()).await.unwrap();
+        assert_eq!(
+            user_store.enable_totp(&user.email).await,
+            Err(UserStoreError::TotpSecretNotFound)
+        );
+        user_store
+            .set_pending_totp_secret(&user.email, secret.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            user_store.get_pending_totp_secret(&user.email).await,
+            Ok(secret.clone())
+        );
+        assert_eq!(
+            user_store.get_totp_secret(&user.email).await,
+            Err(UserStoreError::TotpSecretNotFound)
+        );
+
+        assert_eq!(user_store.enable_totp(&user.email).await, Ok(()));
+        assert_eq!(user_store.get_totp_secret(&user.email).await, Ok(secret));
+        assert_eq!(
+            user_store.get_pending_totp_secret(&user.email).await,
+            Err(UserStoreError::TotpSecretNotFound)
+        );
+        let user = user_store.get_user(&user.email).await.unwrap();
+        assert!(user.requires_2fa);
+        assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+    }
+
+    #[tokio::test]
+    async fn test_use_totp_step() {
+        let mut user_store = HashmapUserStore::new();
+        let user = User::new(
+            Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-1").into()).unwrap(),
+            false,
+        );
+        assert_eq!(
+            user_store.use_totp_step(&user.email, 100).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store.add_user(user.clone()).await.unwrap();
+        assert_eq!(user_store.use_totp_step(&user.email, 100).await, Ok(()));
+        assert_eq!(
+            user_store.use_totp_step(&user.email, 100).await,
+            Err(UserStoreError::TotpCodeReused)
+        );
+        assert_eq!(
+            user_store.use_totp_step(&user.email, 99).await,
+            Err(UserStoreError::TotpCodeReused)
+        );
+        assert_eq!(user_store.use_totp_step(&user.email, 101).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_set_requires_2fa() {
+        let mut user_store = HashmapUserStore::new();
+        let user = User::new(
+            Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-1").into()).unwrap(),
+            false,
+        );
+        assert_eq!(
+            user_store.set_requires_2fa(&user.email, true).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store.add_user(user.clone()).await.unwrap();
+        assert_eq!(user_store.set_requires_2fa(&user.email, true).await, Ok(()));
+        let stored_user = user_store.get_user(&user.email).await.unwrap();
+        assert!(stored_user.requires_2fa);
+        assert_eq!(stored_user.two_fa_method, TwoFAMethod::Email);
+
+        user_store
+            .set_pending_totp_secret(&user.email, TotpSecret::default())
+            .await
+            .unwrap();
+        user_store.enable_totp(&user.email).await.unwrap();
+        assert_eq!(
+            user_store.set_requires_2fa(&user.email, false).await,
+            Ok(())
+        );
+        let stored_user = user_store.get_user(&user.email).await.unwrap();
+        assert!(!stored_user.requires_2fa);
+        assert_eq!(stored_user.two_fa_method, TwoFAMethod::Email);
+        assert_eq!(
+            user_store.get_totp_secret(&user.email).await,
+            Err(UserStoreError::TotpSecretNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_roles() {
+        let mut user_store = HashmapUserStore::new();
+        let user = User::new(
+            Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-1").into()).unwrap(),
+            false,
+        );
+        assert_eq!(
+            user_store.grant_role(&user.id, Role::Admin).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store.add_user(user.clone()).await.unwrap();
+        assert_eq!(user_store.get_roles(&user.id).await, Ok(vec![]));
+        assert_eq!(user_store.grant_role(&user.id, Role::Admin).await, Ok(()));
+        assert_eq!(user_store.grant_role(&user.id, Role::Admin).await, Ok(()));
+        assert_eq!(user_store.get_roles(&user.id).await, Ok(vec![Role::Admin]));
+
+        assert_eq!(user_store.revoke_role(&user.id, Role::Admin).await, Ok(()));
+        assert_eq!(user_store.revoke_role(&user.id, Role::Admin).await, Ok(()));
+        assert_eq!(user_store.get_roles(&user.id).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_get_permissions() {
+        let mut user_store = HashmapUserStore::new();
+        let user = User::new(
+            Email::parse(Secret::new(String::from("a@test.com"))).unwrap(),
+            Password::parse(String::from("some-password-1").into()).unwrap(),
+            false,
+        );
+        user_store.add_user(user.clone()).await.unwrap();
+        assert_eq!(user_store.get_permissions(&user.id).await, Ok(vec![]));
+
+        user_store.grant_role(&user.id, Role::Admin).await.unwrap();
+        let permissions = user_store.get_permissions(&user.id).await.unwrap();
+        assert!(permissions.contains(&Permission::ManageRoles));
+        assert_eq!(permissions.len(), Role::Admin.permissions().len());
+
+        user_store.revoke_role(&user.id, Role::Admin).await.unwrap();
+        assert_eq!(user_store.get_permissions(&user.id).await, Ok(vec![]));
+    }
 }
@@ -1,42 +1,50 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use secrecy::{ExposeSecret, Secret};
+use chrono::Utc;
 
-use crate::domain::{BannedTokenStore, BannedTokenStoreError};
+use crate::domain::{BannedTokenStore, BannedTokenStoreError, UserId};
 
 #[derive(Default)]
 pub struct HashSetBannedTokenStore {
     tokens: HashSet<String>,
+    user_tokens_revoked_at: HashMap<UserId, i64>,
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashSetBannedTokenStore {
-    async fn add_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
-        self.tokens.insert(token.expose_secret().to_owned());
+    async fn add_token(&mut self, jti: String) -> Result<(), BannedTokenStoreError> {
+        self.tokens.insert(jti);
         Ok(())
     }
 
-    async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.tokens.get(token.expose_secret()).is_some())
+    async fn contains_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        Ok(self.tokens.contains(jti))
+    }
+
+    async fn revoke_user_tokens(&mut self, user_id: &UserId) -> Result<(), BannedTokenStoreError> {
+        self.user_tokens_revoked_at
+            .insert(user_id.clone(), Utc::now().timestamp());
+        Ok(())
+    }
+
+    async fn get_user_tokens_revoked_at(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Option<i64>, BannedTokenStoreError> {
+        Ok(self.user_tokens_revoked_at.get(user_id).copied())
     }
 }
 
 #[cfg(test)]
 mod tests {
-
     use super::*;
 
     #[tokio::test]
     async fn test_add_token() {
         let mut store = HashSetBannedTokenStore::default();
-        let result = store.add_token(Secret::new("foo".to_owned())).await;
+        let result = store.add_token("foo".to_owned()).await;
         assert!(result.is_ok());
-        assert!(
-            store
-                .contains_token(&Secret::new("foo".to_owned()))
-                .await
-                .unwrap()
-        );
+        assert!(store.contains_token("foo").await.unwrap());
     }
 
     #[tokio::test]
@@ -44,11 +52,21 @@ mod tests {
         let mut store = HashSetBannedTokenStore::default();
         store.tokens.insert("bar".to_owned());
 
-        assert!(
-            store
-                .contains_token(&Secret::new("bar".to_owned()))
-                .await
-                .unwrap()
-        )
+        assert!(store.contains_token("bar").await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_revoke_user_tokens() {
+        let mut store = HashSetBannedTokenStore::default();
+        let user_id = UserId::default();
+        assert_eq!(
+            store.get_user_tokens_revoked_at(&user_id).await.unwrap(),
+            None
+        );
+
+        let before = Utc::now().timestamp();
+        store.revoke_user_tokens(&user_id).await.unwrap();
+        let revoked_at = store.get_user_tokens_revoked_at(&user_id).await.unwrap();
+        assert!(revoked_at.unwrap() >= before);
     }
 }
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    AuthorizationCode, AuthorizationCodeStore, AuthorizationCodeStoreError, AuthorizationGrant,
+};
+
+// Codes are kept by their value. Unlike the Redis store, codes don't expire here.
+#[derive(Default)]
+pub struct HashmapAuthorizationCodeStore {
+    codes: HashMap<String, AuthorizationGrant>,
+}
+
+impl HashmapAuthorizationCodeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for HashmapAuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        self.codes
+            .insert(code.as_ref().expose_secret().to_owned(), grant);
+        Ok(())
+    }
+
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        self.codes
+            .remove(code.as_ref().expose_secret())
+            .ok_or(AuthorizationCodeStoreError::CodeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{ClientId, CodeChallenge, SessionId, UserId};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_code_only_once() {
+        let mut store = HashmapAuthorizationCodeStore::new();
+        let code = AuthorizationCode::default();
+        let grant = AuthorizationGrant {
+            client_id: ClientId::default(),
+            user_id: UserId::default(),
+            session_id: SessionId::default(),
+            redirect_uri: "https://app.example.com/callback".to_owned(),
+            redirect_uri_required: true,
+            scopes: vec!["read".to_owned()],
+            code_challenge: CodeChallenge::parse(
+                "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_owned(),
+            )
+            .unwrap(),
+            nonce: Some("n-0S6_WzA2Mj".to_owned()),
+        };
+        store.add_code(code.clone(), grant.clone()).await.unwrap();
+
+        assert_eq!(
+            store.take_code(&AuthorizationCode::default()).await,
+            Err(AuthorizationCodeStoreError::CodeNotFound)
+        );
+        assert_eq!(store.take_code(&code).await, Ok(grant));
+        assert_eq!(
+            store.take_code(&code).await,
+            Err(AuthorizationCodeStoreError::CodeNotFound)
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::{
+    domain::{Email, FailedLoginStore, FailedLoginStoreError, FailedLogins},
+    utils::LOGIN_LOCKOUT_DURATION,
+};
+
+#[derive(Default)]
+pub struct HashmapFailedLoginStore {
+    failed_logins: HashMap<Email, FailedLogins>,
+}
+
+impl HashmapFailedLoginStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for HashmapFailedLoginStore {
+    async fn get_failed_logins(
+        &self,
+        email: &Email,
+    ) -> Result<FailedLogins, FailedLoginStoreError> {
+        Ok(self
+            .failed_logins
+            .get(email)
+            .filter(|failed_logins| !is_expired(failed_logins))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn add_failed_login(
+        &mut self,
+        email: &Email,
+    ) -> Result<FailedLogins, FailedLoginStoreError> {
+        let count = self.get_failed_logins(email).await?.count;
+        let failed_logins = FailedLogins {
+            count: count + 1,
+            last_failed_at: Utc::now().timestamp(),
+        };
+        self.failed_logins
+            .insert(email.clone(), failed_logins.clone());
+        Ok(failed_logins)
+    }
+
+    async fn reset_failed_logins(&mut self, email: &Email) -> Result<(), FailedLoginStoreError> {
+        self.failed_logins.remove(email);
+        Ok(())
+    }
+}
+
+fn is_expired(failed_logins: &FailedLogins) -> bool {
+    failed_logins.last_failed_at + LOGIN_LOCKOUT_DURATION.as_secs() as i64 <= Utc::now().timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse(Secret::new("test@example.com".to_owned())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_add_failed_login() {
+        let mut store = HashmapFailedLoginStore::new();
+        assert_eq!(store.get_failed_logins(&email()).await.unwrap().count, 0);
+
+        store.add_failed_login(&email()).await.unwrap();
+        let failed_logins = store.add_failed_login(&email()).await.unwrap();
+        assert_eq!(failed_logins.count, 2);
+        assert_eq!(store.get_failed_logins(&email()).await, Ok(failed_logins));
+    }
+
+    #[tokio::test]
+    async fn test_reset_failed_logins() {
+        let mut store = HashmapFailedLoginStore::new();
+        store.add_failed_login(&email()).await.unwrap();
+
+        assert_eq!(store.reset_failed_logins(&email()).await, Ok(()));
+        assert_eq!(store.get_failed_logins(&email()).await.unwrap().count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_logins_expire() {
+        let mut store = HashmapFailedLoginStore::new();
+        let expired_at = Utc::now().timestamp() - LOGIN_LOCKOUT_DURATION.as_secs() as i64;
+        store.failed_logins.insert(
+            email(),
+            FailedLogins {
+                count: 3,
+                last_failed_at: expired_at,
+            },
+        );
+
+        assert_eq!(store.get_failed_logins(&email()).await.unwrap().count, 0);
+        let failed_logins = store.add_failed_login(&email()).await.unwrap();
+        assert_eq!(failed_logins.count, 1);
+    }
+}
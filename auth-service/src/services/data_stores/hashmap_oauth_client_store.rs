@@ -0,0 +1,159 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::domain::{ClientId, OAuthClient, OAuthClientStore, OAuthClientStoreError, UserId};
+
+#[derive(Default)]
+pub struct HashmapOAuthClientStore {
+    clients: HashMap<ClientId, OAuthClient>,
+    consents: HashMap<(UserId, ClientId), BTreeSet<String>>,
+}
+
+impl HashmapOAuthClientStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for HashmapOAuthClientStore {
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError> {
+        self.clients.insert(client.id.clone(), client);
+        Ok(())
+    }
+
+    async fn get_client(&self, id: &ClientId) -> Result<OAuthClient, OAuthClientStoreError> {
+        self.clients
+            .get(id)
+            .cloned()
+            .ok_or(OAuthClientStoreError::ClientNotFound)
+    }
+
+    async fn list_clients(&self) -> Result<Vec<OAuthClient>, OAuthClientStoreError> {
+        let mut clients: Vec<OAuthClient> = self.clients.values().cloned().collect();
+        clients.sort_by(|a, b| {
+            a.name
+                .cmp(&b.name)
+                .then_with(|| a.id.as_ref().cmp(b.id.as_ref()))
+        });
+        Ok(clients)
+    }
+
+    async fn delete_client(&mut self, id: &ClientId) -> Result<(), OAuthClientStoreError> {
+        self.clients
+            .remove(id)
+            .ok_or(OAuthClientStoreError::ClientNotFound)?;
+        self.consents.retain(|(_, client_id), _| client_id != id);
+        Ok(())
+    }
+
+    async fn get_consent(
+        &self,
+        user_id: &UserId,
+        client_id: &ClientId,
+    ) -> Result<Vec<String>, OAuthClientStoreError> {
+        Ok(self
+            .consents
+            .get(&(user_id.clone(), client_id.clone()))
+            .map(|scopes| scopes.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn grant_consent(
+        &mut self,
+        user_id: &UserId,
+        client_id: &ClientId,
+        scopes: &[String],
+    ) -> Result<(), OAuthClientStoreError> {
+        if !self.clients.contains_key(client_id) {
+            return Err(OAuthClientStoreError::ClientNotFound);
+        }
+        self.consents
+            .entry((user_id.clone(), client_id.clone()))
+            .or_default()
+            .extend(scopes.iter().cloned());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(name: &str) -> OAuthClient {
+        OAuthClient {
+            id: ClientId::default(),
+            name: name.to_owned(),
+            redirect_uris: vec!["https://app.example.com/callback".to_owned()],
+            scopes: vec!["read".to_owned(), "write".to_owned()],
+            secret_hash: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_list_and_delete_clients() {
+        let mut store = HashmapOAuthClientStore::new();
+        let first = client("B app");
+        let second = client("A app");
+        store.add_client(first.clone()).await.unwrap();
+        store.add_client(second.clone()).await.unwrap();
+
+        assert_eq!(store.get_client(&first.id).await, Ok(first.clone()));
+        assert_eq!(
+            store.list_clients().await,
+            Ok(vec![second.clone(), first.clone()])
+        );
+
+        assert_eq!(store.delete_client(&first.id).await, Ok(()));
+        assert_eq!(
+            store.get_client(&first.id).await,
+            Err(OAuthClientStoreError::ClientNotFound)
+        );
+        assert_eq!(
+            store.delete_client(&first.id).await,
+            Err(OAuthClientStoreError::ClientNotFound)
+        );
+        assert_eq!(store.list_clients().await, Ok(vec![second]));
+    }
+
+    #[tokio::test]
+    async fn test_grant_consent() {
+        let mut store = HashmapOAuthClientStore::new();
+        let client = client("App");
+        let user_id = UserId::default();
+        assert_eq!(
+            store
+                .grant_consent(&user_id, &client.id, &["read".to_owned()])
+                .await,
+            Err(OAuthClientStoreError::ClientNotFound)
+        );
+
+        store.add_client(client.clone()).await.unwrap();
+        assert_eq!(store.get_consent(&user_id, &client.id).await, Ok(vec![]));
+        store
+            .grant_consent(&user_id, &client.id, &["read".to_owned()])
+            .await
+            .unwrap();
+        store
+            .grant_consent(
+                &user_id,
+                &client.id,
+                &["write".to_owned(), "read".to_owned()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_consent(&user_id, &client.id).await,
+            Ok(vec!["read".to_owned(), "write".to_owned()])
+        );
+        // Other users consent for themselves
+        assert_eq!(
+            store.get_consent(&UserId::default(), &client.id).await,
+            Ok(vec![])
+        );
+
+        // Consents don't outlive the client
+        store.delete_client(&client.id).await.unwrap();
+        store.add_client(client.clone()).await.unwrap();
+        assert_eq!(store.get_consent(&user_id, &client.id).await, Ok(vec![]));
+    }
+}
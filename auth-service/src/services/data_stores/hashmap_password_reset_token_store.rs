@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    Email, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapPasswordResetTokenStore {
+    tokens: HashMap<Email, PasswordResetToken>,
+}
+
+impl HashmapPasswordResetTokenStore {
+    pub fn new() -> Self {
+        Self {
+            tokens: HashMap::new(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashmapPasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        self.tokens.insert(email, token);
+        Ok(())
+    }
+
+    async fn remove_token(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        match self.tokens.remove(email) {
+            Some(_) => Ok(()),
+            None => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn get_token(
+        &self,
+        email: &Email,
+    ) -> Result<PasswordResetToken, PasswordResetTokenStoreError> {
+        match self.tokens.get(email) {
+            Some(token) => Ok(token.clone()),
+            None => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_token_replaces_previous_token() {
+        let mut store = HashmapPasswordResetTokenStore::new();
+        let email = Email::parse(Secret::new(String::from("a@b.com"))).unwrap();
+        let first_token = PasswordResetToken::default();
+        let second_token = PasswordResetToken::default();
+        store
+            .add_token(email.clone(), first_token.clone())
+            .await
+            .unwrap();
+        store
+            .add_token(email.clone(), second_token.clone())
+            .await
+            .unwrap();
+        assert_eq!(store.get_token(&email).await, Ok(second_token));
+    }
+
+    #[tokio::test]
+    async fn test_remove_token() {
+        let mut store = HashmapPasswordResetTokenStore::new();
+        let email = Email::parse(Secret::new(String::from("a@b.com"))).unwrap();
+        store
+            .add_token(email.clone(), PasswordResetToken::default())
+            .await
+            .unwrap();
+        assert_eq!(store.remove_token(&email).await, Ok(()));
+        assert_eq!(
+            store.get_token(&email).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(
+            store.remove_token(&email).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+    }
+}
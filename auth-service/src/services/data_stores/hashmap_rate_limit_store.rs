@@ -0,0 +1,82 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::Utc;
+
+use crate::domain::{RateLimit, RateLimitStore, RateLimitStoreError, TokenBucket};
+
+// Buckets are only kept by this instance of the service, so clients get a separate
+// budget on every instance
+#[derive(Default)]
+pub struct HashmapRateLimitStore {
+    buckets: HashMap<String, (TokenBucket, RateLimit)>,
+}
+
+impl HashmapRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Full buckets are the same as no bucket at all
+    fn remove_full_buckets(&mut self, now: i64) {
+        self.buckets.retain(|_, (bucket, rate_limit)| {
+            bucket.updated_at + rate_limit.refill_duration().as_millis() as i64 > now
+        });
+    }
+}
+
+const MAX_BUCKETS: usize = 10_000;
+
+#[async_trait::async_trait]
+impl RateLimitStore for HashmapRateLimitStore {
+    async fn take_token(
+        &mut self,
+        key: &str,
+        rate_limit: &RateLimit,
+    ) -> Result<Option<Duration>, RateLimitStoreError> {
+        let now = Utc::now().timestamp_millis();
+        if self.buckets.len() >= MAX_BUCKETS {
+            self.remove_full_buckets(now);
+        }
+
+        let (bucket, _) = self
+            .buckets
+            .entry(key.to_owned())
+            .or_insert_with(|| (TokenBucket::new(rate_limit, now), *rate_limit));
+        Ok(bucket.take(rate_limit, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE_LIMIT: RateLimit = RateLimit {
+        capacity: 2,
+        refill_interval: Duration::from_secs(60),
+    };
+
+    #[tokio::test]
+    async fn test_take_token() {
+        let mut store = HashmapRateLimitStore::new();
+        assert_eq!(store.take_token("key", &RATE_LIMIT).await, Ok(None));
+        assert_eq!(store.take_token("key", &RATE_LIMIT).await, Ok(None));
+        let wait = store.take_token("key", &RATE_LIMIT).await.unwrap();
+        assert!(wait.is_some_and(|wait| wait <= RATE_LIMIT.refill_interval));
+
+        // Buckets are separate per key
+        assert_eq!(store.take_token("other key", &RATE_LIMIT).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_remove_full_buckets() {
+        let mut store = HashmapRateLimitStore::new();
+        store.take_token("key", &RATE_LIMIT).await.unwrap();
+
+        let refilled_at =
+            Utc::now().timestamp_millis() + RATE_LIMIT.refill_duration().as_millis() as i64;
+        store.remove_full_buckets(refilled_at - 1);
+        assert_eq!(store.buckets.len(), 1);
+        store.remove_full_buckets(refilled_at);
+        assert!(store.buckets.is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use crate::domain::{Email, RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError};
+
+#[derive(Default)]
+pub struct HashmapRecoveryCodeStore {
+    // Unused codes of each user; used codes are removed
+    codes: HashMap<Email, Vec<RecoveryCode>>,
+}
+
+impl HashmapRecoveryCodeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashmapRecoveryCodeStore {
+    async fn set_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        self.codes.insert(email.clone(), codes);
+        Ok(())
+    }
+
+    async fn use_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let codes = self
+            .codes
+            .get_mut(email)
+            .ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+        let position = codes
+            .iter()
+            .position(|stored_code| stored_code == code)
+            .ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+        codes.remove(position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::{ExposeSecret, Secret};
+
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse(Secret::new(String::from("a@b.com"))).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_use_code_only_once() {
+        let mut store = HashmapRecoveryCodeStore::new();
+        let codes = RecoveryCode::generate_set();
+        store.set_codes(&email(), codes.clone()).await.unwrap();
+
+        assert_eq!(store.use_code(&email(), &codes[0]).await, Ok(()));
+        assert_eq!(
+            store.use_code(&email(), &codes[0]).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+        assert_eq!(store.use_code(&email(), &codes[1]).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_set_codes_replaces_previous_codes() {
+        let mut store = HashmapRecoveryCodeStore::new();
+        let old_codes = RecoveryCode::generate_set();
+        let new_codes = RecoveryCode::generate_set();
+        store.set_codes(&email(), old_codes.clone()).await.unwrap();
+        store.set_codes(&email(), new_codes.clone()).await.unwrap();
+
+        assert_eq!(
+            store.use_code(&email(), &old_codes[0]).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+        assert_eq!(store.use_code(&email(), &new_codes[0]).await, Ok(()));
+    }
+
+    #[test]
+    fn test_parse_recovery_code() {
+        let code = RecoveryCode::default();
+        assert_eq!(
+            RecoveryCode::parse(code.as_ref().expose_secret().to_owned()).unwrap(),
+            code
+        );
+        assert_eq!(
+            RecoveryCode::parse(" A1B2-C3D4-E5F6-G7H8 ".to_owned()).unwrap(),
+            RecoveryCode::parse("a1b2-c3d4-e5f6-g7h8".to_owned()).unwrap()
+        );
+        for invalid in [
+            "a1b2c3d4e5f6g7h8",
+            "a1b2-c3d4-e5f6-g7h",
+            "a1b2-c3d4-e5f6_g7h8",
+            "a1b2-c3d4-e5f6-g7h8-",
+            "123456",
+        ] {
+            assert!(RecoveryCode::parse(invalid.to_owned()).is_err());
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::{HashMap, HashSet};
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{Email, RefreshToken, RefreshTokenStore, RefreshTokenStoreError, SessionId};
+
+#[derive(Default)]
+pub struct HashmapRefreshTokenStore {
+    tokens: HashMap<String, RefreshTokenRecord>,
+    revoked_families: HashSet<SessionId>,
+}
+
+#[derive(Clone)]
+struct RefreshTokenRecord {
+    email: Email,
+    family_id: SessionId,
+    rotated: bool,
+}
+
+impl HashmapRefreshTokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        session_id: SessionId,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let record = RefreshTokenRecord {
+            email,
+            family_id: session_id,
+            rotated: false,
+        };
+        self.tokens
+            .insert(token.as_ref().expose_secret().to_owned(), record);
+        Ok(())
+    }
+
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+        new_token: RefreshToken,
+    ) -> Result<(Email, SessionId), RefreshTokenStoreError> {
+        let record = match self.tokens.get_mut(token.as_ref().expose_secret()) {
+            Some(record) => record,
+            None => return Err(RefreshTokenStoreError::TokenNotFound),
+        };
+        if self.revoked_families.contains(&record.family_id) {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+        if record.rotated {
+            self.revoked_families.insert(record.family_id.clone());
+            return Err(RefreshTokenStoreError::TokenReused);
+        }
+        record.rotated = true;
+
+        let new_record = RefreshTokenRecord {
+            rotated: false,
+            ..record.clone()
+        };
+        let result = (new_record.email.clone(), new_record.family_id.clone());
+        self.tokens
+            .insert(new_token.as_ref().expose_secret().to_owned(), new_record);
+        Ok(result)
+    }
+
+    async fn revoke_token(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        match self.tokens.get(token.as_ref().expose_secret()) {
+            Some(record) => {
+                self.revoked_families.insert(record.family_id.clone());
+                Ok(())
+            }
+            None => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn revoke_session(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.revoked_families.insert(session_id.clone());
+        Ok(())
+    }
+
+    async fn revoke_user_tokens(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        let family_ids = self
+            .tokens
+            .values()
+            .filter(|record| &record.email == email)
+            .map(|record| record.family_id.clone());
+        self.revoked_families.extend(family_ids);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse(Secret::new(String::from("a@b.com"))).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rotate_token_succeeds() {
+        let mut store = HashmapRefreshTokenStore::new();
+        let token = RefreshToken::default();
+        store
+            .add_token(email(), SessionId::default(), token.clone())
+            .await
+            .unwrap();
+
+        let result = store.rotate_token(&token, RefreshToken::default()).await;
+        assert_eq!(result.unwrap().0, email());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_unknown_token_fails() {
+        let mut store = HashmapRefreshTokenStore::new();
+        let result = store
+            .rotate_token(&RefreshToken::default(), RefreshToken::default())
+            .await;
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_reusing_rotated_token_revokes_family() {
+        let mut store = HashmapRefreshTokenStore::new();
+        let token = RefreshToken::default();
+        let new_token = RefreshToken::default();
+        store
+            .add_token(email(), SessionId::default(), token.clone())
+            .await
+            .unwrap();
+        store.rotate_token(&token, new_token.clone()).await.unwrap();
+
+        let result = store.rotate_token(&token, RefreshToken::default()).await;
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenReused));
+
+        let result = store
+            .rotate_token(&new_token, RefreshToken::default())
+            .await;
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_token() {
+        let mut store = HashmapRefreshTokenStore::new();
+        let token = RefreshToken::default();
+        store
+            .add_token(email(), SessionId::default(), token.clone())
+            .await
+            .unwrap();
+        assert_eq!(store.revoke_token(&token).await, Ok(()));
+
+        let result = store.rotate_token(&token, RefreshToken::default()).await;
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_session() {
+        let mut store = HashmapRefreshTokenStore::new();
+        let session_id = SessionId::default();
+        let token = RefreshToken::default();
+        let new_token = RefreshToken::default();
+        store
+            .add_token(email(), session_id.clone(), token.clone())
+            .await
+            .unwrap();
+        let result = store.rotate_token(&token, new_token.clone()).await;
+        assert_eq!(result.unwrap().1, session_id);
+
+        assert_eq!(store.revoke_session(&session_id).await, Ok(()));
+        let result = store
+            .rotate_token(&new_token, RefreshToken::default())
+            .await;
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_user_tokens() {
+        let mut store = HashmapRefreshTokenStore::new();
+        let token = RefreshToken::default();
+        let other_token = RefreshToken::default();
+        let other_email = Email::parse(Secret::new(String::from("c@d.com"))).unwrap();
+        store
+            .add_token(email(), SessionId::default(), token.clone())
+            .await
+            .unwrap();
+        store
+            .add_token(
+                other_email.clone(),
+                SessionId::default(),
+                other_token.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(store.revoke_user_tokens(&email()).await, Ok(()));
+
+        let result = store.rotate_token(&token, RefreshToken::default()).await;
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+        let result = store
+            .rotate_token(&other_token, RefreshToken::default())
+            .await;
+        assert_eq!(result.unwrap().0, other_email);
+    }
+}
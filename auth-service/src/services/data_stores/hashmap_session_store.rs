@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::{
+    domain::{Email, Session, SessionId, SessionStore, SessionStoreError},
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: HashMap<SessionId, Session>,
+}
+
+impl HashmapSessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .get(id)
+            .filter(|session| !is_expired(session))
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn get_user_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .values()
+            .filter(|session| &session.email == email && !is_expired(session))
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+        Ok(sessions)
+    }
+
+    async fn touch_session(
+        &mut self,
+        id: &SessionId,
+        jti: String,
+        ip: Option<String>,
+    ) -> Result<(), SessionStoreError> {
+        let session = self
+            .sessions
+            .get_mut(id)
+            .ok_or(SessionStoreError::SessionNotFound)?;
+        session.jti = jti;
+        session.ip = ip;
+        session.last_seen_at = Utc::now().timestamp();
+        Ok(())
+    }
+
+    async fn remove_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        self.sessions
+            .remove(id)
+            .map(|_| ())
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn remove_user_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| &session.email != email);
+        Ok(())
+    }
+}
+
+// A session ends once its refresh token has not been used for as long as it is valid
+fn is_expired(session: &Session) -> bool {
+    session.last_seen_at + REFRESH_TOKEN_TTL_SECONDS <= Utc::now().timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn session(email: &str) -> Session {
+        let now = Utc::now().timestamp();
+        Session {
+            id: SessionId::default(),
+            email: Email::parse(Secret::new(email.to_owned())).unwrap(),
+            jti: uuid::Uuid::new_v4().to_string(),
+            device: Some("Firefox on Linux".to_owned()),
+            ip: Some("127.0.0.1".to_owned()),
+            user_agent: None,
+            created_at: now,
+            last_seen_at: now,
+            two_factor: false,
+            authenticated_at: Some(now),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_user_sessions() {
+        let mut store = HashmapSessionStore::new();
+        let first = session("a@b.com");
+        let second = Session {
+            created_at: first.created_at + 1,
+            ..session("a@b.com")
+        };
+        let expired = Session {
+            last_seen_at: first.created_at - REFRESH_TOKEN_TTL_SECONDS,
+            ..session("a@b.com")
+        };
+        let other_user = session("c@d.com");
+        for session in [&second, &first, &expired, &other_user] {
+            store.add_session(session.clone()).await.unwrap();
+        }
+
+        let sessions = store.get_user_sessions(&first.email).await.unwrap();
+        assert_eq!(sessions, vec![first, second]);
+        assert_eq!(
+            store.get_session(&expired.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_touch_session() {
+        let mut store = HashmapSessionStore::new();
+        let session = Session {
+            last_seen_at: 0,
+            ..session("a@b.com")
+        };
+        store.add_session(session.clone()).await.unwrap();
+
+        store
+            .touch_session(&session.id, "new-jti".to_owned(), None)
+            .await
+            .unwrap();
+        let touched = store.get_session(&session.id).await.unwrap();
+        assert_eq!(touched.jti, "new-jti");
+        assert_eq!(touched.ip, None);
+        assert!(touched.last_seen_at > 0);
+
+        let result = store
+            .touch_session(&SessionId::default(), "jti".to_owned(), None)
+            .await;
+        assert_eq!(result, Err(SessionStoreError::SessionNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_remove_sessions() {
+        let mut store = HashmapSessionStore::new();
+        let first = session("a@b.com");
+        let second = session("a@b.com");
+        let other_user = session("c@d.com");
+        for session in [&first, &second, &other_user] {
+            store.add_session(session.clone()).await.unwrap();
+        }
+
+        assert_eq!(store.remove_session(&first.id).await, Ok(()));
+        assert_eq!(
+            store.remove_session(&first.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+
+        store.remove_user_sessions(&second.email).await.unwrap();
+        assert!(
+            store
+                .get_user_sessions(&second.email)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(store.get_session(&other_user.id).await, Ok(other_user));
+    }
+}
@@ -8,17 +8,19 @@ use crate::domain::{
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
     codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    failed_attempts: HashMap<Email, u32>,
 }
 
 impl HashmapTwoFACodeStore {
     pub fn new() -> Self {
-        Self {
-            codes: HashMap::new(),
-        }
+        Self::default()
     }
 
     pub fn new_with_codes(codes: HashMap<Email, (LoginAttemptId, TwoFACode)>) -> Self {
-        Self { codes }
+        Self {
+            codes,
+            failed_attempts: HashMap::new(),
+        }
     }
 }
 
@@ -31,13 +33,15 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
+        self.failed_attempts.remove(&email);
         match self.codes.insert(email, (login_attempt_id, code)) {
             Some(_) => Ok(()),
             None => Ok(()),
         }
     }
     async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        match self.codes.remove(&email) {
+        self.failed_attempts.remove(email);
+        match self.codes.remove(email) {
             Some(_) => Ok(()),
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
@@ -52,11 +56,20 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
+
+    async fn add_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        if !self.codes.contains_key(email) {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+        let failed_attempts = self.failed_attempts.entry(email.clone()).or_default();
+        *failed_attempts += 1;
+        Ok(*failed_attempts)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use secrecy::Secret;
+    use secrecy::{ExposeSecret, Secret};
 
     use super::*;
 
@@ -146,4 +159,44 @@ mod tests {
             Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
         );
     }
+
+    #[tokio::test]
+    async fn test_add_failed_attempt() {
+        let mut store = HashmapTwoFACodeStore::new();
+        let email = Email::parse(Secret::new(String::from("a@b.com"))).unwrap();
+        assert_eq!(
+            store.add_failed_attempt(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        store
+            .add_code(
+                email.clone(),
+                LoginAttemptId::default(),
+                TwoFACode::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(store.add_failed_attempt(&email).await, Ok(1));
+        assert_eq!(store.add_failed_attempt(&email).await, Ok(2));
+
+        // A new login attempt starts counting from scratch
+        store
+            .add_code(
+                email.clone(),
+                LoginAttemptId::default(),
+                TwoFACode::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(store.add_failed_attempt(&email).await, Ok(1));
+    }
+
+    #[test]
+    fn test_default_code_is_valid() {
+        for _ in 0..1000 {
+            let code = TwoFACode::default();
+            assert!(TwoFACode::parse(code.as_ref().expose_secret().to_owned()).is_ok());
+        }
+    }
 }
@@ -0,0 +1,155 @@
+use sqlx::PgPool;
+
+use crate::domain::{ClientId, OAuthClient, OAuthClientStore, OAuthClientStoreError, UserId};
+
+pub struct PostgresOAuthClientStore {
+    pool: PgPool,
+}
+
+impl PostgresOAuthClientStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+// A row of the oauth_clients table
+struct OAuthClientRow {
+    id: String,
+    name: String,
+    redirect_uris: Vec<String>,
+    scopes: Vec<String>,
+    secret_hash: Option<String>,
+}
+
+impl TryFrom<OAuthClientRow> for OAuthClient {
+    type Error = OAuthClientStoreError;
+
+    fn try_from(row: OAuthClientRow) -> Result<Self, Self::Error> {
+        Ok(OAuthClient {
+            id: ClientId::parse(row.id).map_err(OAuthClientStoreError::UnexpectedError)?,
+            name: row.name,
+            redirect_uris: row.redirect_uris,
+            scopes: row.scopes,
+            secret_hash: row.secret_hash,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for PostgresOAuthClientStore {
+    #[tracing::instrument(name = "Adding OAuth client to PostgreSQL", skip_all)]
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO oauth_clients (id, name, redirect_uris, scopes, secret_hash)
+            VALUES ($1::UUID, $2, $3, $4, $5)
+            "#,
+            client.id.as_ref() as &str,
+            client.name,
+            &client.redirect_uris,
+            &client.scopes,
+            client.secret_hash
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving OAuth client from PostgreSQL", skip_all)]
+    async fn get_client(&self, id: &ClientId) -> Result<OAuthClient, OAuthClientStoreError> {
+        sqlx::query_as!(
+            OAuthClientRow,
+            r#"SELECT id::TEXT AS "id!", name, redirect_uris, scopes, secret_hash
+            FROM oauth_clients WHERE id = $1::UUID"#,
+            id.as_ref() as &str
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?
+        .map(OAuthClient::try_from)
+        .unwrap_or(Err(OAuthClientStoreError::ClientNotFound))
+    }
+
+    #[tracing::instrument(name = "Listing OAuth clients from PostgreSQL", skip_all)]
+    async fn list_clients(&self) -> Result<Vec<OAuthClient>, OAuthClientStoreError> {
+        // Ordered byte by byte, like the hashmap store does
+        sqlx::query_as!(
+            OAuthClientRow,
+            r#"SELECT id::TEXT AS "id!", name, redirect_uris, scopes, secret_hash
+            FROM oauth_clients ORDER BY name COLLATE "C", id::TEXT"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(OAuthClient::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Deleting OAuth client from PostgreSQL", skip_all)]
+    async fn delete_client(&mut self, id: &ClientId) -> Result<(), OAuthClientStoreError> {
+        let result = sqlx::query!(
+            "DELETE FROM oauth_clients WHERE id = $1::UUID",
+            id.as_ref() as &str
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(OAuthClientStoreError::ClientNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving OAuth consent from PostgreSQL", skip_all)]
+    async fn get_consent(
+        &self,
+        user_id: &UserId,
+        client_id: &ClientId,
+    ) -> Result<Vec<String>, OAuthClientStoreError> {
+        let scopes = sqlx::query_scalar!(
+            "SELECT scopes FROM oauth_consents WHERE user_id = $1::UUID AND client_id = $2::UUID",
+            user_id.as_ref() as &str,
+            client_id.as_ref() as &str
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+        Ok(scopes.unwrap_or_default())
+    }
+
+    #[tracing::instrument(name = "Granting OAuth consent in PostgreSQL", skip_all)]
+    async fn grant_consent(
+        &mut self,
+        user_id: &UserId,
+        client_id: &ClientId,
+        scopes: &[String],
+    ) -> Result<(), OAuthClientStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO oauth_consents (user_id, client_id, scopes)
+            VALUES ($1::UUID, $2::UUID, $3)
+            ON CONFLICT (user_id, client_id) DO UPDATE SET
+                scopes = ARRAY(
+                    SELECT DISTINCT UNNEST(oauth_consents.scopes || EXCLUDED.scopes) ORDER BY 1
+                ),
+                granted_at = NOW()
+            "#,
+            user_id.as_ref() as &str,
+            client_id.as_ref() as &str,
+            scopes
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            // Consents are only given by logged in users, so the missing row is the client's
+            Some(db_error) if db_error.is_foreign_key_violation() => {
+                OAuthClientStoreError::ClientNotFound
+            }
+            _ => OAuthClientStoreError::UnexpectedError(e.into()),
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use secrecy::ExposeSecret;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::domain::{Email, RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError};
+
+pub struct PostgresRecoveryCodeStore {
+    pool: PgPool,
+}
+
+impl PostgresRecoveryCodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for PostgresRecoveryCodeStore {
+    #[tracing::instrument(name = "Setting recovery codes in PostgreSQL", skip_all)]
+    async fn set_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let code_hashes: Vec<String> = codes.iter().map(compute_code_hash).collect();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+        sqlx::query!(
+            "DELETE FROM recovery_codes WHERE email = $1",
+            email.as_ref().expose_secret()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+        sqlx::query!(
+            "INSERT INTO recovery_codes (email, code_hash) SELECT $1, * FROM UNNEST($2::TEXT[])",
+            email.as_ref().expose_secret(),
+            &code_hashes
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Using recovery code in PostgreSQL", skip_all)]
+    async fn use_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        // Finding and using the code in one statement means concurrent requests can't both use it
+        let result = sqlx::query!(
+            r#"
+            UPDATE recovery_codes SET used_at = NOW()
+            WHERE email = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            email.as_ref().expose_secret(),
+            compute_code_hash(code)
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(RecoveryCodeStoreError::CodeNotFound);
+        }
+        Ok(())
+    }
+}
+
+// Unlike passwords, recovery codes are random with more than 80 bits of entropy, so a
+// fast unsalted hash is enough to keep them from being recovered, and it lets codes be
+// looked up by their hash.
+fn compute_code_hash(code: &RecoveryCode) -> String {
+    let hash = Sha256::digest(code.as_ref().expose_secret().as_bytes());
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,183 @@
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{Email, Session, SessionId, SessionStore, SessionStoreError},
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct PostgresSessionStore {
+    pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+// Sessions end once their refresh token has not been used for as long as it is valid.
+// Timestamps are read and written as Unix timestamps.
+#[async_trait::async_trait]
+impl SessionStore for PostgresSessionStore {
+    #[tracing::instrument(name = "Adding session to PostgreSQL", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        // Clean up the user's ended sessions while at it
+        sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE email = $1 AND last_seen_at <= NOW() - $2::BIGINT * INTERVAL '1 second'
+            "#,
+            session.email.as_ref().expose_secret(),
+            REFRESH_TOKEN_TTL_SECONDS
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions
+                (id, email, jti, device, ip, user_agent, created_at, last_seen_at, two_factor,
+                authenticated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, to_timestamp($7::BIGINT), to_timestamp($8::BIGINT), $9,
+                to_timestamp($10::BIGINT))
+            "#,
+            session.id.as_ref(),
+            session.email.as_ref().expose_secret(),
+            session.jti,
+            session.device,
+            session.ip,
+            session.user_agent,
+            session.created_at,
+            session.last_seen_at,
+            session.two_factor,
+            session.authenticated_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving session from PostgreSQL", skip_all)]
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id, email, jti, device, ip, user_agent,
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS "created_at!",
+                EXTRACT(EPOCH FROM last_seen_at)::BIGINT AS "last_seen_at!",
+                two_factor,
+                EXTRACT(EPOCH FROM authenticated_at)::BIGINT AS authenticated_at
+            FROM sessions
+            WHERE id = $1 AND last_seen_at > NOW() - $2::BIGINT * INTERVAL '1 second'
+            "#,
+            id.as_ref(),
+            REFRESH_TOKEN_TTL_SECONDS
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?
+        .ok_or(SessionStoreError::SessionNotFound)?;
+
+        Ok(Session {
+            id: SessionId::parse(row.id).map_err(SessionStoreError::UnexpectedError)?,
+            email: Email::parse(Secret::new(row.email))
+                .map_err(SessionStoreError::UnexpectedError)?,
+            jti: row.jti,
+            device: row.device,
+            ip: row.ip,
+            user_agent: row.user_agent,
+            created_at: row.created_at,
+            last_seen_at: row.last_seen_at,
+            two_factor: row.two_factor,
+            authenticated_at: row.authenticated_at,
+        })
+    }
+
+    #[tracing::instrument(name = "Retrieving user sessions from PostgreSQL", skip_all)]
+    async fn get_user_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, jti, device, ip, user_agent,
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS "created_at!",
+                EXTRACT(EPOCH FROM last_seen_at)::BIGINT AS "last_seen_at!",
+                two_factor,
+                EXTRACT(EPOCH FROM authenticated_at)::BIGINT AS authenticated_at
+            FROM sessions
+            WHERE email = $1 AND last_seen_at > NOW() - $2::BIGINT * INTERVAL '1 second'
+            ORDER BY created_at
+            "#,
+            email.as_ref().expose_secret(),
+            REFRESH_TOKEN_TTL_SECONDS
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(Session {
+                    id: SessionId::parse(row.id).map_err(SessionStoreError::UnexpectedError)?,
+                    email: email.clone(),
+                    jti: row.jti,
+                    device: row.device,
+                    ip: row.ip,
+                    user_agent: row.user_agent,
+                    created_at: row.created_at,
+                    last_seen_at: row.last_seen_at,
+                    two_factor: row.two_factor,
+                    authenticated_at: row.authenticated_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Updating session in PostgreSQL", skip_all)]
+    async fn touch_session(
+        &mut self,
+        id: &SessionId,
+        jti: String,
+        ip: Option<String>,
+    ) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            "UPDATE sessions SET jti = $1, ip = $2, last_seen_at = NOW() WHERE id = $3",
+            jti,
+            ip,
+            id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing session from PostgreSQL", skip_all)]
+    async fn remove_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!("DELETE FROM sessions WHERE id = $1", id.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing user sessions from PostgreSQL", skip_all)]
+    async fn remove_user_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            "DELETE FROM sessions WHERE email = $1",
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+}
@@ -1,16 +1,25 @@
+use aes_gcm::{
+    Aes256Gcm, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng},
+};
 use argon2::{
     Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
     password_hash::SaltString,
 };
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
 
 use sqlx::PgPool;
 
-use crate::domain::{
-    Email, Password, User,
-    data_stores::{UserStore, UserStoreError},
+use crate::{
+    domain::{
+        Email, Password, Permission, Role, TotpSecret, TwoFAMethod, User, UserId, UserStatus,
+        data_stores::{UserStore, UserStoreError},
+    },
+    utils::TOTP_ENCRYPTION_KEY,
 };
-use color_eyre::eyre::{Context, Result};
+use color_eyre::eyre::{Context, Result, eyre};
 
 pub struct PostgresUserStore {
     pool: PgPool,
@@ -22,6 +31,39 @@ impl PostgresUserStore {
     }
 }
 
+// A row of the users table, as selected when retrieving a user
+struct UserRow {
+    id: String,
+    email: String,
+    password_hash: String,
+    requires_2fa: bool,
+    status: String,
+    two_fa_method: String,
+    deleted_at: Option<i64>,
+    password_reset_required: bool,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: UserId::parse(row.id).map_err(UserStoreError::UnexpectedError)?,
+            status: UserStatus::parse(&row.status).map_err(UserStoreError::UnexpectedError)?,
+            deleted_at: row.deleted_at,
+            password_reset_required: row.password_reset_required,
+            two_fa_method: TwoFAMethod::parse(&row.two_fa_method)
+                .map_err(UserStoreError::UnexpectedError)?,
+            ..User::new(
+                Email::parse(Secret::new(row.email)).map_err(UserStoreError::UnexpectedError)?,
+                Password::parse(Secret::new(row.password_hash))
+                    .map_err(UserStoreError::UnexpectedError)?,
+                row.requires_2fa,
+            )
+        })
+    }
+}
+
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
     #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
@@ -31,10 +73,13 @@ impl UserStore for PostgresUserStore {
             .map_err(UserStoreError::UnexpectedError)?;
 
         sqlx::query!(
-            "INSERT INTO users (email, password_hash, requires_2fa) VALUES ($1, $2, $3)",
+            "INSERT INTO users (id, email, password_hash, requires_2fa, status, two_fa_method) VALUES ($1::UUID, $2, $3, $4, $5, $6)",
+            user.id.as_ref() as &str,
             user.email.as_ref().expose_secret(),
             &password_hash.expose_secret(),
-            user.requires_2fa
+            user.requires_2fa,
+            user.status.as_str(),
+            user.two_fa_method.as_str()
         )
         .execute(&self.pool)
         .await
@@ -44,23 +89,82 @@ impl UserStore for PostgresUserStore {
 
     #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        let result: Result<User, UserStoreError> = sqlx::query!(
-            "SELECT email, password_hash, requires_2fa FROM users WHERE email = $1 LIMIT 1",
+        sqlx::query_as!(
+            UserRow,
+            r#"SELECT id::TEXT AS "id!", email, password_hash, requires_2fa, status,
+                two_fa_method, EXTRACT(EPOCH FROM deleted_at)::BIGINT AS deleted_at,
+                password_reset_required
+            FROM users WHERE email = $1 LIMIT 1"#,
             email.as_ref().expose_secret()
         )
         .fetch_optional(&self.pool)
         .await
         .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
-        .map(|row| {
-            Ok(User::new(
-                Email::parse(Secret::new(row.email)).map_err(UserStoreError::UnexpectedError)?,
-                Password::parse(Secret::new(row.password_hash))
-                    .map_err(UserStoreError::UnexpectedError)?,
-                row.requires_2fa,
-            ))
-        })
-        .unwrap_or(Err(UserStoreError::UserNotFound));
-        result
+        .map(User::try_from)
+        .unwrap_or(Err(UserStoreError::UserNotFound))
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id from PostgreSQL", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        sqlx::query_as!(
+            UserRow,
+            r#"SELECT id::TEXT AS "id!", email, password_hash, requires_2fa, status,
+                two_fa_method, EXTRACT(EPOCH FROM deleted_at)::BIGINT AS deleted_at,
+                password_reset_required
+            FROM users WHERE id = $1::UUID LIMIT 1"#,
+            id.as_ref() as &str
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .map(User::try_from)
+        .unwrap_or(Err(UserStoreError::UserNotFound))
+    }
+
+    #[tracing::instrument(name = "Listing users from PostgreSQL", skip_all)]
+    async fn list_users(
+        &self,
+        search: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<User>, u64), UserStoreError> {
+        // Wildcards in the search are matched literally
+        let pattern = search.map(|search| {
+            let escaped = search
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{}%", escaped)
+        });
+
+        let total = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM users WHERE $1::TEXT IS NULL OR email ILIKE $1"#,
+            pattern.as_deref()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        // Ordered byte by byte, like the hashmap store does
+        let users = sqlx::query_as!(
+            UserRow,
+            r#"SELECT id::TEXT AS "id!", email, password_hash, requires_2fa, status,
+                two_fa_method, EXTRACT(EPOCH FROM deleted_at)::BIGINT AS deleted_at,
+                password_reset_required
+            FROM users WHERE $1::TEXT IS NULL OR email ILIKE $1
+            ORDER BY email COLLATE "C" LIMIT $2 OFFSET $3"#,
+            pattern.as_deref(),
+            limit as i64,
+            offset as i64
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(User::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((users, total as u64))
     }
 
     #[tracing::instrument(name = "Validating user credentials in PostgreSQL", skip_all)]
@@ -81,8 +185,411 @@ impl UserStore for PostgresUserStore {
         .await
         .map_err(|_| UserStoreError::InvalidCredentials)
     }
+
+    #[tracing::instrument(name = "Updating user password in PostgreSQL", skip_all)]
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            "UPDATE users SET password_hash = $1, password_reset_required = FALSE WHERE email = $2",
+            password_hash.expose_secret(),
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Requiring password reset in PostgreSQL", skip_all)]
+    async fn require_password_reset(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET password_reset_required = TRUE WHERE email = $1",
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Setting user status in PostgreSQL", skip_all)]
+    async fn set_status(
+        &mut self,
+        email: &Email,
+        status: UserStatus,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET status = $1 WHERE email = $2",
+            status.as_str(),
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Marking user email as verified in PostgreSQL", skip_all)]
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"UPDATE users
+            SET status = CASE WHEN status = 'pending-verification' THEN 'active' ELSE status END
+            WHERE email = $1"#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Deleting user in PostgreSQL", skip_all)]
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET deleted_at = NOW() WHERE email = $1",
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Restoring user in PostgreSQL", skip_all)]
+    async fn restore_user(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET deleted_at = NULL WHERE email = $1",
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    // Rows referencing the users (sessions, recovery codes) are removed with them, since
+    // their foreign keys cascade on delete
+    #[tracing::instrument(name = "Purging deleted users from PostgreSQL", skip_all)]
+    async fn purge_deleted_users(
+        &mut self,
+        deleted_before: i64,
+    ) -> Result<Vec<Email>, UserStoreError> {
+        let rows = sqlx::query!(
+            "DELETE FROM users WHERE deleted_at < to_timestamp($1::BIGINT) RETURNING email",
+            deleted_before
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Email::parse(Secret::new(row.email)).map_err(UserStoreError::UnexpectedError)
+            })
+            .collect()
+    }
+
+    // A single statement, so the row moves atomically. Rows referencing the user follow
+    // along, since their foreign keys cascade on update.
+    #[tracing::instrument(name = "Updating user email in PostgreSQL", skip_all)]
+    async fn update_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET email = $1 WHERE email = $2",
+            new_email.as_ref().expose_secret(),
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => UserStoreError::UserAlreadyExists,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Setting pending TOTP secret in PostgreSQL", skip_all)]
+    async fn set_pending_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        let encrypted_secret =
+            encrypt_totp_secret(&secret).map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            "UPDATE users SET pending_totp_secret = $1 WHERE email = $2",
+            encrypted_secret,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving pending TOTP secret from PostgreSQL", skip_all)]
+    async fn get_pending_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        let encrypted_secret = sqlx::query_scalar!(
+            "SELECT pending_totp_secret FROM users WHERE email = $1",
+            email.as_ref().expose_secret()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .flatten()
+        .ok_or(UserStoreError::TotpSecretNotFound)?;
+
+        decrypt_totp_secret(&encrypted_secret).map_err(UserStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Enabling TOTP in PostgreSQL", skip_all)]
+    async fn enable_totp(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_secret = pending_totp_secret,
+                pending_totp_secret = NULL,
+                two_fa_method = $1,
+                requires_2fa = TRUE
+            WHERE email = $2 AND pending_totp_secret IS NOT NULL
+            "#,
+            TwoFAMethod::Totp.as_str(),
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::TotpSecretNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving TOTP secret from PostgreSQL", skip_all)]
+    async fn get_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        let encrypted_secret = sqlx::query_scalar!(
+            "SELECT totp_secret FROM users WHERE email = $1",
+            email.as_ref().expose_secret()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .flatten()
+        .ok_or(UserStoreError::TotpSecretNotFound)?;
+
+        decrypt_totp_secret(&encrypted_secret).map_err(UserStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Using TOTP time step in PostgreSQL", skip_all)]
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<(), UserStoreError> {
+        let step: i64 = step
+            .try_into()
+            .map_err(|e: std::num::TryFromIntError| UserStoreError::UnexpectedError(e.into()))?;
+        // Checked and recorded in one statement, so concurrent requests can't both use a code
+        let result = sqlx::query!(
+            r#"
+            UPDATE users SET totp_last_step = $1
+            WHERE email = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)
+            "#,
+            step,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            self.get_user(email).await?;
+            return Err(UserStoreError::TotpCodeReused);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Setting 2FA requirement in PostgreSQL", skip_all)]
+    async fn set_requires_2fa(
+        &mut self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET requires_2fa = $1,
+                two_fa_method = CASE WHEN $1 THEN two_fa_method ELSE $2 END,
+                totp_secret = CASE WHEN $1 THEN totp_secret END,
+                pending_totp_secret = CASE WHEN $1 THEN pending_totp_secret END
+            WHERE email = $3
+            "#,
+            requires_2fa,
+            TwoFAMethod::Email.as_str(),
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving roles from PostgreSQL", skip_all)]
+    async fn get_roles(&self, id: &UserId) -> Result<Vec<Role>, UserStoreError> {
+        let rows = sqlx::query!(
+            "SELECT role FROM user_roles WHERE user_id = $1::UUID ORDER BY role",
+            id.as_ref() as &str
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| Role::parse(&row.role).map_err(UserStoreError::UnexpectedError))
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Granting role in PostgreSQL", skip_all)]
+    async fn grant_role(&mut self, id: &UserId, role: Role) -> Result<(), UserStoreError> {
+        sqlx::query!(
+            "INSERT INTO user_roles (user_id, role) VALUES ($1::UUID, $2) ON CONFLICT DO NOTHING",
+            id.as_ref() as &str,
+            role.as_str()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            // The role is known to the code, so the missing row is the user's
+            Some(db_error) if db_error.is_foreign_key_violation() => UserStoreError::UserNotFound,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking role in PostgreSQL", skip_all)]
+    async fn revoke_role(&mut self, id: &UserId, role: Role) -> Result<(), UserStoreError> {
+        let user_exists = sqlx::query_scalar!(
+            r#"
+            WITH revoked AS (
+                DELETE FROM user_roles WHERE user_id = $1::UUID AND role = $2
+            )
+            SELECT EXISTS(SELECT 1 FROM users WHERE id = $1::UUID) AS "exists!"
+            "#,
+            id.as_ref() as &str,
+            role.as_str()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if !user_exists {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving permissions from PostgreSQL", skip_all)]
+    async fn get_permissions(&self, id: &UserId) -> Result<Vec<Permission>, UserStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT DISTINCT role_permissions.permission
+            FROM user_roles
+            JOIN role_permissions ON role_permissions.role = user_roles.role
+            WHERE user_roles.user_id = $1::UUID
+            ORDER BY role_permissions.permission
+            "#,
+            id.as_ref() as &str
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| Permission::parse(&row.permission).map_err(UserStoreError::UnexpectedError))
+            .collect()
+    }
 }
 
+// TOTP secrets have to be readable to check codes, so unlike passwords they can't be
+// hashed. They are encrypted with AES-256-GCM instead, under a key derived from
+// TOTP_ENCRYPTION_KEY. The stored value is base64(nonce || ciphertext).
+fn totp_cipher() -> Aes256Gcm {
+    let key = Sha256::digest(TOTP_ENCRYPTION_KEY.expose_secret().as_bytes());
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+fn encrypt_totp_secret(secret: &TotpSecret) -> Result<String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = totp_cipher()
+        .encrypt(&nonce, secret.as_ref().expose_secret().as_bytes())
+        .map_err(|_| eyre!("failed to encrypt TOTP secret"))?;
+    let mut stored = nonce.to_vec();
+    stored.extend(ciphertext);
+    Ok(BASE64.encode(stored))
+}
+
+fn decrypt_totp_secret(stored: &str) -> Result<TotpSecret> {
+    let stored = BASE64
+        .decode(stored)
+        .wrap_err("failed to decode encrypted TOTP secret")?;
+    if stored.len() < NONCE_LENGTH {
+        return Err(eyre!("encrypted TOTP secret is too short"));
+    }
+    let (nonce, ciphertext) = stored.split_at(NONCE_LENGTH);
+    let plaintext = totp_cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| eyre!("failed to decrypt TOTP secret"))?;
+    let secret = String::from_utf8(plaintext).wrap_err("decrypted TOTP secret is not UTF-8")?;
+    TotpSecret::parse(Secret::new(secret))
+}
+
+// AES-GCM uses 96-bit nonces
+const NONCE_LENGTH: usize = 12;
+
 // Helper function to verify if a given password matches an expected hash
 // TODO: Hashing is a CPU-intensive operation. To avoid blocking
 // other async tasks, update this function to perform hashing on a
@@ -97,7 +604,7 @@ async fn verify_password_hash(
     let result = tokio::task::spawn_blocking(move || {
         current_span.in_scope(|| {
             let expected_password_hash: PasswordHash<'_> =
-                PasswordHash::new(&expected_password_hash.expose_secret())?;
+                PasswordHash::new(expected_password_hash.expose_secret())?;
 
             Argon2::default()
                 .verify_password(
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        AuthorizationCode, AuthorizationCodeStore, AuthorizationCodeStoreError, AuthorizationGrant,
+        ClientId, CodeChallenge, SessionId, UserId,
+    },
+    utils::auth::AUTHORIZATION_CODE_TTL_SECONDS,
+};
+
+pub struct RedisAuthorizationCodeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisAuthorizationCodeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for RedisAuthorizationCodeStore {
+    #[tracing::instrument(name = "Adding authorization code to Redis", skip_all)]
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        let data = StoredGrant {
+            client_id: grant.client_id.as_ref().to_owned(),
+            user_id: grant.user_id.as_ref().to_owned(),
+            session_id: grant.session_id.as_ref().to_owned(),
+            redirect_uri: grant.redirect_uri,
+            redirect_uri_required: grant.redirect_uri_required,
+            scopes: grant.scopes,
+            code_challenge: grant.code_challenge.as_ref().to_owned(),
+            nonce: grant.nonce,
+        };
+        let serialized = serde_json::to_string(&data)
+            .wrap_err("failed to serialize authorization grant")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(&code), serialized, AUTHORIZATION_CODE_TTL_SECONDS)
+            .wrap_err("failed to set authorization code in Redis")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Taking authorization code from Redis", skip_all)]
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        // Getting and deleting the code in one command means it can't be exchanged twice
+        let value_stored: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(code))
+            .wrap_err("failed to take authorization code from Redis")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+        let value_stored = value_stored.ok_or(AuthorizationCodeStoreError::CodeNotFound)?;
+        let data: StoredGrant = serde_json::from_str(&value_stored)
+            .wrap_err("failed to deserialize authorization grant")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        Ok(AuthorizationGrant {
+            client_id: ClientId::parse(data.client_id)
+                .map_err(AuthorizationCodeStoreError::UnexpectedError)?,
+            user_id: UserId::parse(data.user_id)
+                .map_err(AuthorizationCodeStoreError::UnexpectedError)?,
+            session_id: SessionId::parse(data.session_id)
+                .map_err(AuthorizationCodeStoreError::UnexpectedError)?,
+            redirect_uri: data.redirect_uri,
+            redirect_uri_required: data.redirect_uri_required,
+            scopes: data.scopes,
+            code_challenge: CodeChallenge::parse(data.code_challenge)
+                .map_err(AuthorizationCodeStoreError::UnexpectedError)?,
+            nonce: data.nonce,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredGrant {
+    client_id: String,
+    user_id: String,
+    session_id: String,
+    redirect_uri: String,
+    // Missing from grants stored before it was recorded
+    #[serde(default)]
+    redirect_uri_required: bool,
+    scopes: Vec<String>,
+    code_challenge: String,
+    nonce: Option<String>,
+}
+
+const AUTHORIZATION_CODE_PREFIX: &str = "authorization_code:";
+
+fn get_key(code: &AuthorizationCode) -> String {
+    format!(
+        "{}{}",
+        AUTHORIZATION_CODE_PREFIX,
+        code.as_ref().expose_secret()
+    )
+}
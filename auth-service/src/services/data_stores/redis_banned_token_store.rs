@@ -1,12 +1,15 @@
 use redis::{Commands, Connection};
 
+use chrono::Utc;
 use color_eyre::eyre::WrapErr;
-use secrecy::{ExposeSecret, Secret};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
+    domain::{
+        UserId,
+        data_stores::{BannedTokenStore, BannedTokenStoreError},
+    },
     utils::auth::TOKEN_TTL_SECONDS,
 };
 
@@ -23,10 +26,10 @@ impl RedisBannedTokenStore {
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument(name = "add banned JWT in redis", skip_all)]
-    async fn add_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+    async fn add_token(&mut self, jti: String) -> Result<(), BannedTokenStoreError> {
         // TODO:
         // 1. Create a new key using the get_key helper function.
-        let key = get_key(&token.expose_secret());
+        let key = get_key(&jti);
         // 2. Call the set_ex command on the Redis connection to set a new key/value pair with an expiration time (TTL).
         // The value should simply be a `true` (boolean value).
         // The expiration time should be set to TOKEN_TTL_SECONDS.
@@ -50,8 +53,8 @@ impl BannedTokenStore for RedisBannedTokenStore {
     }
 
     #[tracing::instrument(name = "Check whether JWT is banned (i.e. exists in Redis)", skip_all)]
-    async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        let token_key = get_key(token.expose_secret());
+    async fn contains_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        let token_key = get_key(jti);
 
         let is_banned: bool = self
             .conn
@@ -63,11 +66,51 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
         Ok(is_banned)
     }
+
+    #[tracing::instrument(name = "Revoke all JWTs of a user in redis", skip_all)]
+    async fn revoke_user_tokens(&mut self, user_id: &UserId) -> Result<(), BannedTokenStoreError> {
+        let key = get_user_key(user_id);
+        // Once TOKEN_TTL_SECONDS have passed every token issued before now has expired anyway
+        let ttl: u64 = TOKEN_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast TOKEN_TTL_SECONDS to u64")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(&key, Utc::now().timestamp(), ttl)
+            .wrap_err("failed to set user token revocation time in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Get the time all JWTs of a user were revoked", skip_all)]
+    async fn get_user_tokens_revoked_at(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Option<i64>, BannedTokenStoreError> {
+        let key = get_user_key(user_id);
+
+        self.conn
+            .write()
+            .await
+            .get(&key)
+            .wrap_err("failed to get user token revocation time from Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)
+    }
 }
 
 // We are using a key prefix to prevent collisions and organize data!
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+const REVOKED_USER_TOKENS_KEY_PREFIX: &str = "revoked_user_tokens:";
+
+fn get_key(jti: &str) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, jti)
+}
 
-fn get_key(token: &str) -> String {
-    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
+fn get_user_key(user_id: &UserId) -> String {
+    format!("{}{}", REVOKED_USER_TOKENS_KEY_PREFIX, user_id.as_ref())
 }
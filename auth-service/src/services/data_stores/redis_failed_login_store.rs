@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use color_eyre::eyre::WrapErr;
+use redis::{Commands, Connection};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Email, FailedLoginStore, FailedLoginStoreError, FailedLogins},
+    utils::LOGIN_LOCKOUT_DURATION,
+};
+
+pub struct RedisFailedLoginStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisFailedLoginStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+// The count is kept under a key that expires a lockout duration after the latest failed login
+#[async_trait::async_trait]
+impl FailedLoginStore for RedisFailedLoginStore {
+    #[tracing::instrument(name = "Retrieving failed logins from Redis", skip_all)]
+    async fn get_failed_logins(
+        &self,
+        email: &Email,
+    ) -> Result<FailedLogins, FailedLoginStoreError> {
+        let mut conn = self.conn.write().await;
+        get_record(&mut conn, &get_key(email))
+    }
+
+    #[tracing::instrument(name = "Adding failed login to Redis", skip_all)]
+    async fn add_failed_login(
+        &mut self,
+        email: &Email,
+    ) -> Result<FailedLogins, FailedLoginStoreError> {
+        let key = get_key(email);
+        let mut conn = self.conn.write().await;
+        let record = get_record(&mut conn, &key)?;
+        let record = FailedLogins {
+            count: record.count + 1,
+            last_failed_at: Utc::now().timestamp(),
+        };
+
+        let serialized = serde_json::to_string(&FailedLoginsRecord {
+            count: record.count,
+            last_failed_at: record.last_failed_at,
+        })
+        .wrap_err("failed to serialize failed logins")
+        .map_err(FailedLoginStoreError::UnexpectedError)?;
+        let _: () = conn
+            .set_ex(&key, serialized, LOGIN_LOCKOUT_DURATION.as_secs())
+            .wrap_err("failed to set failed logins in Redis")
+            .map_err(FailedLoginStoreError::UnexpectedError)?;
+        Ok(record)
+    }
+
+    #[tracing::instrument(name = "Resetting failed logins in Redis", skip_all)]
+    async fn reset_failed_logins(&mut self, email: &Email) -> Result<(), FailedLoginStoreError> {
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .del(get_key(email))
+            .wrap_err("failed to delete failed logins from Redis")
+            .map_err(FailedLoginStoreError::UnexpectedError)?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FailedLoginsRecord {
+    count: u32,
+    last_failed_at: i64,
+}
+
+fn get_record(conn: &mut Connection, key: &str) -> Result<FailedLogins, FailedLoginStoreError> {
+    let value: Option<String> = conn
+        .get(key)
+        .wrap_err("failed to get failed logins from Redis")
+        .map_err(FailedLoginStoreError::UnexpectedError)?;
+    let Some(value) = value else {
+        return Ok(FailedLogins::default());
+    };
+    let record: FailedLoginsRecord = serde_json::from_str(&value)
+        .wrap_err("failed to deserialize failed logins")
+        .map_err(FailedLoginStoreError::UnexpectedError)?;
+    Ok(FailedLogins {
+        count: record.count,
+        last_failed_at: record.last_failed_at,
+    })
+}
+
+const FAILED_LOGINS_PREFIX: &str = "failed_logins:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", FAILED_LOGINS_PREFIX, email.as_ref().expose_secret())
+}
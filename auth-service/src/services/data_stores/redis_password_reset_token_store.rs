@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::ExposeSecret;
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Email, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+    utils::auth::PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisPasswordResetTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+    #[tracing::instrument(name = "Adding password reset token to Redis", skip_all)]
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let key = get_key(&email);
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(
+                &key,
+                token.as_ref().expose_secret(),
+                PASSWORD_RESET_TOKEN_TTL_SECONDS,
+            )
+            .wrap_err("failed to set password reset token in Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing password reset token from Redis", skip_all)]
+    async fn remove_token(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        let key = get_key(email);
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .del(&key)
+            .wrap_err("failed to delete password reset token from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking for password reset token in Redis", skip_all)]
+    async fn get_token(
+        &self,
+        email: &Email,
+    ) -> Result<PasswordResetToken, PasswordResetTokenStoreError> {
+        let key = get_key(email);
+        let value_stored: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get(&key)
+            .wrap_err("failed to get password reset token from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+        let value_stored = value_stored.ok_or(PasswordResetTokenStoreError::TokenNotFound)?;
+        PasswordResetToken::parse(value_stored)
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)
+    }
+}
+
+const PASSWORD_RESET_TOKEN_PREFIX: &str = "password_reset_token:";
+
+fn get_key(email: &Email) -> String {
+    format!(
+        "{}{}",
+        PASSWORD_RESET_TOKEN_PREFIX,
+        email.as_ref().expose_secret()
+    )
+}
@@ -0,0 +1,74 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use color_eyre::eyre::WrapErr;
+use lazy_static::lazy_static;
+use redis::{Connection, Script};
+use tokio::sync::RwLock;
+
+use crate::domain::{RateLimit, RateLimitStore, RateLimitStoreError};
+
+pub struct RedisRateLimitStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRateLimitStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+lazy_static! {
+    // Same as `TokenBucket::take`, run as a script so that instances of the service sharing
+    // a bucket can't both take its last token. Returns the milliseconds to wait, if any.
+    static ref TAKE_TOKEN_SCRIPT: Script = Script::new(
+        r"
+        local capacity = tonumber(ARGV[1])
+        local refill_interval = tonumber(ARGV[2])
+        local now = tonumber(ARGV[3])
+
+        local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'updated_at')
+        local tokens = tonumber(bucket[1]) or capacity
+        local updated_at = tonumber(bucket[2]) or now
+        local elapsed = math.max(now - updated_at, 0)
+        tokens = math.min(tokens + elapsed / refill_interval, capacity)
+
+        local wait = 0
+        if tokens >= 1 then
+            tokens = tokens - 1
+        else
+            wait = math.ceil((1 - tokens) * refill_interval)
+        end
+
+        redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated_at', now)
+        redis.call('PEXPIRE', KEYS[1], capacity * refill_interval)
+        return wait
+        "
+    );
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for RedisRateLimitStore {
+    #[tracing::instrument(name = "Taking rate limit token in Redis", skip_all)]
+    async fn take_token(
+        &mut self,
+        key: &str,
+        rate_limit: &RateLimit,
+    ) -> Result<Option<Duration>, RateLimitStoreError> {
+        let wait: u64 = TAKE_TOKEN_SCRIPT
+            .key(get_key(key))
+            .arg(rate_limit.capacity)
+            .arg(rate_limit.refill_interval.as_millis() as u64)
+            .arg(Utc::now().timestamp_millis())
+            .invoke(&mut *self.conn.write().await)
+            .wrap_err("failed to take rate limit token in Redis")
+            .map_err(RateLimitStoreError::UnexpectedError)?;
+        Ok((wait > 0).then(|| Duration::from_millis(wait)))
+    }
+}
+
+const RATE_LIMIT_PREFIX: &str = "rate_limit:";
+
+fn get_key(key: &str) -> String {
+    format!("{}{}", RATE_LIMIT_PREFIX, key)
+}
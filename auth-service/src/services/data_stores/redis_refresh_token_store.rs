@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Email, RefreshToken, RefreshTokenStore, RefreshTokenStoreError, SessionId},
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisRefreshTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    #[tracing::instrument(name = "Adding refresh token to Redis", skip_all)]
+    async fn add_token(
+        &mut self,
+        email: Email,
+        session_id: SessionId,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let record = RefreshTokenRecord {
+            email: email.as_ref().expose_secret().to_owned(),
+            family_id: session_id.as_ref().to_owned(),
+            rotated: false,
+        };
+        let mut conn = self.conn.write().await;
+        set_record(&mut conn, &token, &record)?;
+
+        // Remember the family under the user as well, so all of them can be revoked at once
+        let user_key = get_user_families_key(&email);
+        let _: () = conn
+            .sadd(&user_key, &record.family_id)
+            .wrap_err("failed to add refresh token family to user in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+        let _: () = conn
+            .expire(&user_key, REFRESH_TOKEN_TTL_SECONDS)
+            .wrap_err("failed to set expiry of user refresh token families in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Rotating refresh token in Redis", skip_all)]
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+        new_token: RefreshToken,
+    ) -> Result<(Email, SessionId), RefreshTokenStoreError> {
+        let mut conn = self.conn.write().await;
+        let mut record = get_record(&mut conn, token)?;
+
+        let family_revoked: bool = conn
+            .exists(get_family_key(&record.family_id))
+            .wrap_err("failed to check if refresh token family is revoked in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+        if family_revoked {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+        if record.rotated {
+            revoke_family(&mut conn, &record.family_id)?;
+            return Err(RefreshTokenStoreError::TokenReused);
+        }
+
+        record.rotated = true;
+        set_record(&mut conn, token, &record)?;
+
+        let new_record = RefreshTokenRecord {
+            rotated: false,
+            ..record
+        };
+        set_record(&mut conn, &new_token, &new_record)?;
+
+        let email = Email::parse(Secret::new(new_record.email))
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+        let session_id = SessionId::parse(new_record.family_id)
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+        Ok((email, session_id))
+    }
+
+    #[tracing::instrument(name = "Revoking refresh token in Redis", skip_all)]
+    async fn revoke_token(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let mut conn = self.conn.write().await;
+        let record = get_record(&mut conn, token)?;
+        revoke_family(&mut conn, &record.family_id)
+    }
+
+    #[tracing::instrument(name = "Revoking refresh tokens of a session in Redis", skip_all)]
+    async fn revoke_session(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let mut conn = self.conn.write().await;
+        revoke_family(&mut conn, session_id.as_ref())
+    }
+
+    #[tracing::instrument(name = "Revoking all refresh tokens of a user in Redis", skip_all)]
+    async fn revoke_user_tokens(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        let mut conn = self.conn.write().await;
+        let user_key = get_user_families_key(email);
+        let family_ids: Vec<String> = conn
+            .smembers(&user_key)
+            .wrap_err("failed to get refresh token families of user from Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+        for family_id in family_ids {
+            revoke_family(&mut conn, &family_id)?;
+        }
+        conn.del(&user_key)
+            .wrap_err("failed to delete refresh token families of user from Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenRecord {
+    email: String,
+    family_id: String,
+    rotated: bool,
+}
+
+fn get_record(
+    conn: &mut Connection,
+    token: &RefreshToken,
+) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+    let value_stored: Option<String> = conn
+        .get(get_key(token))
+        .wrap_err("failed to get refresh token from Redis")
+        .map_err(RefreshTokenStoreError::UnexpectedError)?;
+    let value_stored = value_stored.ok_or(RefreshTokenStoreError::TokenNotFound)?;
+    serde_json::from_str(&value_stored)
+        .wrap_err("failed to deserialize refresh token record")
+        .map_err(RefreshTokenStoreError::UnexpectedError)
+}
+
+fn set_record(
+    conn: &mut Connection,
+    token: &RefreshToken,
+    record: &RefreshTokenRecord,
+) -> Result<(), RefreshTokenStoreError> {
+    let serialized = serde_json::to_string(record)
+        .wrap_err("failed to serialize refresh token record")
+        .map_err(RefreshTokenStoreError::UnexpectedError)?;
+    conn.set_ex(get_key(token), serialized, ttl()?)
+        .wrap_err("failed to set refresh token in Redis")
+        .map_err(RefreshTokenStoreError::UnexpectedError)
+}
+
+fn revoke_family(conn: &mut Connection, family_id: &str) -> Result<(), RefreshTokenStoreError> {
+    conn.set_ex(get_family_key(family_id), true, ttl()?)
+        .wrap_err("failed to revoke refresh token family in Redis")
+        .map_err(RefreshTokenStoreError::UnexpectedError)
+}
+
+fn ttl() -> Result<u64, RefreshTokenStoreError> {
+    REFRESH_TOKEN_TTL_SECONDS
+        .try_into()
+        .wrap_err("failed to cast REFRESH_TOKEN_TTL_SECONDS to u64")
+        .map_err(RefreshTokenStoreError::UnexpectedError)
+}
+
+const REFRESH_TOKEN_KEY_PREFIX: &str = "refresh_token:";
+const REVOKED_FAMILY_KEY_PREFIX: &str = "revoked_refresh_token_family:";
+const USER_FAMILIES_KEY_PREFIX: &str = "refresh_token_families:";
+
+// Only the hash of a token is kept, so reading Redis doesn't hand out live sessions. Tokens
+// have more than 256 bits of entropy, so an unsalted SHA-256 is enough, and it still lets them
+// be looked up.
+fn get_key(token: &RefreshToken) -> String {
+    let hash = Sha256::digest(token.as_ref().expose_secret().as_bytes());
+    let hash: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}{}", REFRESH_TOKEN_KEY_PREFIX, hash)
+}
+
+fn get_family_key(family_id: &str) -> String {
+    format!("{}{}", REVOKED_FAMILY_KEY_PREFIX, family_id)
+}
+
+fn get_user_families_key(email: &Email) -> String {
+    format!(
+        "{}{}",
+        USER_FAMILIES_KEY_PREFIX,
+        email.as_ref().expose_secret()
+    )
+}
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use color_eyre::eyre::Context;
-use redis::{Commands, Connection};
+use redis::{Commands, Connection, SetExpiry, SetOptions};
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
@@ -37,6 +37,7 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         let data = TwoFATuple(
             login_attempt_id.as_ref().expose_secret().to_owned(),
             code.as_ref().expose_secret().to_owned(),
+            0,
         );
         // 3. Use serde_json::to_string to serialize the TwoFATuple instance into a JSON string.
         // Return TwoFACodeStoreError::UnexpectedError if serialization fails.
@@ -101,10 +102,37 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         let code = TwoFACode::parse(data.1).map_err(TwoFACodeStoreError::UnexpectedError)?;
         Ok((login_attempt_id, code))
     }
+
+    #[tracing::instrument(name = "Adding failed 2FA attempt to Redis", skip_all)]
+    async fn add_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        let key = get_key(email);
+        let mut conn = self.conn.write().await;
+        let value_stored: Option<String> = conn
+            .get(&key)
+            .wrap_err("failed to get 2FA code from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let value_stored = value_stored.ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+        let mut data: TwoFATuple = serde_json::from_str(&value_stored)
+            .wrap_err("failed to deserialize 2FA tuple")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        data.2 += 1;
+
+        let serialized = serde_json::to_string(&data)
+            .wrap_err("failed to serialize 2FA tuple")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        // The login attempt doesn't last any longer for having been tried
+        let options = SetOptions::default().with_expiration(SetExpiry::KEEPTTL);
+        let _: () = conn
+            .set_options(&key, serialized, options)
+            .wrap_err("failed to set 2FA code in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        Ok(data.2)
+    }
 }
 
+// Login attempt ID, 2FA code and the number of wrong codes given so far
 #[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct TwoFATuple(pub String, pub String, pub u32);
 
 const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
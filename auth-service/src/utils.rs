@@ -1,7 +1,19 @@
+pub mod account_deletion;
 pub mod auth;
+pub mod client_info;
 pub mod constants;
+pub mod rate_limit;
+pub mod roles;
+pub mod signing_key;
+pub mod totp;
 pub mod tracing;
 
+pub use account_deletion::*;
 pub use auth::*;
+pub use client_info::*;
 pub use constants::*;
+pub use rate_limit::*;
+pub use roles::*;
+pub use signing_key::*;
+pub use totp::*;
 pub use tracing::*;
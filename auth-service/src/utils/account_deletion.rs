@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use color_eyre::eyre::Result;
+
+use crate::{
+    app_state::AppState,
+    domain::{Email, TwoFACodeStoreError},
+};
+
+// Removes the accounts deleted before the given Unix timestamp for good, along with what
+// other stores keep about them. Returns the email addresses of the purged accounts.
+#[tracing::instrument(name = "Purging deleted accounts", skip_all)]
+pub async fn purge_deleted_accounts(state: &AppState, deleted_before: i64) -> Result<Vec<Email>> {
+    let emails = state
+        .user_store
+        .write()
+        .await
+        .purge_deleted_users(deleted_before)
+        .await?;
+    // The users are gone already, so one account's leftovers don't keep the others' around
+    for email in &emails {
+        if let Err(e) = purge_account_data(email, state).await {
+            tracing::error!("Failed to purge the data of a deleted account: {:?}", e);
+        }
+    }
+    Ok(emails)
+}
+
+async fn purge_account_data(email: &Email, state: &AppState) -> Result<()> {
+    match state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_code(email)
+        .await
+    {
+        Ok(()) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {}
+        Err(e) => return Err(e.into()),
+    }
+    state
+        .session_store
+        .write()
+        .await
+        .remove_user_sessions(email)
+        .await?;
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_user_tokens(email)
+        .await?;
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .set_codes(email, Vec::new())
+        .await?;
+    state
+        .failed_login_store
+        .write()
+        .await
+        .reset_failed_logins(email)
+        .await?;
+    Ok(())
+}
+
+// Purges accounts once they have been deleted for longer than the grace period
+pub async fn manage_deleted_accounts(state: AppState, grace_period: Duration) {
+    let mut interval = tokio::time::interval(ACCOUNT_PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let deleted_before = Utc::now().timestamp() - grace_period.as_secs() as i64;
+        match purge_deleted_accounts(&state, deleted_before).await {
+            Ok(emails) if !emails.is_empty() => {
+                tracing::info!("Purged {} deleted accounts", emails.len());
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to purge deleted accounts: {:?}", e),
+        }
+    }
+}
+
+const ACCOUNT_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
@@ -1,32 +1,228 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum::http::{HeaderMap, header::AUTHORIZATION};
+use axum_extra::extract::{
+    CookieJar,
+    cookie::{Cookie, SameSite},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
 use chrono::Utc;
 use color_eyre::eyre::{ContextCompat, Result, eyre};
-use jsonwebtoken::{DecodingKey, EncodingKey, Validation, decode, encode};
+use jsonwebtoken::{decode, decode_header, encode};
 use secrecy::{ExposeSecret, Secret};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
 
-use crate::{app_state::BannedTokenStoreType, domain::email::Email};
+use crate::{
+    app_state::{
+        AppState, BannedTokenStoreType, OAuthClientStoreType, RecoveryCodeStoreType,
+        SessionStoreType, UserStoreType,
+    },
+    domain::{
+        AuthAPIError, ClientId, OAuthClient, OAuthClientStoreError, OAuthError, Permission,
+        RecoveryCode, RefreshToken, Role, Session, SessionId, SessionStoreError, User, UserId,
+        UserStatus, UserStoreError, email::Email,
+    },
+};
 use color_eyre::eyre::WrapErr;
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::{
+    client_info::ClientInfo,
+    constants::{JWT_AUDIENCE, JWT_COOKIE_NAME, JWT_ISSUER, REFRESH_TOKEN_COOKIE_NAME},
+    signing_key::{find_verification_key, read_key_ring},
+};
 
-// Create cookie with a new JWT auth token
+// Create cookie with a new JWT auth token for the session. The id of the token
+// is returned as well, so the session can keep track of it.
 #[tracing::instrument(name = "Generating the auth cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
-    Ok(create_auth_cookie(token))
+pub fn generate_auth_cookie(
+    user_id: &UserId,
+    roles: &[Role],
+    permissions: &[Permission],
+    session_id: &SessionId,
+) -> Result<(Cookie<'static>, String)> {
+    let jti = uuid::Uuid::new_v4().to_string();
+    let token = generate_auth_token(user_id, roles, permissions, session_id, jti.clone())?;
+    Ok((create_auth_cookie(token), jti))
 }
 
 #[tracing::instrument(name = "Creating the auth cookie", skip_all)]
 // Create cookie and set the value to the passed-in token string
 fn create_auth_cookie(token: String) -> Cookie<'static> {
-    let cookie = Cookie::build((JWT_COOKIE_NAME, token))
+    Cookie::build((JWT_COOKIE_NAME, token))
         .path("/") // apple cookie to all URLs on the server
         .http_only(true) // prevent JavaScript from accessing the cookie
         .same_site(SameSite::Lax) // send cookie with "same-site" requests, and with "cross-site" top-level navigations.
-        .build();
+        .build()
+}
+
+// Start a new session for the user after a successful login. Returns the auth cookie and
+// the refresh cookie holding the first token of the session's refresh token family.
+#[tracing::instrument(name = "Starting a session", skip_all)]
+pub async fn start_session(
+    user: &User,
+    client_info: &ClientInfo,
+    two_factor: bool,
+    state: &AppState,
+) -> Result<(Cookie<'static>, Cookie<'static>)> {
+    let email = &user.email;
+    // Logging in during the grace period of a deleted account takes the deletion back. That's
+    // only once the login is complete, so a password without the 2FA code can't do it.
+    if user.deleted_at.is_some() {
+        state.user_store.write().await.restore_user(email).await?;
+    }
+
+    let roles = state.user_store.read().await.get_roles(&user.id).await?;
+    let permissions = state
+        .user_store
+        .read()
+        .await
+        .get_permissions(&user.id)
+        .await?;
+    let session_id = SessionId::default();
+    let (auth_cookie, jti) = generate_auth_cookie(&user.id, &roles, &permissions, &session_id)?;
+
+    let refresh_token = RefreshToken::default();
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .add_token(email.clone(), session_id.clone(), refresh_token.clone())
+        .await?;
+
+    let now = Utc::now().timestamp();
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(Session {
+            id: session_id,
+            email: email.clone(),
+            jti,
+            device: client_info.device(),
+            ip: client_info.ip.clone(),
+            user_agent: client_info.user_agent.clone(),
+            created_at: now,
+            last_seen_at: now,
+            two_factor,
+            authenticated_at: Some(now),
+        })
+        .await?;
+
+    Ok((auth_cookie, create_refresh_cookie(&refresh_token)))
+}
+
+// End a session: the given auth token of it is banned and its refresh token family revoked
+#[tracing::instrument(name = "Revoking a session", skip_all)]
+pub async fn revoke_session(session_id: &SessionId, jti: String, state: &AppState) -> Result<()> {
+    state
+        .banned_token_store
+        .write()
+        .await
+        .add_token(jti)
+        .await?;
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_session(session_id)
+        .await?;
+    match state
+        .session_store
+        .write()
+        .await
+        .remove_session(session_id)
+        .await
+    {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// End all of the user's sessions but the given one
+#[tracing::instrument(name = "Revoking other sessions", skip_all)]
+pub async fn revoke_other_sessions(
+    email: &Email,
+    current_session_id: &SessionId,
+    state: &AppState,
+) -> Result<()> {
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_user_sessions(email)
+        .await?;
+    for session in sessions {
+        if &session.id != current_session_id {
+            revoke_session(&session.id, session.jti, state).await?;
+        }
+    }
+    Ok(())
+}
+
+// End all of the user's sessions
+#[tracing::instrument(name = "Revoking all sessions", skip_all)]
+pub async fn revoke_all_sessions(user: &User, state: &AppState) -> Result<()> {
+    let email = &user.email;
+    state
+        .banned_token_store
+        .write()
+        .await
+        .revoke_user_tokens(&user.id)
+        .await?;
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_user_tokens(email)
+        .await?;
+    state
+        .session_store
+        .write()
+        .await
+        .remove_user_sessions(email)
+        .await?;
+    Ok(())
+}
 
-    cookie
+#[tracing::instrument(name = "Creating the refresh cookie", skip_all)]
+pub fn create_refresh_cookie(refresh_token: &RefreshToken) -> Cookie<'static> {
+    let max_age = time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS);
+    Cookie::build((
+        REFRESH_TOKEN_COOKIE_NAME,
+        refresh_token.as_ref().expose_secret().to_owned(),
+    ))
+    .path("/")
+    .http_only(true)
+    .same_site(SameSite::Lax)
+    .max_age(max_age)
+    .build()
+}
+
+// Remove the auth and refresh cookies from the client once its session has ended. The path
+// must match the cookies', or clients only drop them for the path of the request.
+pub fn clear_auth_cookies(jar: CookieJar) -> CookieJar {
+    jar.remove(Cookie::build(JWT_COOKIE_NAME).path("/"))
+        .remove(Cookie::build(REFRESH_TOKEN_COOKIE_NAME).path("/"))
+}
+
+// Replace the user's recovery codes with a fresh set. The plain codes are returned
+// so they can be shown to the user this one time.
+#[tracing::instrument(name = "Generating recovery codes", skip_all)]
+pub async fn generate_recovery_codes(
+    email: &Email,
+    recovery_code_store: RecoveryCodeStoreType,
+) -> Result<Vec<String>> {
+    let codes = RecoveryCode::generate_set();
+    let plain_codes = codes
+        .iter()
+        .map(|code| code.as_ref().expose_secret().to_owned())
+        .collect();
+    recovery_code_store
+        .write()
+        .await
+        .set_codes(email, codes)
+        .await?;
+    Ok(plain_codes)
 }
 
 #[derive(Debug)]
@@ -38,14 +234,152 @@ pub enum GenerateTokenError {
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
+// This value determines how long an unused refresh token stays valid for
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 60 * 60 * 24 * 14; // 14 days
+
+// This value determines how long an emailed password reset link is valid for
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: u64 = 900; // 15 minutes
+
+// This value determines how long an emailed email verification link is valid for
+pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: i64 = 60 * 60 * 24; // 24 hours
+
+// This value determines how long an emailed email change link is valid for
+pub const EMAIL_CHANGE_TOKEN_TTL_SECONDS: i64 = 60 * 60; // 1 hour
+
+// This value determines how long an OAuth client has to exchange an authorization code
+pub const AUTHORIZATION_CODE_TTL_SECONDS: u64 = 60; // 1 minute
+
+// OAuth clients that request this scope are OpenID Connect clients, which get an ID token
+pub const OPENID_SCOPE: &str = "openid";
+
+// Clients validate ID tokens with the published public key, so they can only be issued when
+// tokens are signed with an asymmetric key. The secret of HS256 keys can't be shared.
+pub fn id_tokens_supported() -> bool {
+    read_key_ring().active_key().jwk().is_some()
+}
+
+// Email verification tokens are signed with the same key as auth tokens. The audience
+// keeps one kind of token from being accepted in place of the other.
+const EMAIL_VERIFICATION_AUDIENCE: &str = "email-verification";
+const EMAIL_CHANGE_AUDIENCE: &str = "email-change";
+
 // Create JWT auth token
 
 #[tracing::instrument(name = "Generating the auth token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<String> {
+fn generate_auth_token(
+    user_id: &UserId,
+    roles: &[Role],
+    permissions: &[Permission],
+    session_id: &SessionId,
+    jti: String,
+) -> Result<String> {
+    let claims = Claims {
+        permissions: permissions.to_vec(),
+        ..new_claims(user_id, roles, session_id, jti)?
+    };
+    create_token(&claims)
+}
+
+// Create a token an OAuth client gets for the user, limited to the scopes the user consented
+// to. It carries no roles, so clients can't act as admins on behalf of their users.
+#[tracing::instrument(name = "Generating the OAuth access token", skip_all)]
+pub fn generate_access_token(
+    user_id: &UserId,
+    session_id: &SessionId,
+    client_id: &ClientId,
+    scopes: &[String],
+) -> Result<String> {
+    let jti = uuid::Uuid::new_v4().to_string();
+    let claims = Claims {
+        client_id: Some(client_id.as_ref().to_owned()),
+        scope: Some(scopes.join(" ")),
+        ..new_claims(user_id, &[], session_id, jti)?
+    };
+    create_token(&claims)
+}
+
+// Create a token an OAuth client gets for itself through the client credentials grant. The
+// client is its subject: there is no user, and so no session either.
+#[tracing::instrument(name = "Generating the client access token", skip_all)]
+pub fn generate_client_access_token(client_id: &ClientId, scopes: &[String]) -> Result<String> {
+    let (iat, exp) = token_lifetime()?;
+    let claims = ClientClaims {
+        sub: client_id.as_ref().to_owned(),
+        exp,
+        iat,
+        nbf: iat,
+        jti: uuid::Uuid::new_v4().to_string(),
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_AUDIENCE.to_owned(),
+        client_id: client_id.as_ref().to_owned(),
+        scope: scopes.join(" "),
+    };
+    create_token(&claims)
+}
+
+// Create an OpenID Connect ID token (OpenID Connect Core section 2), telling the client who
+// the user is and how they logged in to the session the client was authorized from
+#[tracing::instrument(name = "Generating the ID token", skip_all)]
+pub fn generate_id_token(
+    user_id: &UserId,
+    client_id: &ClientId,
+    session: &Session,
+    nonce: Option<String>,
+) -> Result<String> {
+    let (iat, exp) = token_lifetime()?;
+    // RFC 8176 authentication method references. The 2FA code is a one-time password,
+    // whether it was emailed or came from an authenticator app.
+    let amr = if session.two_factor {
+        vec!["pwd", "otp", "mfa"]
+    } else {
+        vec!["pwd"]
+    };
+    let claims = IdTokenClaims {
+        iss: JWT_ISSUER.to_owned(),
+        sub: user_id.as_ref().to_owned(),
+        aud: client_id.as_ref().to_owned(),
+        exp,
+        iat,
+        auth_time: session.authenticated_at,
+        nonce,
+        amr: amr.into_iter().map(str::to_owned).collect(),
+        sid: session.id.as_ref().to_owned(),
+    };
+    create_token(&claims)
+}
+
+fn new_claims(
+    user_id: &UserId,
+    roles: &[Role],
+    session_id: &SessionId,
+    jti: String,
+) -> Result<Claims> {
+    let (iat, exp) = token_lifetime()?;
+    let sub = user_id.as_ref().to_owned();
+
+    Ok(Claims {
+        sub,
+        exp,
+        iat,
+        nbf: iat,
+        jti,
+        sid: session_id.as_ref().to_owned(),
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_AUDIENCE.to_owned(),
+        roles: roles.to_vec(),
+        permissions: Vec::new(),
+        client_id: None,
+        scope: None,
+    })
+}
+
+// Issue and expiry time of a token issued now
+fn token_lifetime() -> Result<(usize, usize)> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
         .wrap_err("failed to create 10 minute time delta")?;
 
-    let exp = Utc::now()
+    let now = Utc::now();
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(eyre!("failed to add 10 minutes to current time"))?
         .timestamp();
@@ -54,56 +388,447 @@ fn generate_auth_token(email: &Email) -> Result<String> {
         "failed to cast exp time to usize. exp time: {}",
         exp
     ))?;
-
-    let sub = email.as_ref().expose_secret().to_owned();
-
-    let claims = Claims { sub, exp };
-
-    create_token(&claims)
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .wrap_err("failed to cast iat time to usize")?;
+    Ok((iat, exp))
 }
 
 #[tracing::instrument(name = "Validating the token", skip_all)]
 pub async fn validate_token(
     token: &str,
     banned_token_store: BannedTokenStoreType,
-) -> Result<Claims> {
-    match banned_token_store
+    user_store: UserStoreType,
+    session_store: SessionStoreType,
+    oauth_client_store: OAuthClientStoreType,
+) -> Result<TokenClaims, AuthAPIError> {
+    let claims = decode_token::<TokenClaims>(token, &JWT_AUDIENCE)
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if banned_token_store
         .read()
         .await
-        .contains_token(&Secret::new(token.to_owned()))
+        .contains_token(claims.jti())
         .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
     {
-        Ok(value) => {
-            if value {
-                return Err(eyre!("token is banned"));
-            }
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    match claims {
+        TokenClaims::User(claims) => {
+            validate_user_claims(claims, banned_token_store, user_store, session_store)
+                .await
+                .map(TokenClaims::User)
         }
-        Err(e) => return Err(e.into()),
+        TokenClaims::Client(claims) => validate_client_claims(claims, oauth_client_store)
+            .await
+            .map(TokenClaims::Client),
     }
+}
+
+async fn validate_user_claims(
+    claims: Claims,
+    banned_token_store: BannedTokenStoreType,
+    user_store: UserStoreType,
+    session_store: SessionStoreType,
+) -> Result<Claims, AuthAPIError> {
+    let user_id = UserId::parse(claims.sub.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    // Suspending a user revokes their tokens too. The suspension is checked first, so it's
+    // what these tokens are rejected for.
+    let user = match user_store.read().await.get_user_by_id(&user_id).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+    if user.status == UserStatus::Suspended {
+        return Err(AuthAPIError::AccountSuspended);
+    }
+
+    // Tokens issued before all of the user's tokens were revoked (e.g. by a password reset)
+    // are no longer valid, even though they were never banned individually. Timestamps have
+    // a one second resolution, so tokens issued in the same second are rejected as well.
+    let revoked_at = banned_token_store
+        .read()
+        .await
+        .get_user_tokens_revoked_at(&user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    if let Some(revoked_at) = revoked_at
+        && claims.iat as i64 <= revoked_at
+    {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    // Tokens of sessions that have ended are no longer valid, including the ones issued before
+    // the session's latest token, which was the only one banned when it ended
+    let session_id =
+        SessionId::parse(claims.sid.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    match session_store.read().await.get_session(&session_id).await {
+        Ok(_) => Ok(claims),
+        Err(SessionStoreError::SessionNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Tokens of clients that were deleted, or are no longer confidential, are rejected right away
+async fn validate_client_claims(
+    claims: ClientClaims,
+    oauth_client_store: OAuthClientStoreType,
+) -> Result<ClientClaims, AuthAPIError> {
+    let client_id =
+        ClientId::parse(claims.client_id.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    match oauth_client_store.read().await.get_client(&client_id).await {
+        Ok(client) if client.is_confidential() => Ok(claims),
+        Ok(_) | Err(OAuthClientStoreError::ClientNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Claims of the JWT cookie in the jar, for routes that require a login
+#[tracing::instrument(name = "Authenticating the token", skip_all)]
+pub async fn get_authenticated_claims(
+    jar: &CookieJar,
+    state: &AppState,
+) -> Result<Claims, AuthAPIError> {
+    let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
+    validate_login_token(cookie.value(), state).await
+}
 
-    decode::<Claims>(
+// Validates a token that has to be from a user's own login
+pub async fn validate_login_token(token: &str, state: &AppState) -> Result<Claims, AuthAPIError> {
+    let claims = validate_token(
         token,
-        &DecodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-        &Validation::default(),
+        state.banned_token_store.clone(),
+        state.user_store.clone(),
+        state.session_store.clone(),
+        state.oauth_client_store.clone(),
     )
-    .map(|data| data.claims)
-    .wrap_err("failed to decode token")
+    .await?;
+    // Tokens issued to OAuth clients are limited to their scopes, so they can't stand in
+    // for the user's own login
+    match claims {
+        TokenClaims::User(claims) if claims.client_id.is_none() => Ok(claims),
+        _ => Err(AuthAPIError::InvalidToken),
+    }
+}
+
+// Access token an OAuth client sent in the Authorization header (RFC 6750 section 2.1)
+pub fn get_bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("Bearer") && !token.is_empty()).then_some(token)
+}
+
+// Client id and secret a confidential client sent in the Authorization header with HTTP Basic
+// authentication (RFC 6749 section 2.3.1), each form-urlencoded before they were joined
+pub fn get_basic_credentials(headers: &HeaderMap) -> Option<(String, Secret<String>)> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, credentials) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("Basic") {
+        return None;
+    }
+    let credentials = STANDARD.decode(credentials.trim()).ok()?;
+    let credentials = String::from_utf8(credentials).ok()?;
+    let (client_id, client_secret) = credentials.split_once(':')?;
+    let decode = |value: &str| {
+        form_urlencoded::parse(format!("value={}", value).as_bytes())
+            .next()
+            .map(|(_, value)| value.into_owned())
+    };
+    Some((decode(client_id)?, Secret::new(decode(client_secret)?)))
+}
+
+// The OAuth client making a request to one of the OAuth endpoints, with the client id and
+// secret it gave in the request body, if any. Confidential clients have to authenticate with
+// their secret, in the Authorization header or the body, while public ones only name
+// themselves.
+#[tracing::instrument(name = "Authenticating the OAuth client", skip_all)]
+pub async fn authenticate_client(
+    headers: &HeaderMap,
+    client_id: Option<String>,
+    client_secret: Option<Secret<String>>,
+    state: &AppState,
+) -> Result<OAuthClient, OAuthError> {
+    let (client_id, client_secret) = match get_basic_credentials(headers) {
+        // Clients must not use more than one way to authenticate (RFC 6749 section 2.3)
+        Some(_) if client_secret.is_some() => {
+            return Err(OAuthError::InvalidRequest(
+                "Client secret was given more than once",
+            ));
+        }
+        Some((basic_client_id, basic_client_secret))
+            if client_id.as_ref().is_none_or(|id| *id == basic_client_id) =>
+        {
+            (basic_client_id, Some(basic_client_secret))
+        }
+        Some(_) => {
+            return Err(OAuthError::InvalidRequest(
+                "client_id does not match the Authorization header",
+            ));
+        }
+        None => (
+            client_id.ok_or(OAuthError::InvalidRequest("client_id is required"))?,
+            client_secret,
+        ),
+    };
+
+    let client_id = ClientId::parse(client_id).map_err(|_| OAuthError::InvalidClient)?;
+    let client = match state
+        .oauth_client_store
+        .read()
+        .await
+        .get_client(&client_id)
+        .await
+    {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => return Err(OAuthError::InvalidClient),
+        Err(e) => return Err(OAuthError::ServerError(e.into())),
+    };
+    let authenticated = match &client_secret {
+        Some(client_secret) => client.verify_secret(client_secret),
+        None => !client.is_confidential(),
+    };
+    if !authenticated {
+        return Err(OAuthError::InvalidClient);
+    }
+    Ok(client)
+}
+
+// The user whose JWT cookie is in the jar, for routes that require a login
+#[tracing::instrument(name = "Authenticating the user", skip_all)]
+pub async fn get_authenticated_user(
+    jar: &CookieJar,
+    state: &AppState,
+) -> Result<User, AuthAPIError> {
+    let claims = get_authenticated_claims(jar, state).await?;
+    get_claims_user(&claims, state).await
+}
+
+// The user the validated claims were issued to. Tokens of users that no longer exist are invalid.
+#[tracing::instrument(name = "Retrieving the user of the token", skip_all)]
+pub async fn get_claims_user(claims: &Claims, state: &AppState) -> Result<User, AuthAPIError> {
+    let user_id = UserId::parse(claims.sub.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    match state.user_store.read().await.get_user_by_id(&user_id).await {
+        Ok(user) => Ok(user),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Create a signed token that proves ownership of the email address it was sent to
+#[tracing::instrument(name = "Generating the email verification token", skip_all)]
+pub fn generate_email_verification_token(email: &Email) -> Result<String> {
+    let delta = chrono::Duration::try_seconds(EMAIL_VERIFICATION_TOKEN_TTL_SECONDS)
+        .wrap_err("failed to create 24 hour time delta")?;
+
+    let exp = Utc::now()
+        .checked_add_signed(delta)
+        .ok_or(eyre!("failed to add 24 hours to current time"))?
+        .timestamp();
+
+    let exp: usize = exp.try_into().wrap_err(format!(
+        "failed to cast exp time to usize. exp time: {}",
+        exp
+    ))?;
+
+    let claims = EmailVerificationClaims {
+        sub: email.as_ref().expose_secret().to_owned(),
+        exp,
+        iss: JWT_ISSUER.to_owned(),
+        aud: EMAIL_VERIFICATION_AUDIENCE.to_owned(),
+    };
+
+    create_token(&claims)
+}
+
+#[tracing::instrument(name = "Validating the email verification token", skip_all)]
+pub fn validate_email_verification_token(token: &str) -> Result<Email> {
+    let claims = decode_token::<EmailVerificationClaims>(token, EMAIL_VERIFICATION_AUDIENCE)
+        .wrap_err("failed to decode email verification token")?;
+
+    Email::parse(Secret::new(claims.sub))
+}
+
+// Create a signed token that moves the account of the user to `new_email`, sent to the
+// new address to prove the user owns it
+#[tracing::instrument(name = "Generating the email change token", skip_all)]
+pub fn generate_email_change_token(user_id: &UserId, new_email: &Email) -> Result<String> {
+    let delta = chrono::Duration::try_seconds(EMAIL_CHANGE_TOKEN_TTL_SECONDS)
+        .wrap_err("failed to create 1 hour time delta")?;
+
+    let now = Utc::now();
+    let exp = now
+        .checked_add_signed(delta)
+        .ok_or(eyre!("failed to add 1 hour to current time"))?
+        .timestamp();
+
+    let exp: usize = exp.try_into().wrap_err(format!(
+        "failed to cast exp time to usize. exp time: {}",
+        exp
+    ))?;
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .wrap_err("failed to cast iat time to usize")?;
+
+    let claims = EmailChangeClaims {
+        sub: user_id.as_ref().to_owned(),
+        new_email: new_email.as_ref().expose_secret().to_owned(),
+        exp,
+        iat,
+        iss: JWT_ISSUER.to_owned(),
+        aud: EMAIL_CHANGE_AUDIENCE.to_owned(),
+    };
+
+    create_token(&claims)
+}
+
+// Id of the user whose account the token moves, and the new email address. Like auth tokens,
+// email change tokens issued before all of the user's tokens were revoked are no longer valid.
+#[tracing::instrument(name = "Validating the email change token", skip_all)]
+pub async fn validate_email_change_token(
+    token: &str,
+    banned_token_store: BannedTokenStoreType,
+) -> Result<(UserId, Email)> {
+    let claims = decode_token::<EmailChangeClaims>(token, EMAIL_CHANGE_AUDIENCE)
+        .wrap_err("failed to decode email change token")?;
+
+    let user_id = UserId::parse(claims.sub)?;
+    let revoked_at = banned_token_store
+        .read()
+        .await
+        .get_user_tokens_revoked_at(&user_id)
+        .await?;
+    if let Some(revoked_at) = revoked_at
+        && claims.iat as i64 <= revoked_at
+    {
+        return Err(eyre!("token has been revoked"));
+    }
+
+    let new_email = Email::parse(Secret::new(claims.new_email))?;
+    Ok((user_id, new_email))
 }
 
 #[tracing::instrument(name = "Creating the token", skip_all)]
-fn create_token(claims: &Claims) -> Result<String> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-    )
-    .wrap_err("failed to create token")
+fn create_token<T: Serialize>(claims: &T) -> Result<String> {
+    let signing_key = read_key_ring().active_key();
+    encode(&signing_key.header(), &claims, signing_key.encoding_key())
+        .wrap_err("failed to create token")
+}
+
+// Decode a token signed by one of the keys in the key ring, picked by the key id in its
+// header. Tokens signed by unknown or expired keys are rejected, as are tokens issued by
+// another deployment or meant for another audience.
+fn decode_token<T: DeserializeOwned>(token: &str, audience: &str) -> Result<T> {
+    let header = decode_header(token).wrap_err("failed to decode token header")?;
+    let key = find_verification_key(header.kid.as_deref())
+        .ok_or(eyre!("token was signed by an unknown key"))?;
+
+    let mut validation = key.validation();
+    validation.set_issuer(&[JWT_ISSUER.as_str()]);
+    validation.set_audience(&[audience]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+    let data = decode::<T>(token, key.decoding_key(), &validation)?;
+    Ok(data.claims)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    pub iat: usize,
+    pub nbf: usize,
+    // Unique id of the token, which banned tokens are stored by
+    pub jti: String,
+    // Id of the session the token was issued for
+    pub sid: String,
+    pub iss: String,
+    pub aud: String,
+    // Roles the user had when the token was issued. Tokens from before roles existed have none.
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    // Permissions those roles granted, which is what routes check
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+    // OAuth client the token was issued to, and the scopes it was granted. Only set on
+    // tokens from `/oauth/token`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+// Claims of a token an OAuth client got for itself through the client credentials grant
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientClaims {
+    // Id of the client, which the token was issued to on its own behalf
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub nbf: usize,
+    pub jti: String,
+    pub iss: String,
+    pub aud: String,
+    pub client_id: String,
+    pub scope: String,
+}
+
+// Claims of a valid token, which was issued either to a user, possibly through an OAuth client,
+// or to an OAuth client for itself. Client tokens have no session, which tells the two apart.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TokenClaims {
+    User(Claims),
+    Client(ClientClaims),
+}
+
+impl TokenClaims {
+    pub fn jti(&self) -> &str {
+        match self {
+            TokenClaims::User(claims) => &claims.jti,
+            TokenClaims::Client(claims) => &claims.jti,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    // The client the token was issued to
+    pub aud: String,
+    pub exp: usize,
+    pub iat: usize,
+    // Unix timestamp of when the user logged in, left out if that is unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    pub amr: Vec<String>,
+    pub sid: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EmailVerificationClaims {
+    sub: String,
+    exp: usize,
+    iss: String,
+    aud: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EmailChangeClaims {
+    sub: String,
+    #[serde(rename = "newEmail")]
+    new_email: String,
+    exp: usize,
+    iat: usize,
+    iss: String,
+    aud: String,
 }
 
 #[cfg(test)]
@@ -111,15 +836,95 @@ mod tests {
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
-    use crate::services::HashSetBannedTokenStore;
+    use crate::{
+        domain::{
+            BannedTokenStore, ClientSecret, OAuthClient, OAuthClientStore, Password, SessionStore,
+            UserStore,
+        },
+        services::{
+            HashSetBannedTokenStore, HashmapOAuthClientStore, HashmapSessionStore, HashmapUserStore,
+        },
+    };
     use secrecy::Secret;
 
     use super::*;
 
+    const TEST_USER_ID: &str = "4f1d2c3b-9a8e-4d7c-b6a5-0e1f2a3b4c5d";
+    const TEST_SESSION_ID: &str = "0b6c5d4e-3f2a-4b1c-9d8e-7f6a5b4c3d2e";
+
+    fn test_user_id() -> UserId {
+        UserId::parse(TEST_USER_ID.to_owned()).unwrap()
+    }
+
+    fn test_session_id() -> SessionId {
+        SessionId::parse(TEST_SESSION_ID.to_owned()).unwrap()
+    }
+
+    // A session store holding the session the test tokens are issued for
+    async fn test_session_store() -> SessionStoreType {
+        let mut session_store = HashmapSessionStore::new();
+        let now = Utc::now().timestamp();
+        session_store
+            .add_session(Session {
+                id: test_session_id(),
+                email: Email::parse(Secret::new("test@example.com".to_owned())).unwrap(),
+                jti: uuid::Uuid::new_v4().to_string(),
+                device: None,
+                ip: None,
+                user_agent: None,
+                created_at: now,
+                last_seen_at: now,
+                two_factor: false,
+                authenticated_at: Some(now),
+            })
+            .await
+            .unwrap();
+        Arc::new(RwLock::new(session_store))
+    }
+
+    // A user store holding the user the test tokens are issued to
+    async fn test_user_store(status: UserStatus) -> UserStoreType {
+        let mut user_store = HashmapUserStore::new();
+        let user = User {
+            id: test_user_id(),
+            status,
+            ..User::new(
+                Email::parse(Secret::new("test@example.com".to_owned())).unwrap(),
+                Password::parse("some-password-1".to_owned().into()).unwrap(),
+                false,
+            )
+        };
+        user_store.add_user(user).await.unwrap();
+        Arc::new(RwLock::new(user_store))
+    }
+
+    // Validates a token that has to be a user's
+    async fn validate_user_token(
+        token: &str,
+        banned_token_store: BannedTokenStoreType,
+        user_store: UserStoreType,
+    ) -> Result<Claims, AuthAPIError> {
+        let session_store = test_session_store().await;
+        let oauth_client_store = Arc::new(RwLock::new(HashmapOAuthClientStore::new()));
+        match validate_token(
+            token,
+            banned_token_store,
+            user_store,
+            session_store,
+            oauth_client_store,
+        )
+        .await?
+        {
+            TokenClaims::User(claims) => Ok(claims),
+            TokenClaims::Client(claims) => panic!("token was issued to client {}", claims.sub),
+        }
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let (cookie, jti) =
+            generate_auth_cookie(&test_user_id(), &[], &[], &SessionId::default()).unwrap();
+        assert!(!jti.is_empty());
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -140,20 +945,20 @@ mod tests {
 
     #[tokio::test]
     async fn test_generate_auth_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = test_auth_token(&test_user_id());
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let token = test_auth_token(&test_user_id());
         let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store.clone())
+        let user_store = test_user_store(UserStatus::Active).await;
+        let result = validate_user_token(&token, banned_token_store.clone(), user_store.clone())
             .await
             .unwrap();
-        assert_eq!(result.sub, "test@example.com");
+        assert_eq!(result.sub, TEST_USER_ID);
+        assert_eq!(result.roles, vec![Role::Admin]);
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -163,11 +968,304 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_validate_token_of_ended_session() {
+        let token = test_auth_token(&test_user_id());
+        let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
+        let user_store = test_user_store(UserStatus::Active).await;
+        let session_store = test_session_store().await;
+        session_store
+            .write()
+            .await
+            .remove_session(&test_session_id())
+            .await
+            .unwrap();
+        let oauth_client_store = Arc::new(RwLock::new(HashmapOAuthClientStore::new()));
+
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            user_store,
+            session_store,
+            oauth_client_store,
+        )
+        .await;
+        assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_revoked_user_tokens() {
+        let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
+        let user_store = test_user_store(UserStatus::Active).await;
+        let now = Utc::now().timestamp();
+        let token = create_token(&test_claims(now - 1)).unwrap();
+        banned_token_store
+            .write()
+            .await
+            .revoke_user_tokens(&test_user_id())
+            .await
+            .unwrap();
+
+        let result =
+            validate_user_token(&token, banned_token_store.clone(), user_store.clone()).await;
+        assert!(result.is_err());
+
+        // Issued after the revocation, but already valid
+        let token = create_token(&Claims {
+            nbf: now as usize,
+            ..test_claims(now + 1)
+        })
+        .unwrap();
+        let result =
+            validate_user_token(&token, banned_token_store.clone(), user_store.clone()).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_generate_access_token() {
+        let client_id = ClientId::default();
+        let token = generate_access_token(
+            &test_user_id(),
+            &test_session_id(),
+            &client_id,
+            &["read".to_owned(), "write".to_owned()],
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
+        let user_store = test_user_store(UserStatus::Active).await;
+        let claims = validate_user_token(&token, banned_token_store, user_store)
+            .await
+            .unwrap();
+        assert_eq!(claims.sub, TEST_USER_ID);
+        assert_eq!(claims.client_id.as_deref(), Some(client_id.as_ref()));
+        assert_eq!(claims.scope.as_deref(), Some("read write"));
+        assert!(claims.roles.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_generate_client_access_token() {
+        let mut client = OAuthClient {
+            id: ClientId::default(),
+            name: "Backend job".to_owned(),
+            redirect_uris: vec![],
+            scopes: vec!["read".to_owned()],
+            secret_hash: Some(ClientSecret::default().hash()),
+        };
+        let token = generate_client_access_token(&client.id, &client.scopes).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
+        let user_store = test_user_store(UserStatus::Active).await;
+        let session_store = test_session_store().await;
+        let mut oauth_client_store = HashmapOAuthClientStore::new();
+        oauth_client_store.add_client(client.clone()).await.unwrap();
+        let oauth_client_store: OAuthClientStoreType = Arc::new(RwLock::new(oauth_client_store));
+
+        let claims = validate_token(
+            &token,
+            banned_token_store.clone(),
+            user_store.clone(),
+            session_store.clone(),
+            oauth_client_store.clone(),
+        )
+        .await
+        .unwrap();
+        let TokenClaims::Client(claims) = claims else {
+            panic!("client token was taken for a user's");
+        };
+        assert_eq!(claims.sub, client.id.as_ref());
+        assert_eq!(claims.client_id, client.id.as_ref());
+        assert_eq!(claims.scope, "read");
+
+        // Tokens of clients that are gone, or no longer confidential, are rejected
+        client.secret_hash = None;
+        let mut oauth_client_store = HashmapOAuthClientStore::new();
+        oauth_client_store.add_client(client).await.unwrap();
+        let public_client_store: OAuthClientStoreType = Arc::new(RwLock::new(oauth_client_store));
+        let empty_client_store: OAuthClientStoreType =
+            Arc::new(RwLock::new(HashmapOAuthClientStore::new()));
+        for oauth_client_store in [public_client_store, empty_client_store] {
+            let result = validate_token(
+                &token,
+                banned_token_store.clone(),
+                user_store.clone(),
+                session_store.clone(),
+                oauth_client_store,
+            )
+            .await;
+            assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+        }
+    }
+
+    fn test_auth_token(user_id: &UserId) -> String {
+        let jti = uuid::Uuid::new_v4().to_string();
+        generate_auth_token(
+            user_id,
+            &[Role::Admin],
+            Role::Admin.permissions(),
+            &test_session_id(),
+            jti,
+        )
+        .unwrap()
+    }
+
+    fn test_claims(iat: i64) -> Claims {
+        Claims {
+            sub: TEST_USER_ID.to_owned(),
+            exp: (iat + TOKEN_TTL_SECONDS) as usize,
+            iat: iat as usize,
+            nbf: iat as usize,
+            jti: uuid::Uuid::new_v4().to_string(),
+            sid: TEST_SESSION_ID.to_owned(),
+            iss: JWT_ISSUER.to_owned(),
+            aud: JWT_AUDIENCE.to_owned(),
+            roles: Vec::new(),
+            permissions: Vec::new(),
+            client_id: None,
+            scope: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_checks_standard_claims() {
+        let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
+        let user_store = test_user_store(UserStatus::Active).await;
+        let now = Utc::now().timestamp();
+
+        let token = create_token(&test_claims(now)).unwrap();
+        let claims = validate_user_token(&token, banned_token_store.clone(), user_store.clone())
+            .await
+            .unwrap();
+        assert_eq!(claims.iss, *JWT_ISSUER);
+        assert_eq!(claims.aud, *JWT_AUDIENCE);
+
+        let invalid_claims = [
+            Claims {
+                iss: "https://other.example.com".to_owned(),
+                ..test_claims(now)
+            },
+            Claims {
+                aud: "other-audience".to_owned(),
+                ..test_claims(now)
+            },
+            Claims {
+                nbf: (now + 600) as usize,
+                ..test_claims(now)
+            },
+        ];
+        for claims in invalid_claims {
+            let token = create_token(&claims).unwrap();
+            let result =
+                validate_user_token(&token, banned_token_store.clone(), user_store.clone()).await;
+            assert!(result.is_err(), "accepted token with claims {:?}", claims);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_banned_token() {
+        let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
+        let user_store = test_user_store(UserStatus::Active).await;
+        let token = test_auth_token(&test_user_id());
+        let claims = validate_user_token(&token, banned_token_store.clone(), user_store.clone())
+            .await
+            .unwrap();
+
+        banned_token_store
+            .write()
+            .await
+            .add_token(claims.jti)
+            .await
+            .unwrap();
+        let result =
+            validate_user_token(&token, banned_token_store.clone(), user_store.clone()).await;
+        assert!(result.is_err());
+
+        // Other tokens of the same user are not affected
+        let other_token = test_auth_token(&test_user_id());
+        let result =
+            validate_user_token(&other_token, banned_token_store.clone(), user_store.clone()).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_of_suspended_or_unknown_user() {
+        let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
+        let token = test_auth_token(&test_user_id());
+
+        let user_store = test_user_store(UserStatus::Suspended).await;
+        let result = validate_user_token(&token, banned_token_store.clone(), user_store).await;
+        assert!(matches!(result, Err(AuthAPIError::AccountSuspended)));
+
+        let user_store: UserStoreType = Arc::new(RwLock::new(HashmapUserStore::new()));
+        let result = validate_user_token(&token, banned_token_store, user_store).await;
+        assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_email_verification_token() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let token = generate_email_verification_token(&email).unwrap();
+        let result = validate_email_verification_token(&token).unwrap();
+        assert_eq!(result, email);
+
+        assert!(validate_email_verification_token("invalid_token").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_email_change_token() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let new_email = Email::parse(Secret::new("new@example.com".to_owned())).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
+        let token = generate_email_change_token(&test_user_id(), &new_email).unwrap();
+        let result = validate_email_change_token(&token, banned_token_store.clone())
+            .await
+            .unwrap();
+        assert_eq!(result, (test_user_id(), new_email));
+
+        let verification_token = generate_email_verification_token(&email).unwrap();
+        assert!(
+            validate_email_change_token(&verification_token, banned_token_store.clone())
+                .await
+                .is_err()
+        );
+
+        banned_token_store
+            .write()
+            .await
+            .revoke_user_tokens(&test_user_id())
+            .await
+            .unwrap();
+        assert!(
+            validate_email_change_token(&token, banned_token_store)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tokens_are_not_interchangeable() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
+        let user_store = test_user_store(UserStatus::Active).await;
+
+        let auth_token = test_auth_token(&test_user_id());
+        assert!(validate_email_verification_token(&auth_token).is_err());
+
+        let verification_token = generate_email_verification_token(&email).unwrap();
+        let result = validate_user_token(
+            &verification_token,
+            banned_token_store.clone(),
+            user_store.clone(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_owned();
         let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store.clone()).await;
+        let user_store = test_user_store(UserStatus::Active).await;
+        let result =
+            validate_user_token(&token, banned_token_store.clone(), user_store.clone()).await;
         assert!(result.is_err());
     }
 }
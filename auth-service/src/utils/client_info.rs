@@ -0,0 +1,192 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{Extensions, HeaderMap, header::USER_AGENT, request::Parts},
+};
+
+use super::constants::TRUSTED_PROXIES;
+
+// Where a request came from, as recorded for sessions
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClientInfo {
+    // Short description of the device like "Firefox on Linux", made from the user agent
+    pub fn device(&self) -> Option<String> {
+        let user_agent = self.user_agent.as_deref()?;
+
+        // Order matters: user agents mention the browsers and systems they are compatible with
+        let browser = [
+            ("Edg/", "Edge"),
+            ("OPR/", "Opera"),
+            ("Firefox/", "Firefox"),
+            ("Chrome/", "Chrome"),
+            ("Safari/", "Safari"),
+        ]
+        .into_iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, name)| name);
+        let os = [
+            ("Windows", "Windows"),
+            ("Android", "Android"),
+            ("iPhone", "iOS"),
+            ("iPad", "iOS"),
+            ("Mac OS X", "macOS"),
+            ("Linux", "Linux"),
+        ]
+        .into_iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, name)| name);
+
+        match (browser, os) {
+            (Some(browser), Some(os)) => Some(format!("{} on {}", browser, os)),
+            (Some(name), None) | (None, Some(name)) => Some(name.to_owned()),
+            (None, None) => None,
+        }
+    }
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip = client_ip(&parts.headers, &parts.extensions).map(|ip| ip.to_string());
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        Ok(Self { ip, user_agent })
+    }
+}
+
+// Address of the client that made the request, if the server was told the address of
+// the connection. Behind trusted proxies, that is the address they forwarded the request for.
+pub fn client_ip(headers: &HeaderMap, extensions: &Extensions) -> Option<IpAddr> {
+    let ConnectInfo(peer) = extensions.get::<ConnectInfo<SocketAddr>>()?;
+    Some(resolve_client_ip(peer.ip(), headers, &TRUSTED_PROXIES))
+}
+
+fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> IpAddr {
+    if !trusted_proxies.contains(&peer) {
+        return peer;
+    }
+
+    // Every proxy appends the address it received the request from, so the client is the
+    // last address that isn't one of the trusted proxies. Anything before it could be made up.
+    let mut client = peer;
+    let forwarded_for = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    for address in forwarded_for.into_iter().rev() {
+        match address.trim().parse::<IpAddr>() {
+            Ok(address) => {
+                client = address;
+                if !trusted_proxies.contains(&address) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_resolve_client_ip() {
+        let proxies = [ip("10.0.0.1"), ip("10.0.0.2")];
+
+        // Headers of clients that aren't trusted proxies are ignored
+        let headers = forwarded_for("1.1.1.1");
+        assert_eq!(
+            resolve_client_ip(ip("2.2.2.2"), &headers, &proxies),
+            ip("2.2.2.2")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.1"), &headers, &[]),
+            ip("10.0.0.1")
+        );
+
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.1"), &headers, &proxies),
+            ip("1.1.1.1")
+        );
+        // Addresses prepended by the client are skipped
+        let headers = forwarded_for("9.9.9.9, 1.1.1.1, 10.0.0.2");
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.1"), &headers, &proxies),
+            ip("1.1.1.1")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.1"), &HeaderMap::new(), &proxies),
+            ip("10.0.0.1")
+        );
+        // The address forwarded last is used when it can't be parsed past a proxy
+        let headers = forwarded_for("unknown, 10.0.0.2");
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.1"), &headers, &proxies),
+            ip("10.0.0.2")
+        );
+    }
+
+    fn device(user_agent: &str) -> Option<String> {
+        ClientInfo {
+            ip: None,
+            user_agent: Some(user_agent.to_owned()),
+        }
+        .device()
+    }
+
+    #[test]
+    fn test_device() {
+        assert_eq!(
+            device("Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0"),
+            Some("Firefox on Linux".to_owned())
+        );
+        assert_eq!(
+            device(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Safari/537.36 Edg/129.0.0.0"
+            ),
+            Some("Edge on Windows".to_owned())
+        );
+        assert_eq!(
+            device(
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.6 Mobile/15E148 Safari/604.1"
+            ),
+            Some("Safari on iOS".to_owned())
+        );
+        assert_eq!(
+            device(
+                "Mozilla/5.0 (Linux; Android 14) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Mobile Safari/537.36"
+            ),
+            Some("Chrome on Android".to_owned())
+        );
+        assert_eq!(device("curl/8.5.0"), None);
+        assert_eq!(ClientInfo::default().device(), None);
+    }
+}
@@ -2,13 +2,28 @@ use dotenvy::dotenv;
 use lazy_static::lazy_static;
 use secrecy::Secret;
 use std::env as std_env;
+use std::net::IpAddr;
+use std::time::Duration;
 
 // Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
 lazy_static! {
     pub static ref JWT_SECRET: Secret<String> = set_token();
+    pub static ref JWT_SIGNING_KEY_PATH: Option<String> = set_jwt_signing_key_path();
+    pub static ref JWT_KEY_ID: Option<String> = set_jwt_key_id();
+    pub static ref JWT_SIGNING_KEYS_DIR: Option<String> = set_jwt_signing_keys_dir();
+    pub static ref JWT_KEY_ROTATION_INTERVAL: Option<Duration> = set_jwt_key_rotation_interval();
     pub static ref DATABASE_URL: Secret<String> = set_database_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host();
     pub static ref POSTMARK_AUTH_TOKEN: Secret<String> = set_postmark_auth_token();
+    pub static ref AUTH_SERVICE_URL: String = set_auth_service_url();
+    pub static ref JWT_ISSUER: String = set_jwt_issuer();
+    pub static ref JWT_AUDIENCE: String = set_jwt_audience();
+    pub static ref TOTP_ENCRYPTION_KEY: Secret<String> = set_totp_encryption_key();
+    pub static ref TOTP_SKEW_STEPS: u8 = set_totp_skew_steps();
+    pub static ref LOGIN_LOCKOUT_THRESHOLD: u32 = set_login_lockout_threshold();
+    pub static ref LOGIN_LOCKOUT_DURATION: Duration = set_login_lockout_duration();
+    pub static ref TRUSTED_PROXIES: Vec<IpAddr> = set_trusted_proxies();
+    pub static ref ACCOUNT_DELETION_GRACE_PERIOD: Duration = set_account_deletion_grace_period();
 }
 fn set_postmark_auth_token() -> Secret<String> {
     dotenv().ok();
@@ -25,6 +40,43 @@ fn set_token() -> Secret<String> {
     Secret::new(secret)
 }
 
+// PEM file with the private key tokens are signed with. JWT_SECRET is only needed without it.
+fn set_jwt_signing_key_path() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::JWT_SIGNING_KEY_PATH_ENV_VAR)
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+fn set_jwt_key_id() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::JWT_KEY_ID_ENV_VAR)
+        .ok()
+        .filter(|kid| !kid.is_empty())
+}
+
+// Directory rotated signing keys are kept in. Signing keys can only be rotated when it is set.
+fn set_jwt_signing_keys_dir() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::JWT_SIGNING_KEYS_DIR_ENV_VAR)
+        .ok()
+        .filter(|dir| !dir.is_empty())
+}
+
+// Signing keys are only rotated on a schedule when an interval is set
+fn set_jwt_key_rotation_interval() -> Option<Duration> {
+    dotenv().ok();
+    std_env::var(env::JWT_KEY_ROTATION_INTERVAL_HOURS_ENV_VAR)
+        .ok()
+        .filter(|hours| !hours.is_empty())
+        .map(|hours| {
+            let hours: u64 = hours
+                .parse()
+                .expect("JWT_KEY_ROTATION_INTERVAL_HOURS must be a number of hours.");
+            Duration::from_secs(hours * 60 * 60)
+        })
+}
+
 fn set_database_url() -> Secret<String> {
     dotenv().ok(); // Load environment variables
     let database_url = std_env::var(env::DATABASE_URL_ENV_VAR).expect("DATABASE_URL must be set.");
@@ -39,15 +91,138 @@ fn set_redis_host() -> String {
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(env::DEFAULT_REDIS_HOSTNAME.to_owned())
 }
 
+fn set_auth_service_url() -> String {
+    dotenv().ok();
+    std_env::var(env::AUTH_SERVICE_URL_ENV_VAR).unwrap_or(env::DEFAULT_AUTH_SERVICE_URL.to_owned())
+}
+
+// Identifies this deployment in the `iss` claim of the tokens it issues
+fn set_jwt_issuer() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_ISSUER_ENV_VAR)
+        .ok()
+        .filter(|issuer| !issuer.is_empty())
+        .unwrap_or_else(|| AUTH_SERVICE_URL.to_owned())
+}
+
+// The services auth tokens are meant for, put in their `aud` claim
+fn set_jwt_audience() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_AUDIENCE_ENV_VAR)
+        .ok()
+        .filter(|audience| !audience.is_empty())
+        .unwrap_or(env::DEFAULT_JWT_AUDIENCE.to_owned())
+}
+
+fn set_totp_encryption_key() -> Secret<String> {
+    dotenv().ok();
+    let key =
+        std_env::var(env::TOTP_ENCRYPTION_KEY_ENV_VAR).expect("TOTP_ENCRYPTION_KEY must be set.");
+    if key.is_empty() {
+        panic!("TOTP_ENCRYPTION_KEY must not be empty.");
+    }
+    Secret::new(key)
+}
+
+// Number of 30 second steps a TOTP code may be ahead or behind the server clock
+fn set_totp_skew_steps() -> u8 {
+    dotenv().ok();
+    match std_env::var(env::TOTP_SKEW_STEPS_ENV_VAR) {
+        Ok(steps) => steps
+            .parse()
+            .expect("TOTP_SKEW_STEPS must be a number between 0 and 255."),
+        Err(_) => env::DEFAULT_TOTP_SKEW_STEPS,
+    }
+}
+
+// Number of failed logins in a row after which an account is locked
+fn set_login_lockout_threshold() -> u32 {
+    dotenv().ok();
+    std_env::var(env::LOGIN_LOCKOUT_THRESHOLD_ENV_VAR)
+        .ok()
+        .filter(|threshold| !threshold.is_empty())
+        .map(|threshold| {
+            threshold
+                .parse()
+                .expect("LOGIN_LOCKOUT_THRESHOLD must be a number of failed logins.")
+        })
+        .unwrap_or(env::DEFAULT_LOGIN_LOCKOUT_THRESHOLD)
+}
+
+fn set_login_lockout_duration() -> Duration {
+    dotenv().ok();
+    let minutes = std_env::var(env::LOGIN_LOCKOUT_MINUTES_ENV_VAR)
+        .ok()
+        .filter(|minutes| !minutes.is_empty())
+        .map(|minutes| {
+            minutes
+                .parse()
+                .expect("LOGIN_LOCKOUT_MINUTES must be a number of minutes.")
+        })
+        .unwrap_or(env::DEFAULT_LOGIN_LOCKOUT_MINUTES);
+    Duration::from_secs(minutes * 60)
+}
+
+// Addresses of the reverse proxies in front of the service, whose X-Forwarded-For headers
+// can be trusted to tell the client's address. Separated by commas.
+fn set_trusted_proxies() -> Vec<IpAddr> {
+    dotenv().ok();
+    std_env::var(env::TRUSTED_PROXIES_ENV_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| {
+            proxy
+                .parse()
+                .expect("TRUSTED_PROXIES must be a list of IP addresses separated by commas.")
+        })
+        .collect()
+}
+
+// How long deleted accounts can still be restored by logging in, before they are purged
+fn set_account_deletion_grace_period() -> Duration {
+    dotenv().ok();
+    let days = std_env::var(env::ACCOUNT_DELETION_GRACE_DAYS_ENV_VAR)
+        .ok()
+        .filter(|days| !days.is_empty())
+        .map(|days| {
+            days.parse()
+                .expect("ACCOUNT_DELETION_GRACE_DAYS must be a number of days.")
+        })
+        .unwrap_or(env::DEFAULT_ACCOUNT_DELETION_GRACE_DAYS);
+    Duration::from_secs(days * 60 * 60 * 24)
+}
+
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    pub const JWT_SIGNING_KEY_PATH_ENV_VAR: &str = "JWT_SIGNING_KEY_PATH";
+    pub const JWT_KEY_ID_ENV_VAR: &str = "JWT_KEY_ID";
+    pub const JWT_SIGNING_KEYS_DIR_ENV_VAR: &str = "JWT_SIGNING_KEYS_DIR";
+    pub const JWT_KEY_ROTATION_INTERVAL_HOURS_ENV_VAR: &str = "JWT_KEY_ROTATION_INTERVAL_HOURS";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
     pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
     pub const POSTMARK_AUTH_TOKEN_ENV_VAR: &str = "POSTMARK_AUTH_TOKEN";
+    pub const AUTH_SERVICE_URL_ENV_VAR: &str = "AUTH_SERVICE_URL";
+    pub const DEFAULT_AUTH_SERVICE_URL: &str = "http://localhost:3000";
+    pub const JWT_ISSUER_ENV_VAR: &str = "JWT_ISSUER";
+    pub const JWT_AUDIENCE_ENV_VAR: &str = "JWT_AUDIENCE";
+    pub const DEFAULT_JWT_AUDIENCE: &str = "live-bootcamp";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const TOTP_SKEW_STEPS_ENV_VAR: &str = "TOTP_SKEW_STEPS";
+    pub const DEFAULT_TOTP_SKEW_STEPS: u8 = 1;
+    pub const LOGIN_LOCKOUT_THRESHOLD_ENV_VAR: &str = "LOGIN_LOCKOUT_THRESHOLD";
+    pub const DEFAULT_LOGIN_LOCKOUT_THRESHOLD: u32 = 5;
+    pub const LOGIN_LOCKOUT_MINUTES_ENV_VAR: &str = "LOGIN_LOCKOUT_MINUTES";
+    pub const DEFAULT_LOGIN_LOCKOUT_MINUTES: u64 = 15;
+    pub const TRUSTED_PROXIES_ENV_VAR: &str = "TRUSTED_PROXIES";
+    pub const ACCOUNT_DELETION_GRACE_DAYS_ENV_VAR: &str = "ACCOUNT_DELETION_GRACE_DAYS";
+    pub const DEFAULT_ACCOUNT_DELETION_GRACE_DAYS: u64 = 30;
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_TOKEN_COOKIE_NAME: &str = "refresh_token";
 
 pub mod prod {
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
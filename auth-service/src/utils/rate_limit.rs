@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::{MatchedPath, Request, State},
+    http::{StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{ErrorResponse, app_state::AppState, domain::RateLimit};
+
+use super::client_info::client_ip;
+
+// Budgets of requests per client IP address. Routes that check credentials or send emails
+// get small budgets of their own, all other routes share a larger one.
+fn rate_limit_for(route: &str) -> (&'static str, RateLimit) {
+    match route {
+        "/login" => ("login", per_minute(10)),
+        "/verify-2fa" => ("verify-2fa", per_minute(10)),
+        "/2fa/enable" => ("2fa-enable", per_minute(10)),
+        "/2fa/disable" => ("2fa-disable", per_minute(10)),
+        "/change-password" => ("change-password", per_minute(10)),
+        "/account" => ("account", per_minute(10)),
+        "/change-email" => ("change-email", per_hour(10)),
+        "/change-email/confirm" => ("change-email-confirm", per_minute(10)),
+        "/signup" => ("signup", per_hour(10)),
+        "/password-reset/request" => ("password-reset", per_hour(10)),
+        "/password-reset/confirm" => ("password-reset-confirm", per_minute(10)),
+        "/verify-email" => ("verify-email", per_minute(10)),
+        "/verify-email/resend" => ("verify-email-resend", per_hour(10)),
+        "/2fa/totp/enroll" => ("2fa-totp-enroll", per_minute(10)),
+        "/2fa/totp/confirm" => ("2fa-totp-confirm", per_minute(10)),
+        "/2fa/recovery-codes" => ("2fa-recovery-codes", per_minute(10)),
+        "/oauth/token" => ("oauth-token", per_minute(10)),
+        "/introspect" => ("introspect", per_minute(10)),
+        "/revoke" => ("revoke", per_minute(10)),
+        _ => ("default", per_minute(120)),
+    }
+}
+
+const fn per_minute(requests: u32) -> RateLimit {
+    RateLimit {
+        capacity: requests,
+        refill_interval: Duration::from_millis(60_000 / requests as u64),
+    }
+}
+
+const fn per_hour(requests: u32) -> RateLimit {
+    RateLimit {
+        capacity: requests,
+        refill_interval: Duration::from_millis(3_600_000 / requests as u64),
+    }
+}
+
+// Middleware that responds with 429 Too Many Requests once a client used up the budget of
+// the route. Requests are let through if the budget can't be checked.
+#[tracing::instrument(name = "Rate limiting", skip_all)]
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(ip) = client_ip(request.headers(), request.extensions()) else {
+        return next.run(request).await;
+    };
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or_else(|| request.uri().path());
+    let (budget, rate_limit) = rate_limit_for(route);
+
+    let key = format!("{}:{}", budget, ip);
+    let result = state
+        .rate_limit_store
+        .write()
+        .await
+        .take_token(&key, &rate_limit)
+        .await;
+    match result {
+        Ok(None) => next.run(request).await,
+        Ok(Some(wait)) => too_many_requests(wait),
+        Err(e) => {
+            tracing::error!(error = ?e, "failed to check rate limit");
+            next.run(request).await
+        }
+    }
+}
+
+fn too_many_requests(wait: Duration) -> Response {
+    // Retry-After is in whole seconds, so round up to not have clients retry too early
+    let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    let body = Json(ErrorResponse {
+        error: "Too many requests".to_owned(),
+    });
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, retry_after.to_string())],
+        body,
+    )
+        .into_response()
+}
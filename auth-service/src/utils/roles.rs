@@ -0,0 +1,69 @@
+use std::marker::PhantomData;
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use axum_extra::extract::CookieJar;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Permission},
+};
+
+use super::auth::{Claims, get_authenticated_claims};
+
+// A permission a route can require, named by a type so the requirement is part of the
+// route's signature: `RequirePermission<ManageRoles>`
+pub trait RequiredPermission {
+    const PERMISSION: Permission;
+}
+
+pub struct ManageUsers;
+
+impl RequiredPermission for ManageUsers {
+    const PERMISSION: Permission = Permission::ManageUsers;
+}
+
+pub struct ManageRoles;
+
+impl RequiredPermission for ManageRoles {
+    const PERMISSION: Permission = Permission::ManageRoles;
+}
+
+pub struct ManageOAuthClients;
+
+impl RequiredPermission for ManageOAuthClients {
+    const PERMISSION: Permission = Permission::ManageOAuthClients;
+}
+
+pub struct RotateSigningKeys;
+
+impl RequiredPermission for RotateSigningKeys {
+    const PERMISSION: Permission = Permission::RotateSigningKeys;
+}
+
+// Claims of the logged in user, for routes that only users with the permission `P` may use.
+// Permissions are read from the token, so a revoked role only loses its permissions once the
+// token is replaced.
+pub struct RequirePermission<P: RequiredPermission> {
+    pub claims: Claims,
+    permission: PhantomData<P>,
+}
+
+#[axum::async_trait]
+impl<P: RequiredPermission> FromRequestParts<AppState> for RequirePermission<P> {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_headers(&parts.headers);
+        let claims = get_authenticated_claims(&jar, state).await?;
+        if !claims.permissions.contains(&P::PERMISSION) {
+            return Err(AuthAPIError::Forbidden);
+        }
+        Ok(Self {
+            claims,
+            permission: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,677 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use color_eyre::eyre::{Result, WrapErr, eyre};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+        PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+};
+use lazy_static::lazy_static;
+use ring::{
+    rand::SystemRandom,
+    signature::{
+        ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair,
+        RsaPublicKeyComponents,
+    },
+};
+use secrecy::ExposeSecret;
+use sha2::{Digest, Sha256};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::{Duration, Instant},
+};
+
+use super::{
+    auth::EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+    constants::{JWT_KEY_ID, JWT_SECRET, JWT_SIGNING_KEY_PATH, JWT_SIGNING_KEYS_DIR},
+};
+
+lazy_static! {
+    pub static ref JWT_KEY_RING: RwLock<JwtKeyRing> = RwLock::new(load_key_ring());
+}
+
+fn load_key_ring() -> JwtKeyRing {
+    let mut key_ring = JwtKeyRing::new(
+        load_configured_key(),
+        JWT_SIGNING_KEYS_DIR.as_ref().map(PathBuf::from),
+    );
+    key_ring
+        .reload()
+        .expect("JWT_SIGNING_KEYS_DIR must be a readable directory of signing keys.");
+    key_ring
+}
+
+// Without a key file tokens are signed with the shared JWT_SECRET, which can't be published
+fn load_configured_key() -> JwtSigningKey {
+    match JWT_SIGNING_KEY_PATH.as_ref() {
+        Some(path) => {
+            let pem = std::fs::read(path).expect("JWT_SIGNING_KEY_PATH must point to a PEM file.");
+            JwtSigningKey::from_pem(&pem, JWT_KEY_ID.clone())
+                .expect("JWT_SIGNING_KEY_PATH must contain an RSA, P-256 or Ed25519 private key.")
+        }
+        None => JwtSigningKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
+    }
+}
+
+// A lock is only poisoned if a panic happened while it was held. The key ring is never
+// left half updated, so it is still safe to use.
+pub fn read_key_ring() -> RwLockReadGuard<'static, JwtKeyRing> {
+    JWT_KEY_RING.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write_key_ring() -> RwLockWriteGuard<'static, JwtKeyRing> {
+    JWT_KEY_RING.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Start signing tokens with a new key. Tokens signed with the previous key stay valid
+// until they expire. Returns the key id of the new key.
+#[tracing::instrument(name = "Rotating the signing key", skip_all)]
+pub fn rotate_signing_key() -> Result<String> {
+    rotate_key(&JWT_KEY_RING)
+}
+
+fn rotate_key(key_ring: &RwLock<JwtKeyRing>) -> Result<String> {
+    // The key is generated and written to the keys directory without holding the lock, so
+    // tokens can still be signed and validated in the meantime
+    let (keys_dir, algorithm, created_after) = {
+        let key_ring = key_ring.read().unwrap_or_else(PoisonError::into_inner);
+        let keys_dir = key_ring.keys_dir.clone().ok_or(eyre!(
+            "JWT_SIGNING_KEYS_DIR must be set to rotate signing keys"
+        ))?;
+        (
+            keys_dir,
+            key_ring.active_key().algorithm(),
+            key_ring.active_key_created_at(),
+        )
+    };
+    let (created_at, key) = create_key(&keys_dir, algorithm, created_after)?;
+    let kid = key.kid().unwrap_or_default().to_owned();
+    key_ring
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .add_rotated_key(created_at, key);
+    Ok(kid)
+}
+
+// Load the rotated keys from the keys directory again, picking up keys other instances
+// have rotated in. The directory is read without holding the lock, and the keys are swapped
+// in once they are loaded.
+#[tracing::instrument(name = "Reloading the signing keys", skip_all)]
+pub fn reload_key_ring() -> Result<()> {
+    let Some(keys_dir) = read_key_ring().keys_dir.clone() else {
+        return Ok(());
+    };
+    let rotated_keys = read_rotated_keys(&keys_dir)?;
+    write_key_ring().rotated_keys = rotated_keys;
+    Ok(())
+}
+
+// Tokens signed with a key id this instance doesn't know yet were most likely signed by
+// another instance that just rotated its key, so the keys directory is read again rather
+// than waiting for the next periodic reload. Reloads are spaced out, so tokens with made
+// up key ids can't keep the instance busy reading the directory.
+fn reload_key_ring_for_unknown_kid() {
+    static LAST_RELOAD: Mutex<Option<Instant>> = Mutex::new(None);
+    {
+        let mut last_reload = LAST_RELOAD.lock().unwrap_or_else(PoisonError::into_inner);
+        if last_reload
+            .is_some_and(|last_reload| last_reload.elapsed() < UNKNOWN_KID_RELOAD_INTERVAL)
+        {
+            return;
+        }
+        *last_reload = Some(Instant::now());
+    }
+    if let Err(e) = reload_key_ring() {
+        tracing::error!("Failed to reload the signing keys: {:?}", e);
+    }
+}
+
+// The key a token with this key id in its header has to be validated with, reloading the
+// key ring if the key id is unknown
+pub fn find_verification_key(kid: Option<&str>) -> Option<Arc<JwtSigningKey>> {
+    let key = read_key_ring().verification_key(kid);
+    if key.is_some() || kid.is_none() {
+        return key;
+    }
+    reload_key_ring_for_unknown_kid();
+    read_key_ring().verification_key(kid)
+}
+
+// Keeps the key ring in sync with the keys directory, which other instances may have
+// rotated, and rotates the signing key once it is older than the rotation interval.
+pub async fn manage_signing_keys(rotation_interval: Option<Duration>) {
+    let mut interval = tokio::time::interval(KEY_RING_RELOAD_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = reload_key_ring() {
+            tracing::error!("Failed to reload the signing keys: {:?}", e);
+            continue;
+        }
+        let active_key_age = read_key_ring().active_key_age();
+        if let Some(rotation_interval) = rotation_interval
+            && active_key_age >= rotation_interval.as_secs() as i64
+            && let Err(e) = rotate_signing_key()
+        {
+            tracing::error!("Failed to rotate the signing key: {:?}", e);
+        }
+    }
+}
+
+const KEY_RING_RELOAD_INTERVAL: Duration = Duration::from_secs(60 * 5);
+
+// Least time between reloads of the key ring for tokens with an unknown key id
+const UNKNOWN_KID_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+// Retired keys are kept until the last token they signed has expired. Email verification
+// tokens live the longest of all tokens.
+const RETIRED_KEY_TTL_SECONDS: i64 = EMAIL_VERIFICATION_TOKEN_TTL_SECONDS;
+
+// Tokens are signed with the newest key in the ring. The configured key (JWT_SIGNING_KEY_PATH
+// or JWT_SECRET) is the oldest one, and rotated keys are kept in the keys directory as
+// `<created at>-<kid>.pem` files, so they survive restarts and are shared between instances.
+pub struct JwtKeyRing {
+    configured_key: Arc<JwtSigningKey>,
+    keys_dir: Option<PathBuf>,
+    // Rotated keys with their creation timestamps, oldest first
+    rotated_keys: Vec<(i64, Arc<JwtSigningKey>)>,
+}
+
+impl JwtKeyRing {
+    pub fn new(configured_key: JwtSigningKey, keys_dir: Option<PathBuf>) -> Self {
+        Self {
+            configured_key: Arc::new(configured_key),
+            keys_dir,
+            rotated_keys: Vec::new(),
+        }
+    }
+
+    // The key new tokens are signed with
+    pub fn active_key(&self) -> Arc<JwtSigningKey> {
+        self.rotated_keys
+            .last()
+            .map(|(_, key)| key.clone())
+            .unwrap_or_else(|| self.configured_key.clone())
+    }
+
+    fn active_key_age(&self) -> i64 {
+        match self.rotated_keys.last() {
+            Some((created_at, _)) => Utc::now().timestamp() - created_at,
+            None => i64::MAX,
+        }
+    }
+
+    // Creation timestamp of the active key, or 0 for the configured key
+    fn active_key_created_at(&self) -> i64 {
+        self.rotated_keys
+            .last()
+            .map_or(0, |(created_at, _)| *created_at)
+    }
+
+    // A reload may have read the key from the keys directory in the meantime
+    fn add_rotated_key(&mut self, created_at: i64, key: JwtSigningKey) {
+        if self
+            .rotated_keys
+            .iter()
+            .all(|(_, rotated_key)| rotated_key.kid() != key.kid())
+        {
+            self.rotated_keys.push((created_at, Arc::new(key)));
+            self.rotated_keys.sort_by_key(|(created_at, _)| *created_at);
+        }
+    }
+
+    // Keys that tokens may still be signed with: the active key, and retired keys whose
+    // successor was created less than RETIRED_KEY_TTL_SECONDS ago
+    fn valid_keys(&self) -> impl Iterator<Item = &Arc<JwtSigningKey>> {
+        let now = Utc::now().timestamp();
+        let keys = std::iter::once(&self.configured_key)
+            .chain(self.rotated_keys.iter().map(|(_, key)| key));
+        let retired_at = self
+            .rotated_keys
+            .iter()
+            .map(|(created_at, _)| Some(*created_at))
+            .chain(std::iter::once(None));
+        keys.zip(retired_at)
+            .filter(move |(_, retired_at)| {
+                retired_at.is_none_or(|retired_at| retired_at + RETIRED_KEY_TTL_SECONDS > now)
+            })
+            .map(|(key, _)| key)
+    }
+
+    // The key a token with this key id in its header has to be validated with
+    pub fn verification_key(&self, kid: Option<&str>) -> Option<Arc<JwtSigningKey>> {
+        self.valid_keys().find(|key| key.kid() == kid).cloned()
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .valid_keys()
+                .filter_map(|key| key.jwk().cloned())
+                .collect(),
+        }
+    }
+
+    // Load the rotated keys from the keys directory and delete the ones that have expired
+    pub fn reload(&mut self) -> Result<()> {
+        if let Some(keys_dir) = &self.keys_dir {
+            self.rotated_keys = read_rotated_keys(keys_dir)?;
+        }
+        Ok(())
+    }
+}
+
+// The rotated keys in the keys directory with their creation timestamps, oldest first.
+// Keys that have expired are deleted.
+fn read_rotated_keys(keys_dir: &Path) -> Result<Vec<(i64, Arc<JwtSigningKey>)>> {
+    let mut rotated_keys = Vec::new();
+    for entry in std::fs::read_dir(keys_dir).wrap_err("failed to read the keys directory")? {
+        let path = entry?.path();
+        let Some((created_at, kid)) = parse_key_file_name(&path) else {
+            continue;
+        };
+        let pem = std::fs::read(&path).wrap_err("failed to read signing key")?;
+        let key = JwtSigningKey::from_pem(&pem, Some(kid))
+            .wrap_err(format!("failed to load signing key {:?}", path))?;
+        rotated_keys.push((created_at, path, Arc::new(key)));
+    }
+    rotated_keys.sort_by_key(|(created_at, _, _)| *created_at);
+
+    // A key expires once its successor has been signing tokens for long enough
+    let now = Utc::now().timestamp();
+    let expired_keys = rotated_keys
+        .windows(2)
+        .take_while(|pair| pair[1].0 + RETIRED_KEY_TTL_SECONDS <= now)
+        .count();
+    for (_, path, _) in rotated_keys.drain(..expired_keys) {
+        std::fs::remove_file(&path)
+            .wrap_err(format!("failed to delete expired signing key {:?}", path))?;
+    }
+
+    Ok(rotated_keys
+        .into_iter()
+        .map(|(created_at, _, key)| (created_at, key))
+        .collect())
+}
+
+// Generate a signing key to succeed the active key, which was created at `created_after`,
+// and write it to the keys directory. Keys of the same type as the active key are generated
+// for ES256 and EdDSA. RSA keys can't be generated, and a shared secret can't be published,
+// so EdDSA keys are used in place of those.
+fn create_key(
+    keys_dir: &Path,
+    active_algorithm: Algorithm,
+    created_after: i64,
+) -> Result<(i64, JwtSigningKey)> {
+    let algorithm = match active_algorithm {
+        Algorithm::ES256 => Algorithm::ES256,
+        _ => Algorithm::EdDSA,
+    };
+    let pem = generate_pem(algorithm)?;
+    let key = JwtSigningKey::from_pem(pem.as_bytes(), None)?;
+    let kid = key.kid().unwrap_or_default();
+
+    // Keys can't be read back with a lower timestamp than the current active key
+    let created_at = (created_after + 1).max(Utc::now().timestamp());
+    let path = keys_dir.join(format!("{}-{}.pem", created_at, kid));
+    write_private_file(&path, pem.as_bytes())
+        .wrap_err(format!("failed to write signing key {:?}", path))?;
+    Ok((created_at, key))
+}
+
+fn parse_key_file_name(path: &Path) -> Option<(i64, String)> {
+    if path.extension()? != "pem" {
+        return None;
+    }
+    let (created_at, kid) = path.file_stem()?.to_str()?.split_once('-')?;
+    Some((created_at.parse().ok()?, kid.to_owned()))
+}
+
+fn generate_pem(algorithm: Algorithm) -> Result<String> {
+    let rng = SystemRandom::new();
+    let pkcs8 = match algorithm {
+        Algorithm::ES256 => EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng),
+        _ => Ed25519KeyPair::generate_pkcs8(&rng),
+    }
+    .map_err(|_| eyre!("failed to generate signing key"))?;
+    Ok(pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref())))
+}
+
+// Only the owner may read private keys
+fn write_private_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+// The key tokens are signed and validated with. Asymmetric keys also carry the public
+// JWK that is published, so other services can validate tokens without the private key.
+pub struct JwtSigningKey {
+    algorithm: Algorithm,
+    kid: Option<String>,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Option<Jwk>,
+}
+
+impl JwtSigningKey {
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self {
+            algorithm: Algorithm::HS256,
+            kid: None,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+        }
+    }
+
+    // The algorithm follows from the type of the key: RS256 for RSA keys, ES256 for P-256
+    // keys and EdDSA for Ed25519 keys. Without a key id, the key's RFC 7638 thumbprint is used.
+    pub fn from_pem(pem: &[u8], kid: Option<String>) -> Result<Self> {
+        let parsed = pem::parse(pem).wrap_err("failed to parse the PEM file")?;
+        let der = parsed.contents();
+
+        // RSA keys may also be in the older PKCS#1 format, other keys have to be PKCS#8
+        let rsa_key_pair = match parsed.tag() {
+            "RSA PRIVATE KEY" => RsaKeyPair::from_der(der),
+            _ => RsaKeyPair::from_pkcs8(der),
+        };
+
+        let (algorithm, encoding_key, parameters) = if let Ok(key_pair) = rsa_key_pair {
+            let components = RsaPublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+            let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(components.n),
+                e: URL_SAFE_NO_PAD.encode(components.e),
+            });
+            (
+                Algorithm::RS256,
+                EncodingKey::from_rsa_pem(pem)?,
+                parameters,
+            )
+        } else if let Ok(key_pair) =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, der, &SystemRandom::new())
+        {
+            // Uncompressed point: 0x04 followed by the x and y coordinates
+            let point = key_pair.public_key().as_ref();
+            let (x, y) = point[1..].split_at((point.len() - 1) / 2);
+            let parameters = AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x: URL_SAFE_NO_PAD.encode(x),
+                y: URL_SAFE_NO_PAD.encode(y),
+            });
+            (Algorithm::ES256, EncodingKey::from_ec_pem(pem)?, parameters)
+        } else if let Ok(key_pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+            let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+            });
+            (Algorithm::EdDSA, EncodingKey::from_ed_pem(pem)?, parameters)
+        } else {
+            return Err(eyre!("unsupported signing key type"));
+        };
+
+        let kid = kid.unwrap_or_else(|| thumbprint(&parameters));
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm(algorithm)),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+        let decoding_key = DecodingKey::from_jwk(&jwk)?;
+
+        Ok(Self {
+            algorithm,
+            kid: Some(kid),
+            encoding_key,
+            decoding_key,
+            jwk: Some(jwk),
+        })
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    pub fn jwk(&self) -> Option<&Jwk> {
+        self.jwk.as_ref()
+    }
+
+    pub fn header(&self) -> Header {
+        Header {
+            kid: self.kid.clone(),
+            ..Header::new(self.algorithm)
+        }
+    }
+
+    pub fn validation(&self) -> Validation {
+        Validation::new(self.algorithm)
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+}
+
+fn key_algorithm(algorithm: Algorithm) -> KeyAlgorithm {
+    match algorithm {
+        Algorithm::RS256 => KeyAlgorithm::RS256,
+        Algorithm::ES256 => KeyAlgorithm::ES256,
+        Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+        _ => KeyAlgorithm::HS256,
+    }
+}
+
+// RFC 7638 JWK thumbprint: the hash of the required members, in lexicographic order
+fn thumbprint(parameters: &AlgorithmParameters) -> String {
+    let members = match parameters {
+        AlgorithmParameters::RSA(rsa) => {
+            format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, rsa.e, rsa.n)
+        }
+        AlgorithmParameters::EllipticCurve(ec) => format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            ec.x, ec.y
+        ),
+        AlgorithmParameters::OctetKeyPair(okp) => {
+            format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, okp.x)
+        }
+        AlgorithmParameters::OctetKey(oct) => format!(r#"{{"k":"{}","kty":"oct"}}"#, oct.value),
+    };
+    URL_SAFE_NO_PAD.encode(Sha256::digest(members.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{decode, encode};
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    const RSA_KEY: &str = include_str!("../../tests/keys/rsa_private.pem");
+    const EC_KEY: &str = include_str!("../../tests/keys/ec_private.pem");
+    const ED_KEY: &str = include_str!("../../tests/keys/ed_private.pem");
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn round_trip(key: &JwtSigningKey) {
+        let claims = TestClaims {
+            sub: "test@example.com".to_owned(),
+            exp: (chrono::Utc::now().timestamp() + 60) as usize,
+        };
+        let token = encode(&key.header(), &claims, key.encoding_key()).unwrap();
+
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        assert_eq!(header.kid.as_deref(), key.kid());
+
+        // Tokens can be validated with nothing but the published JWK
+        let decoding_key = DecodingKey::from_jwk(key.jwk().unwrap()).unwrap();
+        let decoded = decode::<TestClaims>(&token, &decoding_key, &key.validation()).unwrap();
+        assert_eq!(decoded.claims, claims);
+    }
+
+    #[test]
+    fn test_rsa_key() {
+        let key = JwtSigningKey::from_pem(RSA_KEY.as_bytes(), None).unwrap();
+        assert_eq!(key.validation().algorithms, vec![Algorithm::RS256]);
+        round_trip(&key);
+    }
+
+    #[test]
+    fn test_ec_key() {
+        let key = JwtSigningKey::from_pem(EC_KEY.as_bytes(), None).unwrap();
+        assert_eq!(key.validation().algorithms, vec![Algorithm::ES256]);
+        round_trip(&key);
+    }
+
+    #[test]
+    fn test_ed_key() {
+        let key = JwtSigningKey::from_pem(ED_KEY.as_bytes(), None).unwrap();
+        assert_eq!(key.validation().algorithms, vec![Algorithm::EdDSA]);
+        round_trip(&key);
+    }
+
+    #[test]
+    fn test_kid_defaults_to_thumbprint() {
+        let key = JwtSigningKey::from_pem(ED_KEY.as_bytes(), None).unwrap();
+        let same_key = JwtSigningKey::from_pem(ED_KEY.as_bytes(), None).unwrap();
+        assert_eq!(key.kid(), same_key.kid());
+        assert_eq!(key.kid().unwrap().len(), 43);
+
+        let named_key = JwtSigningKey::from_pem(ED_KEY.as_bytes(), Some("key-1".to_owned()));
+        assert_eq!(named_key.unwrap().kid(), Some("key-1"));
+    }
+
+    #[test]
+    fn test_secret_key_is_not_published() {
+        let key = JwtSigningKey::from_secret(b"secret");
+        assert!(key.kid().is_none());
+        assert!(key.jwk().is_none());
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(JwtSigningKey::from_pem(b"not a key", None).is_err());
+    }
+
+    fn create_keys_dir() -> PathBuf {
+        let keys_dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir(&keys_dir).unwrap();
+        keys_dir
+    }
+
+    fn write_key(keys_dir: &Path, created_at: i64) -> String {
+        let pem = generate_pem(Algorithm::EdDSA).unwrap();
+        let kid = JwtSigningKey::from_pem(pem.as_bytes(), None)
+            .unwrap()
+            .kid()
+            .unwrap()
+            .to_owned();
+        let path = keys_dir.join(format!("{}-{}.pem", created_at, kid));
+        write_private_file(&path, pem.as_bytes()).unwrap();
+        kid
+    }
+
+    #[test]
+    fn test_rotate_requires_keys_dir() {
+        let key_ring = RwLock::new(JwtKeyRing::new(JwtSigningKey::from_secret(b"secret"), None));
+        assert!(rotate_key(&key_ring).is_err());
+        assert!(key_ring.read().unwrap().active_key().kid().is_none());
+    }
+
+    #[test]
+    fn test_rotate_keeps_retired_key_valid() {
+        let keys_dir = create_keys_dir();
+        let configured_key = JwtSigningKey::from_pem(EC_KEY.as_bytes(), None).unwrap();
+        let configured_kid = configured_key.kid().unwrap().to_owned();
+        let key_ring = RwLock::new(JwtKeyRing::new(configured_key, Some(keys_dir.clone())));
+
+        let kid = rotate_key(&key_ring).unwrap();
+        let key_ring = key_ring.into_inner().unwrap();
+        let active_key = key_ring.active_key();
+        assert_eq!(active_key.kid(), Some(kid.as_str()));
+        assert_ne!(kid, configured_kid);
+        // Keys of the same type are generated
+        assert_eq!(active_key.algorithm(), Algorithm::ES256);
+
+        assert!(key_ring.verification_key(Some(&configured_kid)).is_some());
+        assert!(key_ring.verification_key(Some(&kid)).is_some());
+        assert!(key_ring.verification_key(Some("unknown")).is_none());
+        assert_eq!(key_ring.jwks().keys.len(), 2);
+
+        // Another instance picks up the rotated key from the keys directory
+        let mut other_key_ring = JwtKeyRing::new(
+            JwtSigningKey::from_pem(EC_KEY.as_bytes(), None).unwrap(),
+            Some(keys_dir.clone()),
+        );
+        other_key_ring.reload().unwrap();
+        assert_eq!(other_key_ring.active_key().kid(), Some(kid.as_str()));
+
+        std::fs::remove_dir_all(keys_dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotated_key_is_added_once() {
+        let keys_dir = create_keys_dir();
+        let mut key_ring = JwtKeyRing::new(
+            JwtSigningKey::from_secret(b"secret"),
+            Some(keys_dir.clone()),
+        );
+        let (created_at, key) = create_key(&keys_dir, Algorithm::HS256, 0).unwrap();
+        let kid = key.kid().unwrap().to_owned();
+
+        // The key ring was reloaded after the key was written, but before it was added
+        key_ring.reload().unwrap();
+        key_ring.add_rotated_key(created_at, key);
+        assert_eq!(key_ring.active_key().kid(), Some(kid.as_str()));
+        assert_eq!(key_ring.jwks().keys.len(), 1);
+
+        std::fs::remove_dir_all(keys_dir).unwrap();
+    }
+
+    #[test]
+    fn test_reload_removes_expired_keys() {
+        let keys_dir = create_keys_dir();
+        let now = Utc::now().timestamp();
+        let expired_kid = write_key(&keys_dir, now - 3 * RETIRED_KEY_TTL_SECONDS);
+        let retired_kid = write_key(&keys_dir, now - 2 * RETIRED_KEY_TTL_SECONDS);
+        let active_kid = write_key(&keys_dir, now - 10);
+
+        let mut key_ring = JwtKeyRing::new(
+            JwtSigningKey::from_secret(b"secret"),
+            Some(keys_dir.clone()),
+        );
+        key_ring.reload().unwrap();
+
+        assert_eq!(key_ring.active_key().kid(), Some(active_kid.as_str()));
+        assert!(key_ring.verification_key(Some(&retired_kid)).is_some());
+        assert!(key_ring.verification_key(Some(&expired_kid)).is_none());
+        // The configured secret was retired by the first rotated key long ago
+        assert!(key_ring.verification_key(None).is_none());
+        assert_eq!(std::fs::read_dir(&keys_dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(keys_dir).unwrap();
+    }
+}
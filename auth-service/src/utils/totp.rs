@@ -0,0 +1,117 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre::{Result, WrapErr};
+use secrecy::ExposeSecret;
+use totp_rs::{Algorithm, TOTP};
+
+use crate::domain::{Email, TotpSecret};
+
+use super::constants::TOTP_SKEW_STEPS;
+
+// Name shown for the account in authenticator apps
+const TOTP_ISSUER: &str = "LiveBootcamp";
+// The defaults of Google Authenticator and most other apps
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+
+fn build_totp(secret: &TotpSecret, email: &Email) -> Result<TOTP> {
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        *TOTP_SKEW_STEPS,
+        TOTP_STEP_SECONDS,
+        secret.to_bytes()?,
+        Some(TOTP_ISSUER.to_owned()),
+        email.as_ref().expose_secret().to_owned(),
+    )
+    .wrap_err("failed to create TOTP")
+}
+
+// The otpauth:// URI authenticator apps enroll from, usually by scanning it as a QR code
+#[tracing::instrument(name = "Creating the TOTP URI", skip_all)]
+pub fn create_totp_uri(secret: &TotpSecret, email: &Email) -> Result<String> {
+    Ok(build_totp(secret, email)?.get_url())
+}
+
+// Returns the time step the code is valid for, if it is one of the current code or the
+// codes of the steps within the skew window around it. The step is what callers record to
+// refuse the code if it's given again.
+#[tracing::instrument(name = "Verifying the TOTP code", skip_all)]
+pub fn verify_totp_code(secret: &TotpSecret, email: &Email, code: &str) -> Result<Option<u64>> {
+    let totp = TOTP {
+        skew: 0,
+        ..build_totp(secret, email)?
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .wrap_err("failed to read the system time")?
+        .as_secs();
+    let current_step = now / TOTP_STEP_SECONDS;
+    let skew = u64::from(*TOTP_SKEW_STEPS);
+    // `check` compares in constant time, so timing doesn't tell how close a guess was
+    let step = (current_step.saturating_sub(skew)..=current_step + skew)
+        .rev()
+        .find(|step| totp.check(code, step * TOTP_STEP_SECONDS));
+    Ok(step)
+}
+
+// TOTP codes are any 6 digits, leading zeros included
+pub fn is_valid_totp_code_format(code: &str) -> bool {
+    code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse(Secret::new("test@example.com".to_owned())).unwrap()
+    }
+
+    #[test]
+    fn test_create_totp_uri() {
+        let secret = TotpSecret::default();
+        let uri = create_totp_uri(&secret, &email()).unwrap();
+        assert!(uri.starts_with("otpauth://totp/LiveBootcamp:test%40example.com?"));
+        assert!(uri.contains(&format!("secret={}", secret.as_ref().expose_secret())));
+    }
+
+    #[test]
+    fn test_verify_totp_code() {
+        let secret = TotpSecret::default();
+        let totp = build_totp(&secret, &email()).unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let code = totp.generate(now);
+        assert_eq!(
+            verify_totp_code(&secret, &email(), &code).unwrap(),
+            Some(now / TOTP_STEP_SECONDS)
+        );
+
+        // Codes from within the skew window are valid for their own step
+        let previous_code = totp.generate(now - TOTP_STEP_SECONDS);
+        assert_eq!(
+            verify_totp_code(&secret, &email(), &previous_code).unwrap(),
+            Some(now / TOTP_STEP_SECONDS - 1)
+        );
+
+        // Codes from far outside of the skew window are rejected
+        let old_code = totp.generate(now - 10 * TOTP_STEP_SECONDS);
+        assert_eq!(
+            verify_totp_code(&secret, &email(), &old_code).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_valid_totp_code_format() {
+        assert!(is_valid_totp_code_format("012345"));
+        assert!(!is_valid_totp_code_format("12345"));
+        assert!(!is_valid_totp_code_format("12345a"));
+    }
+}
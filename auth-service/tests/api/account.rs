@@ -0,0 +1,160 @@
+use auth_service::{
+    domain::Email,
+    routes::DeleteAccountResponse,
+    utils::{JWT_COOKIE_NAME, purge_deleted_accounts},
+};
+use chrono::Utc;
+use secrecy::Secret;
+
+use crate::helpers::{TestApp, get_cookie, login_body, mount_email_server, signup_and_login};
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let response = app.delete_account(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_password() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let auth_token = get_cookie(&app, JWT_COOKIE_NAME);
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "wrong-password" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_delete_account_and_restore_it_on_login() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    let auth_token = get_cookie(&app, JWT_COOKIE_NAME);
+    mount_email_server(&app, ..).await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(auth_cookie.value().is_empty());
+    assert_eq!(
+        response
+            .json::<DeleteAccountResponse>()
+            .await
+            .expect("Could not deserialize response body to DeleteAccountResponse"),
+        DeleteAccountResponse {
+            message: "Account has been deleted. Log in within 30 days to restore it.".to_owned(),
+        }
+    );
+
+    // All sessions end with the deletion
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login(&login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Restored accounts aren't purged
+    let purged = purge_deleted_accounts(&app.app_state, Utc::now().timestamp() + 1)
+        .await
+        .expect("Failed to purge deleted accounts");
+    assert!(purged.is_empty());
+    let response = app.post_login(&login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_restore_account_before_2fa_code_is_given() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    mount_email_server(&app, ..).await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let parsed_email = Email::parse(Secret::new(email.clone())).unwrap();
+    app.app_state
+        .user_store
+        .write()
+        .await
+        .set_requires_2fa(&parsed_email, true)
+        .await
+        .expect("Failed to turn on 2FA");
+
+    // The password alone only starts the login
+    let response = app.post_login(&login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let purged = purge_deleted_accounts(&app.app_state, Utc::now().timestamp() + 1)
+        .await
+        .expect("Failed to purge deleted accounts");
+    assert_eq!(purged, vec![parsed_email]);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_purge_account_after_grace_period() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    mount_email_server(&app, ..).await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Accounts still in their grace period are kept
+    let purged = purge_deleted_accounts(&app.app_state, Utc::now().timestamp() - 60)
+        .await
+        .expect("Failed to purge deleted accounts");
+    assert!(purged.is_empty());
+
+    let purged = purge_deleted_accounts(&app.app_state, Utc::now().timestamp() + 1)
+        .await
+        .expect("Failed to purge deleted accounts");
+    assert_eq!(
+        purged,
+        vec![Email::parse(Secret::new(email.clone())).unwrap()]
+    );
+
+    let response = app.post_login(&login_body(&email)).await;
+    assert_ne!(response.status().as_u16(), 200);
+
+    // The address is free to sign up with again
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.clean_up().await;
+}
@@ -0,0 +1,443 @@
+use auth_service::{
+    ErrorResponse,
+    domain::{Permission, Role, User, UserStatus},
+    routes::{AdminUserDetailsResponse, RolesResponse, UsersResponse},
+    utils::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+use secrecy::ExposeSecret;
+
+use crate::helpers::{
+    TestApp, get_cookie_value, get_random_email, get_token_claims, get_user, login_admin,
+    login_body, mount_email_server, signup, signup_with_2fa,
+};
+
+// Logs the user in with a client of their own, which keeps their cookies
+async fn login_other_client(app: &TestApp, user: &User) -> reqwest::Client {
+    let client = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let response = client
+        .post(format!("{}/login", &app.address))
+        .json(&login_body(user.email.as_ref().expose_secret()))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    client
+}
+
+async fn get_user_details(app: &TestApp, user: &User) -> AdminUserDetailsResponse {
+    let response = app.get_admin_user(user.id.as_ref()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<AdminUserDetailsResponse>()
+        .await
+        .expect("Could not deserialize response body to AdminUserDetailsResponse")
+}
+
+async fn get_error(response: reqwest::Response) -> String {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+    let user = signup(&app).await;
+
+    let response = app
+        .post_grant_role(user.id.as_ref(), &serde_json::json!({ "role": "admin" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_if_not_admin() {
+    let mut app = TestApp::new().await;
+    let user = signup(&app).await;
+    let response = app
+        .post_login(&login_body(user.email.as_ref().expose_secret()))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_grant_role(user.id.as_ref(), &serde_json::json!({ "role": "admin" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Insufficient permissions".to_owned()
+    );
+
+    let response = app.delete_role(user.id.as_ref(), "admin").await;
+    assert_eq!(response.status().as_u16(), 403);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_user_not_found() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+
+    let body = serde_json::json!({ "role": "admin" });
+    for id in ["not-a-uuid", "4f1d2c3b-9a8e-4d7c-b6a5-0e1f2a3b4c5d"] {
+        let response = app.post_grant_role(id, &body).await;
+        assert_eq!(response.status().as_u16(), 404, "Failed for id: {}", id);
+
+        let response = app.delete_role(id, "admin").await;
+        assert_eq!(response.status().as_u16(), 404, "Failed for id: {}", id);
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_role_does_not_exist() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let user = signup(&app).await;
+
+    let response = app
+        .post_grant_role(user.id.as_ref(), &serde_json::json!({ "role": "owner" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.delete_role(user.id.as_ref(), "owner").await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_grant_role() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let user = signup(&app).await;
+
+    let response = app
+        .post_grant_role(user.id.as_ref(), &serde_json::json!({ "role": "admin" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<RolesResponse>()
+            .await
+            .expect("Could not deserialize response body to RolesResponse"),
+        RolesResponse {
+            roles: vec![Role::Admin]
+        }
+    );
+
+    // Tokens issued from then on carry the role
+    let response = reqwest::Client::new()
+        .post(format!("{}/login", &app.address))
+        .json(&login_body(user.email.as_ref().expose_secret()))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let token = get_cookie_value(&response, JWT_COOKIE_NAME);
+    let claims = get_token_claims(&token);
+    assert_eq!(claims.roles, vec![Role::Admin]);
+    assert!(claims.permissions.contains(&Permission::ManageRoles));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_role_and_tokens_carrying_it() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let user = signup(&app).await;
+    app.app_state
+        .user_store
+        .write()
+        .await
+        .grant_role(&user.id, Role::Admin)
+        .await
+        .unwrap();
+    let user_client = login_other_client(&app, &user).await;
+
+    let response = app.delete_role(user.id.as_ref(), "admin").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<RolesResponse>()
+            .await
+            .expect("Could not deserialize response body to RolesResponse"),
+        RolesResponse { roles: vec![] }
+    );
+
+    // The user's token still carries the role, so it no longer works
+    let response = user_client
+        .get(format!("{}/sessions", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Their session stays, and a refresh gets them a token without the role
+    let response = user_client
+        .post(format!("{}/refresh", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!get_cookie_value(&response, REFRESH_TOKEN_COOKIE_NAME).is_empty());
+    let token = get_cookie_value(&response, JWT_COOKIE_NAME);
+    let claims = get_token_claims(&token);
+    assert!(claims.roles.is_empty());
+    assert!(claims.permissions.is_empty());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_from_user_routes_if_not_admin() {
+    let mut app = TestApp::new().await;
+    let user = signup(&app).await;
+    let response = app
+        .post_login(&login_body(user.email.as_ref().expose_secret()))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let id = user.id.as_ref();
+
+    assert_eq!(app.get_admin_users("").await.status().as_u16(), 403);
+    assert_eq!(app.get_admin_user(id).await.status().as_u16(), 403);
+    for action in ["suspend", "reactivate", "password-reset", "2fa/reset"] {
+        let response = app.post_admin_user_action(id, action).await;
+        assert_eq!(response.status().as_u16(), 403, "Failed for {}", action);
+    }
+    assert_eq!(
+        app.delete_admin_user_sessions(id).await.status().as_u16(),
+        403
+    );
+    assert_eq!(app.post_rotate_signing_key().await.status().as_u16(), 403);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_users_a_page_at_a_time() {
+    let mut app = TestApp::new().await;
+    let admin = login_admin(&app).await;
+    let mut emails = vec![admin.email.as_ref().expose_secret().to_owned()];
+    for _ in 0..3 {
+        let user = signup(&app).await;
+        emails.push(user.email.as_ref().expose_secret().to_owned());
+    }
+    emails.sort();
+
+    let response = app.get_admin_users("page=2&perPage=3").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<UsersResponse>()
+        .await
+        .expect("Could not deserialize response body to UsersResponse");
+    assert_eq!((body.page, body.per_page, body.total), (2, 3, 4));
+    assert_eq!(body.users.len(), 1);
+    assert_eq!(body.users[0].email, emails[3]);
+
+    // Searching ignores case
+    let search = emails[1][..8].to_uppercase();
+    let response = app.get_admin_users(&format!("search={}", search)).await;
+    let body = response
+        .json::<UsersResponse>()
+        .await
+        .expect("Could not deserialize response body to UsersResponse");
+    assert_eq!(body.total, 1);
+    assert_eq!(body.users[0].email, emails[1]);
+
+    let response = app.get_admin_users("search=%25").await;
+    let body = response
+        .json::<UsersResponse>()
+        .await
+        .expect("Could not deserialize response body to UsersResponse");
+    assert_eq!(body.total, 0);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_user_details() {
+    let mut app = TestApp::new().await;
+    let admin = login_admin(&app).await;
+
+    let details = get_user_details(&app, &admin).await;
+    assert_eq!(details.user.id, admin.id.as_ref());
+    assert_eq!(
+        details.user.email,
+        admin.email.as_ref().expose_secret().to_owned()
+    );
+    assert_eq!(details.user.status, UserStatus::Active);
+    assert_eq!(details.roles, vec![Role::Admin]);
+
+    let response = app
+        .get_admin_user("4f1d2c3b-9a8e-4d7c-b6a5-0e1f2a3b4c5d")
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_suspend_and_reactivate_user() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let user = signup(&app).await;
+    let user_client = login_other_client(&app, &user).await;
+
+    let response = app
+        .post_admin_user_action(user.id.as_ref(), "suspend")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        get_user_details(&app, &user).await.user.status,
+        UserStatus::Suspended
+    );
+
+    // The token the user holds is rejected, their session has ended, and they can't
+    // log in again
+    let response = user_client
+        .get(format!("{}/sessions", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(get_error(response).await, "Account has been suspended");
+    let response = user_client
+        .post(format!("{}/refresh", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+    let response = user_client
+        .post(format!("{}/login", &app.address))
+        .json(&login_body(user.email.as_ref().expose_secret()))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(get_error(response).await, "Account has been suspended");
+
+    let response = app
+        .post_admin_user_action(user.id.as_ref(), "reactivate")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        get_user_details(&app, &user).await.user.status,
+        UserStatus::Active
+    );
+    login_other_client(&app, &user).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_force_password_reset() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let user = signup(&app).await;
+    let user_client = login_other_client(&app, &user).await;
+    mount_email_server(&app, ..).await;
+
+    let response = app
+        .post_admin_user_action(user.id.as_ref(), "password-reset")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        get_user_details(&app, &user)
+            .await
+            .user
+            .password_reset_required
+    );
+
+    let response = user_client
+        .post(format!("{}/refresh", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+    let response = user_client
+        .post(format!("{}/login", &app.address))
+        .json(&login_body(user.email.as_ref().expose_secret()))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 403);
+
+    // The emailed link lifts the requirement
+    let token = app
+        .password_reset_token_store
+        .read()
+        .await
+        .get_token(&user.email)
+        .await
+        .expect("No password reset token was sent");
+    let response = user_client
+        .post(format!("{}/password-reset/confirm", &app.address))
+        .json(&serde_json::json!({
+            "email": user.email.as_ref().expose_secret(),
+            "token": token.as_ref().expose_secret(),
+            "newPassword": "password123",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    login_other_client(&app, &user).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reset_2fa() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let email = get_random_email();
+    signup_with_2fa(&app, &email).await;
+    let user = get_user(&app, &email).await;
+    mount_email_server(&app, ..).await;
+
+    let response = app
+        .post_admin_user_action(user.id.as_ref(), "2fa/reset")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!get_user_details(&app, &user).await.user.requires_2fa);
+
+    // The user logs in with their password alone
+    login_other_client(&app, &user).await;
+
+    let response = app
+        .post_admin_user_action(user.id.as_ref(), "2fa/reset")
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_all_sessions_of_user() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let user = signup(&app).await;
+    let user_client = login_other_client(&app, &user).await;
+
+    let response = app.delete_admin_user_sessions(user.id.as_ref()).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = user_client
+        .get(format!("{}/sessions", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+    let response = user_client
+        .post(format!("{}/refresh", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The admin's own session is unaffected
+    assert_eq!(app.get_admin_users("").await.status().as_u16(), 200);
+    app.clean_up().await;
+}
@@ -0,0 +1,187 @@
+use auth_service::{ErrorResponse, routes::ChangeEmailResponse, utils::JWT_COOKIE_NAME};
+
+use crate::helpers::{
+    TestApp, get_link_token, get_random_email, login_body, mount_email_server, signup_and_login,
+};
+
+fn change_email_body(new_email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "newEmail": new_email,
+        "password": "password123",
+    })
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+    let response = app
+        .post_change_email(&change_email_body(&get_random_email()))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let test_cases = [
+        serde_json::json!({ "newEmail": get_random_email() }),
+        serde_json::json!({ "password": "password123" }),
+    ];
+    for test_case in test_cases {
+        let response = app.post_change_email(&test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "failed for input: {:?}",
+            test_case
+        );
+    }
+    let response = app.post_change_email_confirm(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    mount_email_server(&app, 0).await;
+
+    for new_email in ["invalidemail", email.as_str()] {
+        let response = app.post_change_email(&change_email_body(new_email)).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "failed for new email: {}",
+            new_email
+        );
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_password() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+    mount_email_server(&app, 0).await;
+
+    let body = serde_json::json!({
+        "newEmail": get_random_email(),
+        "password": "wrong-password",
+    });
+    let response = app.post_change_email(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_409_if_new_email_taken() {
+    let mut app = TestApp::new().await;
+    let other_email = signup_and_login(&app).await;
+    signup_and_login(&app).await;
+    mount_email_server(&app, 0).await;
+
+    let response = app
+        .post_change_email(&change_email_body(&other_email))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let mut app = TestApp::new().await;
+    let response = app
+        .post_change_email_confirm(&serde_json::json!({ "token": "invalid" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Email change link is invalid or has expired".to_owned()
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_change_email_once_confirmed_from_new_address() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    let new_email = get_random_email();
+    mount_email_server(&app, 2).await;
+
+    let response = app.post_change_email(&change_email_body(&new_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<ChangeEmailResponse>()
+            .await
+            .expect("Could not deserialize response body to ChangeEmailResponse"),
+        ChangeEmailResponse {
+            message: "A confirmation link has been sent to the new email address.".to_owned(),
+        }
+    );
+
+    // The link goes to the new address, while the old one only gets notified
+    let requests = app
+        .email_server
+        .received_requests()
+        .await
+        .expect("Request recording is disabled");
+    let recipient = |request: &wiremock::Request| {
+        let body: serde_json::Value =
+            serde_json::from_slice(&request.body).expect("Failed to parse email request body");
+        body["To"].as_str().map(str::to_owned)
+    };
+    let confirmation = requests
+        .iter()
+        .find(|request| recipient(request).as_deref() == Some(new_email.as_str()))
+        .expect("No email sent to the new address");
+    let token = get_link_token(confirmation, "changeEmailToken")
+        .expect("Email contains no confirmation link");
+    let notification = requests
+        .iter()
+        .find(|request| recipient(request).as_deref() == Some(email.as_str()))
+        .expect("No email sent to the old address");
+    assert!(get_link_token(notification, "changeEmailToken").is_none());
+
+    // Nothing changes until the link is followed
+    let response = app.post_login(&login_body(&email)).await;
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_change_email_confirm(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Tokens naming the old address no longer work, and neither does the link
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_change_email_confirm(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login(&login_body(&email)).await;
+    assert_ne!(response.status().as_u16(), 200);
+    let response = app.post_login(&login_body(&new_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
@@ -0,0 +1,158 @@
+use auth_service::{
+    ErrorResponse,
+    routes::{ChangePasswordResponse, SessionsResponse},
+    utils::JWT_COOKIE_NAME,
+};
+use reqwest::Url;
+
+use crate::helpers::{TEST_PASSWORD, TestApp, get_cookie_value, login_body, signup_and_login};
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+    let body = serde_json::json!({
+        "currentPassword": TEST_PASSWORD,
+        "newPassword": "password456",
+    });
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let mut app = TestApp::new().await;
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let body = serde_json::json!({
+        "currentPassword": TEST_PASSWORD,
+        "newPassword": "password456",
+    });
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let test_cases = [
+        serde_json::json!({ "currentPassword": TEST_PASSWORD }),
+        serde_json::json!({ "newPassword": "password456" }),
+    ];
+    for test_case in test_cases {
+        let response = app.post_change_password(&test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "failed for input: {:?}",
+            test_case
+        );
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_new_password() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let body = serde_json::json!({
+        "currentPassword": TEST_PASSWORD,
+        "newPassword": "short",
+    });
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_current_password() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+
+    let body = serde_json::json!({
+        "currentPassword": "wrong-password",
+        "newPassword": "password456",
+    });
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Incorrect credentials".to_owned()
+    );
+
+    // The password was left alone
+    let response = app.post_login(&login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_change_password_and_end_other_sessions() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+
+    // Log in from another device, which doesn't share the app client's cookies
+    let response = reqwest::Client::new()
+        .post(format!("{}/login", &app.address))
+        .json(&login_body(&email))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let other_token = get_cookie_value(&response, JWT_COOKIE_NAME);
+
+    let body = serde_json::json!({
+        "currentPassword": TEST_PASSWORD,
+        "newPassword": "password456",
+    });
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<ChangePasswordResponse>()
+            .await
+            .expect("Could not deserialize response body to ChangePasswordResponse"),
+        ChangePasswordResponse {
+            message: "Password has been changed.".to_owned(),
+        }
+    );
+
+    // The other session has ended, while this one goes on
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let sessions = response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+        .sessions;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+
+    let response = app.post_login(&login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password456",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
@@ -0,0 +1,187 @@
+use auth_service::{
+    OAuthErrorResponse,
+    routes::{IntrospectionResponse, TokenResponse},
+    utils::ClientClaims,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+use crate::helpers::{TestApp, login_admin, register_client};
+
+// Reads the claims of a client's token without validating it
+fn get_client_token_claims(token: &str) -> ClientClaims {
+    let payload = token.split('.').nth(1).expect("Token has no payload");
+    let payload = URL_SAFE_NO_PAD.decode(payload).unwrap();
+    serde_json::from_slice(&payload).expect("Failed to parse client token claims")
+}
+
+#[tokio::test]
+async fn should_issue_token_for_client_credentials() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let client = register_client(&app, true).await;
+    assert!(client.client.confidential);
+    let client_id = client.client.client_id.clone();
+    let client_secret = client.client_secret.expect("No client secret issued");
+
+    let response = app
+        .post_oauth_token_with_basic_auth(
+            &client_id,
+            &client_secret,
+            &[("grant_type", "client_credentials"), ("scope", "jobs:read")],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("cache-control").unwrap(), "no-store");
+    let token = response.json::<TokenResponse>().await.unwrap();
+    assert_eq!(token.token_type, "Bearer");
+    assert_eq!(token.scope, "jobs:read");
+    assert!(token.id_token.is_none());
+
+    let claims = get_client_token_claims(&token.access_token);
+    assert_eq!(claims.sub, client_id);
+    assert_eq!(claims.client_id, client_id);
+    assert_eq!(claims.scope, "jobs:read");
+
+    // Client tokens aren't logins, so they aren't valid to the service's own routes
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token.access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The secret can be sent in the body as well, and clients get all their scopes by default
+    let response = app
+        .post_oauth_token(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", &client_id),
+            ("client_secret", &client_secret),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = response.json::<TokenResponse>().await.unwrap();
+    assert_eq!(token.scope, "jobs:read jobs:write");
+
+    // Client tokens can't get user info, as there is no user
+    let response = app.get_userinfo(Some(&token.access_token)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Deleting the client revokes its tokens, as another client finds at `/introspect`
+    let other_client = register_client(&app, true).await;
+    let other_client_id = other_client.client.client_id.as_str();
+    let other_client_secret = other_client.client_secret.as_deref().unwrap();
+    let response = app.delete_admin_oauth_client(&client_id).await;
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app
+        .post_introspect(
+            other_client_id,
+            other_client_secret,
+            &[("token", token.access_token.as_str())],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        !response
+            .json::<IntrospectionResponse>()
+            .await
+            .unwrap()
+            .active
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_invalid_client_credentials() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let client = register_client(&app, true).await;
+    let client_id = client.client.client_id.as_str();
+    let client_secret = client.client_secret.as_deref().unwrap();
+
+    let response = app
+        .post_oauth_token_with_basic_auth(
+            client_id,
+            "wrong-secret",
+            &[("grant_type", "client_credentials")],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response.headers().contains_key("www-authenticate"));
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_client");
+
+    let test_cases = [
+        (
+            vec![("grant_type", "client_credentials")],
+            "invalid_request",
+        ),
+        (
+            vec![
+                ("grant_type", "client_credentials"),
+                ("client_id", client_id),
+                ("client_secret", "wrong-secret"),
+            ],
+            "invalid_client",
+        ),
+        (
+            vec![
+                ("grant_type", "client_credentials"),
+                ("client_id", client_id),
+                ("client_secret", client_secret),
+                ("scope", "admin"),
+            ],
+            "invalid_scope",
+        ),
+    ];
+    for (form, expected_error) in test_cases {
+        let response = app.post_oauth_token(&form).await;
+        let error = response.json::<OAuthErrorResponse>().await.unwrap();
+        assert_eq!(error.error, expected_error, "Failed for {:?}", form);
+    }
+
+    // Secrets must not be sent in both the header and the body
+    let response = app
+        .post_oauth_token_with_basic_auth(
+            client_id,
+            client_secret,
+            &[
+                ("grant_type", "client_credentials"),
+                ("client_secret", client_secret),
+            ],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_request");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_issue_client_token_to_public_client() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let client = register_client(&app, false).await;
+    assert!(!client.client.confidential);
+    assert!(client.client_secret.is_none());
+
+    let response = app
+        .post_oauth_token(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", &client.client.client_id),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "unauthorized_client");
+
+    // Only confidential clients can do without redirect URIs
+    let response = app
+        .post_admin_oauth_client(&serde_json::json!({
+            "name": "Backend job",
+            "redirectUris": [],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
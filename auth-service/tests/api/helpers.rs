@@ -1,16 +1,23 @@
-use auth_service::app_state::{BannedTokenStoreType, TwoFACodeStoreType};
-use auth_service::domain::Email;
+use auth_service::app_state::{
+    AppState, BannedTokenStoreType, FailedLoginStoreType, PasswordResetTokenStoreType,
+    RefreshTokenStoreType, TwoFACodeStoreType,
+};
+use auth_service::domain::{Email, Role, User};
+use auth_service::routes::{RegisteredOAuthClientResponse, SignupResponse, TwoFactorAuthResponse};
 use auth_service::services::{
-    MockEmailClient, PostgresUserStore, PostmarkEmailClient, RedisBannedTokenStore,
-    RedisTwoFACodeStore,
+    HashmapRateLimitStore, PostgresOAuthClientStore, PostgresRecoveryCodeStore,
+    PostgresSessionStore, PostgresUserStore, PostmarkEmailClient, RedisAuthorizationCodeStore,
+    RedisBannedTokenStore, RedisFailedLoginStore, RedisPasswordResetTokenStore,
+    RedisRefreshTokenStore, RedisTwoFACodeStore,
 };
-use auth_service::utils::DATABASE_URL;
 use auth_service::utils::constants::test;
 use auth_service::utils::env::DEFAULT_REDIS_HOSTNAME;
+use auth_service::utils::{Claims, DATABASE_URL};
 use auth_service::{Application, get_postgres_pool, get_redis_client};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use core::panic;
-use reqwest::Client;
-use reqwest::cookie::Jar;
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::{Client, Url};
 use secrecy::{ExposeSecret, Secret};
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
@@ -18,13 +25,19 @@ use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate, Times};
 
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub failed_login_store: FailedLoginStoreType,
+    // For running the service's background jobs on demand
+    pub app_state: AppState,
     pub email_server: MockServer,
     pub http_client: reqwest::Client,
     pub database_name: String,
@@ -35,22 +48,49 @@ impl TestApp {
     pub async fn new() -> Self {
         let (pg_pool, database_name) = configure_postgresql().await;
         let redis_connection = Arc::new(RwLock::new(configure_redis()));
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone())));
+        let recovery_code_store =
+            Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+        let session_store = Arc::new(RwLock::new(PostgresSessionStore::new(pg_pool.clone())));
+        let oauth_client_store = Arc::new(RwLock::new(PostgresOAuthClientStore::new(pg_pool)));
         let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
             redis_connection.clone(),
         )));
-        let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
+        let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(
+            redis_connection.clone(),
+        )));
+        let refresh_token_store = Arc::new(RwLock::new(RedisRefreshTokenStore::new(
+            redis_connection.clone(),
+        )));
+        let failed_login_store = Arc::new(RwLock::new(RedisFailedLoginStore::new(
+            redis_connection.clone(),
+        )));
+        let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(
+            redis_connection.clone(),
+        )));
+        let password_reset_token_store = Arc::new(RwLock::new(RedisPasswordResetTokenStore::new(
+            redis_connection,
+        )));
         let email_server = MockServer::start().await;
         let base_url = email_server.uri();
         let email_client = Arc::new(RwLock::new(configure_postmark_email_client(base_url)));
 
-        let app_state = auth_service::app_state::AppState::new(
+        let app_state = AppState::new(
             user_store,
             banned_token_store.clone(),
             two_fa_code_store.clone(),
             email_client,
+            refresh_token_store.clone(),
+            password_reset_token_store.clone(),
+            recovery_code_store,
+            session_store,
+            failed_login_store.clone(),
+            // Every test app gets budgets of its own, although all tests use the same address
+            Arc::new(RwLock::new(HashmapRateLimitStore::new())),
+            oauth_client_store,
+            authorization_code_store,
         );
-        let app = Application::build(app_state, test::APP_ADDRESS)
+        let app = Application::build(app_state.clone(), test::APP_ADDRESS)
             .await
             .expect("Failed to build app");
 
@@ -74,6 +114,10 @@ impl TestApp {
             cookie_jar,
             http_client,
             two_fa_code_store,
+            refresh_token_store,
+            password_reset_token_store,
+            failed_login_store,
+            app_state,
             database_name,
             email_server,
             clean_up_called: false,
@@ -87,7 +131,15 @@ impl TestApp {
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -100,19 +152,46 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    // Signs up a user and follows the link from the verification email,
+    // so that the new account can log in right away
+    pub async fn post_signup_and_verify_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let verification_email = Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .with_priority(1)
+            .expect(1)
+            .mount_as_scoped(&self.email_server)
+            .await;
+
+        let response = self.post_signup(body).await;
+        assert_eq!(response.status().as_u16(), 201);
+
+        let requests = verification_email.received_requests().await;
+        let token = get_email_verification_token(&requests[0]);
+        let verify_email_response = self
+            .post_verify_email(&serde_json::json!({ "token": token }))
+            .await;
+        assert_eq!(verify_email_response.status().as_u16(), 200);
+
+        response
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
@@ -121,19 +200,277 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
             .header("jwt", "foo")
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .delete(format!("{}/account", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_users(&self, query: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/users?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_user(&self, user_id: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/users/{}", &self.address, user_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Posts to one of the routes acting on a user, like `disable` or `2fa/reset`
+    pub async fn post_admin_user_action(&self, user_id: &str, action: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!(
+                "{}/admin/users/{}/{}",
+                &self.address, user_id, action
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_admin_user_sessions(&self, user_id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!(
+                "{}/admin/users/{}/sessions",
+                &self.address, user_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_rotate_signing_key(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin/signing-key/rotate", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_grant_role<Body>(&self, user_id: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/users/{}/roles", &self.address, user_id))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_role(&self, user_id: &str, role: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!(
+                "{}/admin/users/{}/roles/{}",
+                &self.address, user_id, role
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/verify-2fa", &self.address))
+            .post(format!("{}/verify-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-email/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email/resend", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_enable_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/enable", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_disable_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/disable", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_enroll<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/enroll", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_regenerate_recovery_codes<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/recovery-codes", &self.address))
             .json(body)
             .send()
             .await
@@ -145,12 +482,164 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/verify-token", &self.address))
+            .post(format!("{}/verify-token", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
+
+    pub async fn post_admin_oauth_client<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/oauth/clients", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_oauth_clients(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/oauth/clients", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_admin_oauth_client(&self, client_id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!(
+                "{}/admin/oauth/clients/{}",
+                &self.address, client_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Redirects aren't followed, so tests can see where the route sends the user
+    pub async fn get_oauth_authorize(&self, query: &str) -> reqwest::Response {
+        reqwest::Client::builder()
+            .cookie_provider(self.cookie_jar.clone())
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+            .get(format!("{}/oauth/authorize?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_oauth_authorize<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/oauth/authorize", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_oauth_client(&self, client_id: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/oauth/clients/{}", &self.address, client_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_oauth_token<Body>(&self, form: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/oauth/token", &self.address))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Token request of a confidential client that authenticates with HTTP Basic
+    pub async fn post_oauth_token_with_basic_auth<Body>(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        form: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/oauth/token", &self.address))
+            .basic_auth(client_id, Some(client_secret))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Introspection request of a confidential client that authenticates with HTTP Basic
+    pub async fn post_introspect<Body>(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        form: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/introspect", &self.address))
+            .basic_auth(client_id, Some(client_secret))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Revocation request of a confidential client that authenticates with HTTP Basic
+    pub async fn post_revoke<Body>(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        form: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/revoke", &self.address))
+            .basic_auth(client_id, Some(client_secret))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_openid_configuration(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_userinfo(&self, access_token: Option<&str>) -> reqwest::Response {
+        let mut request = self.http_client.get(format!("{}/userinfo", &self.address));
+        if let Some(access_token) = access_token {
+            request = request.bearer_auth(access_token);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
 }
 
 impl Drop for TestApp {
@@ -165,6 +654,226 @@ pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
 
+// Pulls the token out of the link in an email sent by the signup route
+pub fn get_email_verification_token(request: &wiremock::Request) -> String {
+    get_link_token(request, "verifyEmailToken").expect("Email contains no verification link")
+}
+
+// Pulls the token passed as the `key` query parameter out of the link in an email
+pub fn get_link_token(request: &wiremock::Request, key: &str) -> Option<String> {
+    let body: serde_json::Value =
+        serde_json::from_slice(&request.body).expect("Failed to parse email request body");
+    let content = body["TextBody"]
+        .as_str()
+        .expect("Email request has no text body");
+    content
+        .split_whitespace()
+        .filter_map(|word| reqwest::Url::parse(word).ok())
+        .find_map(|url| {
+            url.query_pairs()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.into_owned())
+        })
+}
+
+// Reads the claims of a JWT without validating it
+pub fn get_token_claims(token: &str) -> Claims {
+    let payload = token.split('.').nth(1).expect("Token has no payload");
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload)
+        .expect("Failed to decode token payload");
+    serde_json::from_slice(&payload).expect("Failed to parse token claims")
+}
+
+// The password of the users signed up by the helpers below
+pub const TEST_PASSWORD: &str = "password123";
+
+pub fn login_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": TEST_PASSWORD,
+    })
+}
+
+// Signs up a user with a random email and logs them in with the app's client.
+// Returns their email.
+pub async fn signup_and_login(app: &TestApp) -> String {
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": TEST_PASSWORD,
+        "requires2FA": false,
+    });
+    app.post_signup_and_verify_email(&signup_body).await;
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    random_email
+}
+
+// Signs up a 2FA user. Returns their recovery codes.
+pub async fn signup_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": TEST_PASSWORD,
+        "requires2FA": true,
+    });
+    app.post_signup_and_verify_email(&signup_body)
+        .await
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes
+        .expect("No recovery codes in response body")
+}
+
+// Logs in a 2FA user. Returns the login attempt ID for the second factor.
+pub async fn login_with_2fa(app: &TestApp, email: &str) -> String {
+    let response = app.post_login(&login_body(email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
+
+// Answers the second factor of a login with `code`. Returns the status code.
+pub async fn verify_2fa(app: &TestApp, email: &str, login_attempt_id: &str, code: &str) -> u16 {
+    let body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    });
+    app.post_verify_2fa(&body).await.status().as_u16()
+}
+
+// Signs up a 2FA user and logs them in, accepting any emails sent from then on.
+// Returns their recovery codes.
+pub async fn signup_and_login_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let recovery_codes = signup_with_2fa(app, email).await;
+    mount_email_server(app, ..).await;
+
+    let login_attempt_id = login_with_2fa(app, email).await;
+    let two_fa_code = get_two_fa_code(app, email).await;
+    assert_eq!(
+        verify_2fa(app, email, &login_attempt_id, &two_fa_code).await,
+        200
+    );
+    recovery_codes
+}
+
+// The code of the pending 2FA check, as emailed to the user
+pub async fn get_two_fa_code(app: &TestApp, email: &str) -> String {
+    let email = Email::parse(Secret::new(email.to_owned())).unwrap();
+    let (_, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .expect("No pending 2FA check");
+    two_fa_code.as_ref().expose_secret().to_owned()
+}
+
+// The user as stored
+pub async fn get_user(app: &TestApp, email: &str) -> User {
+    let email = Email::parse(Secret::new(email.to_owned())).unwrap();
+    app.app_state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .unwrap()
+}
+
+// Signs up a user with a random email. Returns them as stored.
+pub async fn signup(app: &TestApp) -> User {
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": TEST_PASSWORD,
+        "requires2FA": false,
+    });
+    app.post_signup_and_verify_email(&signup_body).await;
+    get_user(app, &random_email).await
+}
+
+// Signs up an admin and logs them in with the app's client, so they can use the admin routes
+pub async fn login_admin(app: &TestApp) -> User {
+    let admin = signup(app).await;
+    app.app_state
+        .user_store
+        .write()
+        .await
+        .grant_role(&admin.id, Role::Admin)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_login(&login_body(admin.email.as_ref().expose_secret()))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    admin
+}
+
+// Registers a client for the logged in admin. Only the public client needs a redirect URI.
+pub async fn register_client(app: &TestApp, confidential: bool) -> RegisteredOAuthClientResponse {
+    let redirect_uris = match confidential {
+        true => vec![],
+        false => vec!["https://app.example.com/callback"],
+    };
+    let response = app
+        .post_admin_oauth_client(&serde_json::json!({
+            "name": "Backend job",
+            "redirectUris": redirect_uris,
+            "scopes": ["jobs:read", "jobs:write"],
+            "confidential": confidential,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    response
+        .json::<RegisteredOAuthClientResponse>()
+        .await
+        .expect("Could not deserialize response body to RegisteredOAuthClientResponse")
+}
+
+// Answers the emails sent by the app, expecting as many as `expected_emails` allows
+pub async fn mount_email_server(app: &TestApp, expected_emails: impl Into<Times>) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected_emails)
+        .mount(&app.email_server)
+        .await;
+}
+
+// The value of a cookie set by the response
+pub fn get_cookie_value(response: &reqwest::Response, name: &str) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == name)
+        .unwrap_or_else(|| panic!("Cookie with name '{}' not found", name))
+        .value()
+        .to_owned()
+}
+
+// The value of a cookie of the logged in user, from the app's cookie jar
+pub fn get_cookie(app: &TestApp, name: &str) -> String {
+    let cookies = app
+        .cookie_jar
+        .cookies(&Url::parse(&app.address).unwrap())
+        .expect("The cookie jar is empty");
+    cookies
+        .to_str()
+        .unwrap()
+        .split("; ")
+        .find_map(|cookie| cookie.strip_prefix(&format!("{}=", name)))
+        .unwrap_or_else(|| panic!("Cookie with name '{}' not in the cookie jar", name))
+        .to_owned()
+}
+
 async fn configure_postgresql() -> (PgPool, String) {
     let postgresql_conn_url = DATABASE_URL.expose_secret().to_owned();
 
@@ -0,0 +1,114 @@
+use auth_service::{
+    OAuthErrorResponse,
+    routes::{IntrospectionResponse, TokenResponse},
+    utils::JWT_COOKIE_NAME,
+};
+
+use crate::helpers::{TestApp, get_cookie, login_admin, register_client};
+
+async fn introspect(
+    app: &TestApp,
+    client_id: &str,
+    client_secret: &str,
+    token: &str,
+) -> IntrospectionResponse {
+    let response = app
+        .post_introspect(client_id, client_secret, &[("token", token)])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("cache-control").unwrap(), "no-store");
+    response.json::<IntrospectionResponse>().await.unwrap()
+}
+
+#[tokio::test]
+async fn should_introspect_client_token() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let client = register_client(&app, true).await;
+    let (client_id, client_secret) = (client.client.client_id, client.client_secret.unwrap());
+
+    let response = app
+        .post_oauth_token_with_basic_auth(
+            &client_id,
+            &client_secret,
+            &[("grant_type", "client_credentials")],
+        )
+        .await;
+    let token = response.json::<TokenResponse>().await.unwrap();
+
+    let introspection = introspect(&app, &client_id, &client_secret, &token.access_token).await;
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_deref(), Some(client_id.as_str()));
+    assert_eq!(introspection.client_id.as_deref(), Some(client_id.as_str()));
+    assert_eq!(introspection.scope.as_deref(), Some("jobs:read jobs:write"));
+    let (exp, iat) = (introspection.exp.unwrap(), introspection.iat.unwrap());
+    assert_eq!(exp - iat, token.expires_in as usize);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_introspect_user_token() {
+    let mut app = TestApp::new().await;
+    let user = login_admin(&app).await;
+    let client = register_client(&app, true).await;
+    let (client_id, client_secret) = (client.client.client_id, client.client_secret.unwrap());
+    let token = get_cookie(&app, JWT_COOKIE_NAME);
+
+    let introspection = introspect(&app, &client_id, &client_secret, &token).await;
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_deref(), Some(user.id.as_ref()));
+    assert!(introspection.scope.is_none());
+    assert!(introspection.client_id.is_none());
+
+    // Tokens banned at logout are no longer active
+    assert_eq!(app.post_logout().await.status().as_u16(), 200);
+    let introspection = introspect(&app, &client_id, &client_secret, &token).await;
+    assert_eq!(
+        introspection,
+        IntrospectionResponse {
+            active: false,
+            sub: None,
+            exp: None,
+            iat: None,
+            scope: None,
+            client_id: None,
+        }
+    );
+
+    let introspection = introspect(&app, &client_id, &client_secret, "not-a-token").await;
+    assert!(!introspection.active);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_client_authentication() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app, true).await.client.client_id;
+    let token = get_cookie(&app, JWT_COOKIE_NAME);
+
+    let response = app
+        .post_introspect(&client_id, "wrong-secret", &[("token", token.as_str())])
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_client");
+
+    // Public clients can't authenticate
+    let public_client = register_client(&app, false).await;
+    let response = app
+        .http_client
+        .post(format!("{}/introspect", &app.address))
+        .form(&[
+            ("token", token.as_str()),
+            ("client_id", &public_client.client.client_id),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
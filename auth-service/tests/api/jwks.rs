@@ -0,0 +1,47 @@
+use auth_service::utils::JWT_COOKIE_NAME;
+use jsonwebtoken::{DecodingKey, decode_header, jwk::JwkSet};
+
+use crate::helpers::{TestApp, get_random_email};
+
+#[tokio::test]
+async fn should_publish_the_key_tokens_are_signed_with() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let signup_request_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    app.post_signup_and_verify_email(&signup_request_body).await;
+
+    let login_request_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+    let login_response = app.post_login(&login_request_body).await;
+    assert_eq!(login_response.status().as_u16(), 200);
+    let token = login_response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let jwks = response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    // Tokens signed with a shared secret have no key id and nothing is published for them
+    match decode_header(&token).unwrap().kid {
+        Some(kid) => {
+            let jwk = jwks.find(&kid).expect("Signing key is not published");
+            assert!(DecodingKey::from_jwk(jwk).is_ok());
+        }
+        None => assert!(jwks.keys.is_empty()),
+    }
+    app.clean_up().await;
+}
@@ -1,11 +1,18 @@
-use auth_service::{domain::Email, routes::TwoFactorAuthResponse, utils::JWT_COOKIE_NAME};
+use auth_service::{
+    ErrorResponse,
+    domain::Email,
+    routes::TwoFactorAuthResponse,
+    utils::{
+        JWT_AUDIENCE, JWT_COOKIE_NAME, JWT_ISSUER, LOGIN_LOCKOUT_DURATION, LOGIN_LOCKOUT_THRESHOLD,
+    },
+};
 use secrecy::{ExposeSecret, Secret};
 use wiremock::{
     Mock, ResponseTemplate,
     matchers::{method, path},
 };
 
-use crate::helpers::{TestApp, get_random_email};
+use crate::helpers::{TestApp, get_random_email, get_token_claims};
 
 #[tokio::test]
 async fn should_return_401_if_old_code() {
@@ -18,7 +25,7 @@ async fn should_return_401_if_old_code() {
         "requires2FA": true
     });
 
-    let response = app.post_signup(&signup_body).await;
+    let response = app.post_signup_and_verify_email(&signup_body).await;
 
     assert_eq!(response.status().as_u16(), 201);
 
@@ -88,7 +95,7 @@ async fn should_return_422_if_malformed_credentials() {
     let input_missing_password = serde_json::json!( {
         "email": "spammer@some_domain.com",
     });
-    let test_cases = vec![input_missing_email, input_missing_password];
+    let test_cases = [input_missing_email, input_missing_password];
 
     for test_case in test_cases.iter() {
         let response = app.post_login(test_case).await;
@@ -115,7 +122,7 @@ async fn should_return_400_if_invalid_input() {
         "email": "some@mydomain.com",
         "password": "0",
     });
-    let test_cases = vec![invalid_email, invalid_password];
+    let test_cases = [invalid_email, invalid_password];
     for test_case in test_cases.iter() {
         let response = app.post_login(&test_case).await;
         assert_eq!(
@@ -137,7 +144,7 @@ async fn should_return_401_if_incorrect_credentials() {
         "email": "coder@tester.com",
         "password": "password123",
     });
-    let response = app.post_signup(&initial_credentials).await;
+    let response = app.post_signup_and_verify_email(&initial_credentials).await;
     assert_eq!(response.status().as_u16(), 201);
 
     Mock::given(path("/email"))
@@ -174,7 +181,7 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
         "requires2FA": false
     });
 
-    let response = app.post_signup(&signup_body).await;
+    let response = app.post_signup_and_verify_email(&signup_body).await;
 
     assert_eq!(response.status().as_u16(), 201);
 
@@ -193,6 +200,23 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
+
+    // Tokens name the user by their id rather than their email address
+    let email = Email::parse(Secret::new(random_email)).unwrap();
+    let user = app
+        .app_state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .unwrap();
+    let claims = get_token_claims(auth_cookie.value());
+    assert_eq!(claims.sub, user.id.as_ref());
+    assert_eq!(claims.iss, *JWT_ISSUER);
+    assert_eq!(claims.aud, *JWT_AUDIENCE);
+    assert_eq!(claims.nbf, claims.iat);
+    assert!(!claims.jti.is_empty());
     app.clean_up().await;
 }
 
@@ -208,7 +232,7 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
         "requires2FA": true
     });
 
-    let response = app.post_signup(&signup_body).await;
+    let response = app.post_signup_and_verify_email(&signup_body).await;
 
     assert_eq!(response.status().as_u16(), 201);
 
@@ -246,3 +270,143 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     assert!(retrieved_value.is_ok());
     app.clean_up().await;
 }
+
+async fn signup_with_random_email(app: &TestApp) -> String {
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    let response = app.post_signup_and_verify_email(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+    random_email
+}
+
+async fn add_failed_logins(app: &TestApp, email: &str, count: u32) {
+    let email = Email::parse(Secret::new(email.to_owned())).unwrap();
+    for _ in 0..count {
+        app.failed_login_store
+            .write()
+            .await
+            .add_failed_login(&email)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn should_return_429_if_retried_too_soon_after_failed_logins() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_with_random_email(&app).await;
+
+    let incorrect_credentials = serde_json::json!({
+        "email": random_email,
+        "password": "wrong-password",
+    });
+    for _ in 0..3 {
+        let response = app.post_login(&incorrect_credentials).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // Even the right password is refused until the backoff is over
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many failed login attempts, try again later".to_owned()
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_account_locked() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_with_random_email(&app).await;
+    add_failed_logins(&app, &random_email, *LOGIN_LOCKOUT_THRESHOLD).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_lock_account_and_notify_owner_after_too_many_failed_logins() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_with_random_email(&app).await;
+    add_failed_logins(&app, &random_email, *LOGIN_LOCKOUT_THRESHOLD - 1).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Wait out the backoff of the failed logins so far
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let failed_logins = app
+        .failed_login_store
+        .read()
+        .await
+        .get_failed_logins(&email)
+        .await
+        .unwrap();
+    let lockout_seconds = LOGIN_LOCKOUT_DURATION.as_secs() as i64;
+    if let Some(retry_at) = failed_logins.retry_at(*LOGIN_LOCKOUT_THRESHOLD, lockout_seconds) {
+        let wait = (retry_at - chrono::Utc::now().timestamp() + 1).max(0) as u64;
+        tokio::time::sleep(std::time::Duration::from_secs(wait)).await;
+    }
+
+    let incorrect_credentials = serde_json::json!({
+        "email": random_email,
+        "password": "wrong-password",
+    });
+    let response = app.post_login(&incorrect_credentials).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reset_failed_logins_after_successful_login() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_with_random_email(&app).await;
+    add_failed_logins(&app, &random_email, 2).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email = Email::parse(Secret::new(random_email)).unwrap();
+    let failed_logins = app
+        .failed_login_store
+        .read()
+        .await
+        .get_failed_logins(&email)
+        .await
+        .unwrap();
+    assert_eq!(failed_logins.count, 0);
+    app.clean_up().await;
+}
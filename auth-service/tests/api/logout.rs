@@ -1,9 +1,8 @@
 use auth_service::utils::JWT_COOKIE_NAME;
 use reqwest::Url;
-use secrecy::Secret;
 
 use crate::helpers::TestApp;
-use crate::helpers::get_random_email;
+use crate::helpers::{get_random_email, get_token_claims};
 
 #[tokio::test]
 async fn should_return_400_if_jwt_cookie_missing() {
@@ -51,7 +50,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
         "password": "longenough",
         "requires2FA": false,
     });
-    app.post_signup(&signup_request_body).await;
+    app.post_signup_and_verify_email(&signup_request_body).await;
 
     let login_request_body = serde_json::json!({
         "email": random_email,
@@ -61,7 +60,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
     let token = login_response
         .cookies()
         .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
-        .expect(format!("Cookie with name '{}' not found", JWT_COOKIE_NAME).as_str());
+        .unwrap_or_else(|| panic!("Cookie with name '{}' not found", JWT_COOKIE_NAME));
     let token = token.value();
 
     app.cookie_jar.add_cookie_str(
@@ -78,7 +77,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
         .banned_token_store
         .read()
         .await
-        .contains_token(&Secret::new(token.to_owned()))
+        .contains_token(&get_token_claims(token).jti)
         .await
         .expect("Could not check whether token is banned.");
     assert!(contains_token);
@@ -95,7 +94,7 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
         "password": "longenough",
         "requires2FA": false,
     });
-    app.post_signup(&signup_request_body).await;
+    app.post_signup_and_verify_email(&signup_request_body).await;
 
     let login_request_body = serde_json::json!({
         "email": random_email,
@@ -105,7 +104,7 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
     let token = login_response
         .cookies()
         .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
-        .expect(format!("Cookie with name '{}' not found", JWT_COOKIE_NAME).as_str());
+        .unwrap_or_else(|| panic!("Cookie with name '{}' not found", JWT_COOKIE_NAME));
     let token = token.value();
 
     app.cookie_jar.add_cookie_str(
@@ -1,7 +1,25 @@
+mod account;
+mod admin;
+mod change_email;
+mod change_password;
+mod client_credentials;
 mod helpers;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod oauth;
+mod oidc;
+mod password_reset;
+mod rate_limit;
+mod recovery_codes;
+mod refresh;
+mod revoke;
 mod root;
+mod sessions;
 mod signup;
+mod totp;
+mod two_fa;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
@@ -0,0 +1,365 @@
+use auth_service::{
+    ErrorResponse, OAuthErrorResponse,
+    domain::{Email, Role, User},
+    routes::{OAuthClientInfoResponse, OAuthClientResponse, RedirectResponse, TokenResponse},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+use crate::helpers::{TestApp, get_random_email, get_token_claims};
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+// Signs up an admin, logs them in with the app's client and registers a client with them.
+// The admin is the user the client is then authorized by.
+async fn setup_client(app: &TestApp) -> (User, OAuthClientResponse) {
+    let random_email = get_random_email();
+    let signup_request_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false,
+    });
+    app.post_signup_and_verify_email(&signup_request_body).await;
+    let email = Email::parse(Secret::new(random_email)).unwrap();
+    let user = app
+        .app_state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .unwrap();
+    app.app_state
+        .user_store
+        .write()
+        .await
+        .grant_role(&user.id, Role::Admin)
+        .await
+        .unwrap();
+    let login_request_body = serde_json::json!({
+        "email": user.email.as_ref().expose_secret(),
+        "password": "password123",
+    });
+    assert_eq!(
+        app.post_login(&login_request_body).await.status().as_u16(),
+        200
+    );
+
+    let response = app
+        .post_admin_oauth_client(&serde_json::json!({
+            "name": "Example app",
+            "redirectUris": [REDIRECT_URI],
+            "scopes": ["profile", "email"],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let client = response
+        .json::<OAuthClientResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthClientResponse");
+    (user, client)
+}
+
+fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier))
+}
+
+fn authorize_params(client: &OAuthClientResponse) -> Vec<(&'static str, String)> {
+    vec![
+        ("response_type", "code".to_owned()),
+        ("client_id", client.client_id.clone()),
+        ("redirect_uri", REDIRECT_URI.to_owned()),
+        ("scope", "profile".to_owned()),
+        ("state", "xyz".to_owned()),
+        ("code_challenge", code_challenge(CODE_VERIFIER)),
+        ("code_challenge_method", "S256".to_owned()),
+    ]
+}
+
+fn authorize_query(params: &[(&str, String)]) -> String {
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish()
+}
+
+fn location(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get("location")
+        .expect("Response has no location")
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+fn query_param(uri: &str, key: &str) -> Option<String> {
+    Url::parse(uri)
+        .unwrap()
+        .query_pairs()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.into_owned())
+}
+
+// Approves the request on the consent page, and returns the code the client gets
+async fn approve(app: &TestApp, params: &[(&str, String)]) -> String {
+    let mut body: serde_json::Map<String, serde_json::Value> = params
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone().into()))
+        .collect();
+    body.insert("approved".to_owned(), true.into());
+    let response = app.post_oauth_authorize(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let redirect_uri = response
+        .json::<RedirectResponse>()
+        .await
+        .unwrap()
+        .redirect_uri;
+    assert!(redirect_uri.starts_with(REDIRECT_URI));
+    assert_eq!(query_param(&redirect_uri, "state").as_deref(), Some("xyz"));
+    query_param(&redirect_uri, "code").expect("Redirect URI has no code")
+}
+
+fn token_form(client: &OAuthClientResponse, code: &str, verifier: &str) -> Vec<(String, String)> {
+    vec![
+        ("grant_type".to_owned(), "authorization_code".to_owned()),
+        ("code".to_owned(), code.to_owned()),
+        ("redirect_uri".to_owned(), REDIRECT_URI.to_owned()),
+        ("client_id".to_owned(), client.client_id.clone()),
+        ("code_verifier".to_owned(), verifier.to_owned()),
+    ]
+}
+
+// Token request of a client that leaves out the redirect URI
+fn form_without_redirect_uri(client: &OAuthClientResponse, code: &str) -> Vec<(String, String)> {
+    let mut form = token_form(client, code, CODE_VERIFIER);
+    form.retain(|(name, _)| name != "redirect_uri");
+    form
+}
+
+#[tokio::test]
+async fn should_issue_token_for_authorization_code() {
+    let mut app = TestApp::new().await;
+    let (user, client) = setup_client(&app).await;
+    let params = authorize_params(&client);
+
+    // Users are asked for their consent the first time
+    let response = app.get_oauth_authorize(&authorize_query(&params)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(location(&response).starts_with("/?consent="));
+
+    let response = app.get_oauth_client(&client.client_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let info = response.json::<OAuthClientInfoResponse>().await.unwrap();
+    assert_eq!(info.name, "Example app");
+
+    let code = approve(&app, &params).await;
+    let response = app
+        .post_oauth_token(&token_form(&client, &code, CODE_VERIFIER))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("cache-control").unwrap(), "no-store");
+    let token = response.json::<TokenResponse>().await.unwrap();
+    assert_eq!(token.token_type, "Bearer");
+    assert_eq!(token.scope, "profile");
+
+    let claims = get_token_claims(&token.access_token);
+    assert_eq!(claims.sub, user.id.as_ref());
+    assert_eq!(claims.client_id.as_deref(), Some(client.client_id.as_str()));
+    assert_eq!(claims.scope.as_deref(), Some("profile"));
+    assert!(claims.roles.is_empty());
+
+    // Access tokens are limited to their scopes, so they don't stand in for the user's login
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token.access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Codes can only be exchanged once
+    let response = app
+        .post_oauth_token(&token_form(&client, &code, CODE_VERIFIER))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_grant");
+
+    // Once the user consented, codes are issued right away
+    let response = app.get_oauth_authorize(&authorize_query(&params)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let redirect_uri = location(&response);
+    assert!(redirect_uri.starts_with(REDIRECT_URI));
+    assert!(query_param(&redirect_uri, "code").is_some());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_wrong_code_verifier() {
+    let mut app = TestApp::new().await;
+    let (_, client) = setup_client(&app).await;
+    let code = approve(&app, &authorize_params(&client)).await;
+
+    let other_verifier = "a".repeat(43);
+    let response = app
+        .post_oauth_token(&token_form(&client, &code, &other_verifier))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_grant");
+
+    // The failed attempt used the code up
+    let response = app
+        .post_oauth_token(&token_form(&client, &code, CODE_VERIFIER))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_oauth_token(&[("grant_type", "password")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "unsupported_grant_type");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_redirect_uri_named_in_authorization_request() {
+    let mut app = TestApp::new().await;
+    let (_, client) = setup_client(&app).await;
+    let code = approve(&app, &authorize_params(&client)).await;
+
+    let response = app
+        .post_oauth_token(&form_without_redirect_uri(&client, &code))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_grant");
+
+    // Clients that left it to their only registered URI don't have to name it
+    let mut params = authorize_params(&client);
+    params.retain(|(name, _)| *name != "redirect_uri");
+    let code = approve(&app, &params).await;
+    let response = app
+        .post_oauth_token(&form_without_redirect_uri(&client, &code))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_send_users_without_login_to_login_page() {
+    let mut app = TestApp::new().await;
+    let (_, client) = setup_client(&app).await;
+    app.post_logout().await;
+
+    let query = authorize_query(&authorize_params(&client));
+    let response = app.get_oauth_authorize(&query).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let login_page = format!("http://localhost{}", location(&response));
+    assert_eq!(query_param(&login_page, "authorize"), Some(query));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_redirect_errors_to_client() {
+    let mut app = TestApp::new().await;
+    let (_, client) = setup_client(&app).await;
+
+    let test_cases = [
+        ("code_challenge_method", "plain", "invalid_request"),
+        ("code_challenge", "", "invalid_request"),
+        ("response_type", "token", "unsupported_response_type"),
+        ("scope", "admin", "invalid_scope"),
+    ];
+    for (name, value, expected_error) in test_cases {
+        let mut params = authorize_params(&client);
+        for param in params.iter_mut().filter(|(param, _)| *param == name) {
+            param.1 = value.to_owned();
+        }
+        let response = app.get_oauth_authorize(&authorize_query(&params)).await;
+        assert_eq!(response.status().as_u16(), 303);
+        let redirect_uri = location(&response);
+        assert!(redirect_uri.starts_with(REDIRECT_URI));
+        assert_eq!(
+            query_param(&redirect_uri, "error").as_deref(),
+            Some(expected_error),
+            "Failed for {}={}",
+            name,
+            value
+        );
+        assert_eq!(query_param(&redirect_uri, "state").as_deref(), Some("xyz"));
+    }
+
+    // Denying the request is reported to the client as well
+    let mut body = serde_json::Map::new();
+    for (name, value) in authorize_params(&client) {
+        body.insert(name.to_owned(), value.into());
+    }
+    body.insert("approved".to_owned(), false.into());
+    let response = app.post_oauth_authorize(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let redirect_uri = response
+        .json::<RedirectResponse>()
+        .await
+        .unwrap()
+        .redirect_uri;
+    assert_eq!(
+        query_param(&redirect_uri, "error").as_deref(),
+        Some("access_denied")
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_redirect_to_unregistered_uri() {
+    let mut app = TestApp::new().await;
+    let (_, client) = setup_client(&app).await;
+
+    let mut params = authorize_params(&client);
+    params[2].1 = "https://evil.example.com/callback".to_owned();
+    let response = app.get_oauth_authorize(&authorize_query(&params)).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Invalid redirect URI"
+    );
+
+    let mut params = authorize_params(&client);
+    params[1].1 = uuid::Uuid::new_v4().to_string();
+    let response = app.get_oauth_authorize(&authorize_query(&params)).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_manage_clients() {
+    let mut app = TestApp::new().await;
+    let (_, client) = setup_client(&app).await;
+
+    let response = app
+        .post_admin_oauth_client(&serde_json::json!({
+            "name": "Insecure app",
+            "redirectUris": ["http://app.example.com/callback"],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_admin_oauth_clients().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let clients = response.json::<Vec<OAuthClientResponse>>().await.unwrap();
+    assert_eq!(clients, vec![client.clone()]);
+
+    let response = app.delete_admin_oauth_client(&client.client_id).await;
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app.delete_admin_oauth_client(&client.client_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+    let response = app.get_oauth_client(&client.client_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
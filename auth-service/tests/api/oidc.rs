@@ -0,0 +1,281 @@
+use auth_service::{
+    domain::{ClientId, OAuthClient, Session, SessionId},
+    routes::{OpenIdConfiguration, RedirectResponse, TokenResponse, UserInfoResponse},
+    utils::{IdTokenClaims, JWT_COOKIE_NAME, JWT_ISSUER, id_tokens_supported},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+
+use crate::helpers::{
+    TestApp, get_cookie, get_random_email, get_token_claims, signup_and_login,
+    signup_and_login_with_2fa,
+};
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+const NONCE: &str = "n-0S6_WzA2Mj";
+
+async fn add_client(app: &TestApp) -> OAuthClient {
+    let client = OAuthClient {
+        id: ClientId::default(),
+        name: "Example app".to_owned(),
+        redirect_uris: vec![REDIRECT_URI.to_owned()],
+        scopes: vec!["openid".to_owned(), "email".to_owned()],
+        secret_hash: None,
+    };
+    app.app_state
+        .oauth_client_store
+        .write()
+        .await
+        .add_client(client.clone())
+        .await
+        .unwrap();
+    client
+}
+
+// Runs the authorization code flow for the logged in user, approving the consent page
+async fn get_tokens(app: &TestApp, client: &OAuthClient, scope: &str) -> TokenResponse {
+    let response = app
+        .post_oauth_authorize(&serde_json::json!({
+            "response_type": "code",
+            "client_id": client.id.as_ref(),
+            "scope": scope,
+            "state": "xyz",
+            "nonce": NONCE,
+            "code_challenge": URL_SAFE_NO_PAD.encode(Sha256::digest(CODE_VERIFIER)),
+            "code_challenge_method": "S256",
+            "approved": true,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let redirect_uri = response
+        .json::<RedirectResponse>()
+        .await
+        .unwrap()
+        .redirect_uri;
+    let code = Url::parse(&redirect_uri)
+        .unwrap()
+        .query_pairs()
+        .find(|(name, _)| name == "code")
+        .map(|(_, value)| value.into_owned())
+        .expect("Redirect URI has no code");
+
+    let response = app
+        .post_oauth_token(&[
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("client_id", client.id.as_ref()),
+            ("code_verifier", CODE_VERIFIER),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json::<TokenResponse>().await.unwrap()
+}
+
+// Reads the claims of an ID token without validating it
+fn get_id_token_claims(token: &str) -> IdTokenClaims {
+    let payload = token.split('.').nth(1).expect("Token has no payload");
+    let payload = URL_SAFE_NO_PAD.decode(payload).unwrap();
+    serde_json::from_slice(&payload).expect("Failed to parse ID token claims")
+}
+
+#[tokio::test]
+async fn should_return_openid_configuration() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_openid_configuration().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let configuration = response.json::<OpenIdConfiguration>().await.unwrap();
+    assert_eq!(configuration.issuer, *JWT_ISSUER);
+    assert!(
+        configuration
+            .authorization_endpoint
+            .ends_with("/oauth/authorize")
+    );
+    assert!(configuration.token_endpoint.ends_with("/oauth/token"));
+    assert!(configuration.userinfo_endpoint.ends_with("/userinfo"));
+    assert!(configuration.jwks_uri.ends_with("/.well-known/jwks.json"));
+    assert_eq!(configuration.code_challenge_methods_supported, vec!["S256"]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_issue_id_token_for_openid_scope() {
+    if !id_tokens_supported() {
+        return;
+    }
+    let mut app = TestApp::new().await;
+    let client = add_client(&app).await;
+    let email = signup_and_login(&app).await;
+
+    let tokens = get_tokens(&app, &client, "openid email").await;
+    let id_token = tokens.id_token.expect("No ID token issued");
+    let claims = get_id_token_claims(&id_token);
+    let access_token_claims = get_token_claims(&tokens.access_token);
+    assert_eq!(claims.iss, *JWT_ISSUER);
+    assert_eq!(claims.sub, access_token_claims.sub);
+    assert_eq!(claims.aud, client.id.as_ref());
+    assert_eq!(claims.nonce.as_deref(), Some(NONCE));
+    assert_eq!(claims.amr, vec!["pwd"]);
+    assert_eq!(claims.sid, access_token_claims.sid);
+    assert!(
+        claims
+            .auth_time
+            .is_some_and(|auth_time| auth_time <= claims.iat as i64)
+    );
+
+    // ID tokens are meant for the client, not for the service's own routes
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": id_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_userinfo(Some(&tokens.access_token)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let userinfo = response.json::<UserInfoResponse>().await.unwrap();
+    assert_eq!(userinfo.sub, claims.sub);
+    assert_eq!(userinfo.email, Some(email));
+    assert_eq!(userinfo.email_verified, Some(true));
+
+    // Without the `email` scope the address is left out
+    let tokens = get_tokens(&app, &client, "openid").await;
+    let response = app.get_userinfo(Some(&tokens.access_token)).await;
+    let userinfo = response.json::<UserInfoResponse>().await.unwrap();
+    assert_eq!(userinfo.email, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reflect_2fa_in_amr() {
+    if !id_tokens_supported() {
+        return;
+    }
+    let mut app = TestApp::new().await;
+    let client = add_client(&app).await;
+    signup_and_login_with_2fa(&app, &get_random_email()).await;
+
+    let tokens = get_tokens(&app, &client, "openid").await;
+    let claims = get_id_token_claims(&tokens.id_token.expect("No ID token issued"));
+    assert_eq!(claims.amr, vec!["pwd", "otp", "mfa"]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_leave_out_auth_time_if_unknown() {
+    if !id_tokens_supported() {
+        return;
+    }
+    let mut app = TestApp::new().await;
+    let client = add_client(&app).await;
+    signup_and_login(&app).await;
+
+    // Sessions recorded before their login time was don't know it
+    let session_id =
+        SessionId::parse(get_token_claims(&get_cookie(&app, JWT_COOKIE_NAME)).sid).unwrap();
+    let mut session_store = app.app_state.session_store.write().await;
+    let session = session_store.get_session(&session_id).await.unwrap();
+    session_store.remove_session(&session_id).await.unwrap();
+    session_store
+        .add_session(Session {
+            authenticated_at: None,
+            ..session
+        })
+        .await
+        .unwrap();
+    drop(session_store);
+
+    let tokens = get_tokens(&app, &client, "openid").await;
+    let claims = get_id_token_claims(&tokens.id_token.expect("No ID token issued"));
+    assert_eq!(claims.auth_time, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_refuse_openid_scope_without_asymmetric_key() {
+    if id_tokens_supported() {
+        return;
+    }
+    let mut app = TestApp::new().await;
+    let client = add_client(&app).await;
+    signup_and_login(&app).await;
+
+    let response = app
+        .post_oauth_authorize(&serde_json::json!({
+            "response_type": "code",
+            "client_id": client.id.as_ref(),
+            "scope": "openid",
+            "state": "xyz",
+            "code_challenge": URL_SAFE_NO_PAD.encode(Sha256::digest(CODE_VERIFIER)),
+            "code_challenge_method": "S256",
+            "approved": true,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let redirect_uri = response
+        .json::<RedirectResponse>()
+        .await
+        .unwrap()
+        .redirect_uri;
+    let error = Url::parse(&redirect_uri)
+        .unwrap()
+        .query_pairs()
+        .find(|(name, _)| name == "error")
+        .map(|(_, value)| value.into_owned());
+    assert_eq!(error.as_deref(), Some("invalid_scope"));
+
+    // Nor is it offered
+    let response = app.get_openid_configuration().await;
+    let configuration = response.json::<OpenIdConfiguration>().await.unwrap();
+    assert!(
+        !configuration
+            .scopes_supported
+            .contains(&"openid".to_owned())
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_issue_id_token_without_openid_scope() {
+    let mut app = TestApp::new().await;
+    let client = add_client(&app).await;
+    signup_and_login(&app).await;
+
+    let tokens = get_tokens(&app, &client, "email").await;
+    assert!(tokens.id_token.is_none());
+
+    // The token can't get user info either
+    let response = app.get_userinfo(Some(&tokens.access_token)).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(
+        response.headers()["www-authenticate"]
+            .to_str()
+            .unwrap()
+            .contains("insufficient_scope")
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_from_userinfo_without_access_token() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let response = app.get_userinfo(None).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+
+    // The user's own auth token is no access token of a client
+    let response = app
+        .get_userinfo(Some(&get_cookie(&app, JWT_COOKIE_NAME)))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,182 @@
+use auth_service::{domain::Email, routes::PasswordResetResponse, utils::JWT_COOKIE_NAME};
+use secrecy::{ExposeSecret, Secret};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{TestApp, get_cookie, get_random_email, login_body, signup_and_login};
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+    let response = app
+        .post_password_reset_request(&serde_json::json!({}))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let test_cases = [
+        serde_json::json!({ "token": "foo", "newPassword": "password456" }),
+        serde_json::json!({ "email": "a@b.com", "newPassword": "password456" }),
+        serde_json::json!({ "email": "a@b.com", "token": "foo" }),
+    ];
+    for test_case in test_cases.iter() {
+        let response = app.post_password_reset_confirm(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": "invalidemail" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let test_cases = [
+        serde_json::json!({
+            "email": "invalidemail",
+            "token": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "newPassword": "password456"
+        }),
+        serde_json::json!({
+            "email": "a@b.com",
+            "token": "tooshort",
+            "newPassword": "password456"
+        }),
+        serde_json::json!({
+            "email": "a@b.com",
+            "token": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "newPassword": "short"
+        }),
+    ];
+    for test_case in test_cases.iter() {
+        let response = app.post_password_reset_confirm(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_without_sending_email_if_user_does_not_exist() {
+    let mut app = TestApp::new().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_token() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_and_login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "email": random_email,
+            "token": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "newPassword": "password456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reset_password_and_revoke_existing_tokens() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_and_login(&app).await;
+    let old_token = get_cookie(&app, JWT_COOKIE_NAME);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let token = app
+        .password_reset_token_store
+        .read()
+        .await
+        .get_token(&email)
+        .await
+        .unwrap();
+
+    let confirm_body = serde_json::json!({
+        "email": random_email,
+        "token": token.as_ref().expose_secret(),
+        "newPassword": "password456"
+    });
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<PasswordResetResponse>()
+            .await
+            .expect("Could not deserialize response body to PasswordResetResponse")
+            .message,
+        "Password has been reset.".to_owned()
+    );
+
+    // The link can only be used once
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Sessions started with the old password are gone
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
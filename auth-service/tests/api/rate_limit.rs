@@ -0,0 +1,81 @@
+use auth_service::ErrorResponse;
+use reqwest::header::RETRY_AFTER;
+
+use crate::helpers::TestApp;
+
+// Budget of the login route per client
+const LOGIN_REQUESTS: usize = 10;
+
+async fn post_login_forwarded_for(app: &TestApp, forwarded_for: &str) -> reqwest::Response {
+    app.http_client
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", forwarded_for)
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn should_return_429_with_retry_after_once_budget_is_used_up() {
+    let mut app = TestApp::new().await;
+
+    // Requests count against the budget whether or not they succeed
+    for _ in 0..LOGIN_REQUESTS {
+        let response = app.post_login(&serde_json::json!({})).await;
+        assert_eq!(response.status().as_u16(), 422);
+    }
+
+    let response = app.post_login(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("No Retry-After header found")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=6).contains(&retry_after));
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many requests".to_owned()
+    );
+
+    // Other routes have budgets of their own
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_ignore_forwarded_for_header_of_untrusted_clients() {
+    let mut app = TestApp::new().await;
+
+    for i in 0..LOGIN_REQUESTS {
+        let response = post_login_forwarded_for(&app, &format!("1.1.1.{}", i)).await;
+        assert_eq!(response.status().as_u16(), 422);
+    }
+
+    let response = post_login_forwarded_for(&app, "2.2.2.2").await;
+    assert_eq!(response.status().as_u16(), 429);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_limit_oauth_token_route_like_login() {
+    let mut app = TestApp::new().await;
+
+    for _ in 0..LOGIN_REQUESTS {
+        let response = app.post_oauth_token(&[("grant_type", "password")]).await;
+        assert_eq!(response.status().as_u16(), 400);
+    }
+
+    let response = app.post_oauth_token(&[("grant_type", "password")]).await;
+    assert_eq!(response.status().as_u16(), 429);
+    app.clean_up().await;
+}
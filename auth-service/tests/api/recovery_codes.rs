@@ -0,0 +1,119 @@
+use auth_service::{domain::RECOVERY_CODE_COUNT, routes::RecoveryCodesResponse};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{body_partial_json, method, path},
+};
+
+use crate::helpers::{
+    TestApp, get_random_email, login_with_2fa, mount_email_server, signup_and_login,
+    signup_with_2fa, verify_2fa,
+};
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+    let response = app
+        .post_regenerate_recovery_codes(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_2fa_not_enabled() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let response = app
+        .post_regenerate_recovery_codes(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_accept_recovery_code_only_once() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    // One 2FA code per login
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .and(body_partial_json(
+            serde_json::json!({ "Subject": "2FA code" }),
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    // Using a recovery code is reported to the user
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .and(body_partial_json(
+            serde_json::json!({ "Subject": "Recovery code used" }),
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_attempt_id = login_with_2fa(&app, &random_email).await;
+    // Recovery codes are case insensitive
+    let code = recovery_codes[0].to_uppercase();
+    assert_eq!(
+        verify_2fa(&app, &random_email, &login_attempt_id, &code).await,
+        200
+    );
+
+    let login_attempt_id = login_with_2fa(&app, &random_email).await;
+    assert_eq!(
+        verify_2fa(&app, &random_email, &login_attempt_id, &code).await,
+        401
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_invalidate_old_codes_on_regenerate() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    let old_codes = signup_with_2fa(&app, &random_email).await;
+
+    mount_email_server(&app, ..).await;
+
+    let login_attempt_id = login_with_2fa(&app, &random_email).await;
+    assert_eq!(
+        verify_2fa(&app, &random_email, &login_attempt_id, &old_codes[0]).await,
+        200
+    );
+
+    // The session alone isn't enough to get new codes
+    let response = app
+        .post_regenerate_recovery_codes(&serde_json::json!({ "password": "wrongpassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_regenerate_recovery_codes(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+    assert_eq!(new_codes.len(), RECOVERY_CODE_COUNT);
+
+    let login_attempt_id = login_with_2fa(&app, &random_email).await;
+    assert_eq!(
+        verify_2fa(&app, &random_email, &login_attempt_id, &old_codes[1]).await,
+        401
+    );
+    assert_eq!(
+        verify_2fa(&app, &random_email, &login_attempt_id, &new_codes[0]).await,
+        200
+    );
+    app.clean_up().await;
+}
@@ -0,0 +1,174 @@
+use auth_service::{
+    domain::{RefreshToken, RefreshTokenStoreError, SessionId},
+    utils::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+use reqwest::Url;
+use secrecy::ExposeSecret;
+
+use crate::helpers::{TestApp, get_cookie, get_cookie_value, get_token_claims, signup_and_login};
+
+fn set_refresh_cookie(app: &TestApp, value: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            REFRESH_TOKEN_COOKIE_NAME, value
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+}
+
+#[tokio::test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let mut app = TestApp::new().await;
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_refresh_token() {
+    let mut app = TestApp::new().await;
+    set_refresh_cookie(&app, "invalid");
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    set_refresh_cookie(&app, RefreshToken::default().as_ref().expose_secret());
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_rotate_refresh_token() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let old_refresh_token = get_cookie(&app, REFRESH_TOKEN_COOKIE_NAME);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_token = get_cookie_value(&response, JWT_COOKIE_NAME);
+    let new_refresh_token = get_cookie_value(&response, REFRESH_TOKEN_COOKIE_NAME);
+    assert!(!auth_token.is_empty());
+    assert_ne!(old_refresh_token, new_refresh_token);
+
+    let verify_token_body = serde_json::json!({ "token": auth_token });
+    let response = app.post_verify_token(&verify_token_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The rotated cookie replaced the old one in the jar, so refreshing keeps working
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_token_family_if_rotated_token_reused() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let old_refresh_token = get_cookie(&app, REFRESH_TOKEN_COOKIE_NAME);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_refresh_token = get_cookie_value(&response, REFRESH_TOKEN_COOKIE_NAME);
+
+    // Replay the token that was already rotated
+    set_refresh_cookie(&app, &old_refresh_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The legitimate successor belongs to the revoked family and is rejected as well
+    let new_refresh_token = RefreshToken::parse(new_refresh_token).unwrap();
+    let result = app
+        .refresh_token_store
+        .write()
+        .await
+        .rotate_token(&new_refresh_token, RefreshToken::default())
+        .await;
+    assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+
+    set_refresh_cookie(&app, new_refresh_token.as_ref().expose_secret());
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_token_family_if_session_ended() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let refresh_token = get_cookie(&app, REFRESH_TOKEN_COOKIE_NAME);
+    let session_id =
+        SessionId::parse(get_token_claims(&get_cookie(&app, JWT_COOKIE_NAME)).sid).unwrap();
+    app.app_state
+        .session_store
+        .write()
+        .await
+        .remove_session(&session_id)
+        .await
+        .unwrap();
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+    // The cookies are cleared
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    // The family is gone, rather than left to be replayed
+    let result = app
+        .refresh_token_store
+        .write()
+        .await
+        .rotate_token(
+            &RefreshToken::parse(refresh_token).unwrap(),
+            RefreshToken::default(),
+        )
+        .await;
+    assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_refresh_token_on_logout() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let auth_token = get_cookie(&app, JWT_COOKIE_NAME);
+    let refresh_token = get_cookie(&app, REFRESH_TOKEN_COOKIE_NAME);
+
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME, auth_token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    set_refresh_cookie(&app, &refresh_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_earlier_auth_tokens_on_logout_after_refresh() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let old_auth_token = get_cookie(&app, JWT_COOKIE_NAME);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_auth_token = get_cookie_value(&response, JWT_COOKIE_NAME);
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Both the token the refresh replaced and its successor end with the session
+    for token in [old_auth_token, new_auth_token] {
+        let verify_token_body = serde_json::json!({ "token": token });
+        let response = app.post_verify_token(&verify_token_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    app.clean_up().await;
+}
@@ -0,0 +1,155 @@
+use auth_service::{
+    OAuthErrorResponse,
+    routes::{IntrospectionResponse, TokenResponse},
+    utils::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+
+use crate::helpers::{TestApp, get_cookie, login_admin, register_client};
+
+async fn client_token(app: &TestApp, client_id: &str, client_secret: &str) -> String {
+    let response = app
+        .post_oauth_token_with_basic_auth(
+            client_id,
+            client_secret,
+            &[("grant_type", "client_credentials")],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json::<TokenResponse>().await.unwrap().access_token
+}
+
+// Whether the client's token is active, as it tells at `/introspect`
+async fn is_active(app: &TestApp, client_id: &str, client_secret: &str, token: &str) -> bool {
+    let response = app
+        .post_introspect(client_id, client_secret, &[("token", token)])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<IntrospectionResponse>()
+        .await
+        .unwrap()
+        .active
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn should_revoke_client_token() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let client = register_client(&app, true).await;
+    let (client_id, client_secret) = (client.client.client_id, client.client_secret.unwrap());
+    let token = client_token(&app, &client_id, &client_secret).await;
+    assert!(is_active(&app, &client_id, &client_secret, &token).await);
+
+    // Other clients can't revoke it
+    let other_client = register_client(&app, true).await;
+    let (other_client_id, other_client_secret) = (
+        other_client.client.client_id,
+        other_client.client_secret.unwrap(),
+    );
+    let response = app
+        .post_revoke(
+            &other_client_id,
+            &other_client_secret,
+            &[("token", token.as_str())],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "unauthorized_client");
+    assert!(is_active(&app, &client_id, &client_secret, &token).await);
+
+    let response = app
+        .post_revoke(
+            &client_id,
+            &client_secret,
+            &[
+                ("token", token.as_str()),
+                ("token_type_hint", "access_token"),
+            ],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!is_active(&app, &client_id, &client_secret, &token).await);
+
+    // Revoking it again, or a token that never was one, changes nothing
+    for token in [token.as_str(), "not-a-token"] {
+        let response = app
+            .post_revoke(&client_id, &client_secret, &[("token", token)])
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_user_tokens() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let client = register_client(&app, true).await;
+    let (client_id, client_secret) = (client.client.client_id, client.client_secret.unwrap());
+    let auth_token = get_cookie(&app, JWT_COOKIE_NAME);
+    let refresh_token = get_cookie(&app, REFRESH_TOKEN_COOKIE_NAME);
+
+    let response = app
+        .post_revoke(
+            &client_id,
+            &client_secret,
+            &[("token", auth_token.as_str())],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(verify_token_status(&app, &auth_token).await, 401);
+
+    let response = app
+        .post_revoke(
+            &client_id,
+            &client_secret,
+            &[("token", refresh_token.as_str())],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(app.post_refresh().await.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_client_authentication() {
+    let mut app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app, true).await.client.client_id;
+    let auth_token = get_cookie(&app, JWT_COOKIE_NAME);
+
+    let response = app
+        .post_revoke(
+            &client_id,
+            "wrong-secret",
+            &[("token", auth_token.as_str())],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_client");
+
+    let response = app
+        .http_client
+        .post(format!("{}/revoke", &app.address))
+        .form(&[("token", auth_token.as_str())])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_request");
+    assert_eq!(verify_token_status(&app, &auth_token).await, 200);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,215 @@
+use auth_service::{
+    routes::SessionsResponse,
+    utils::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+
+use crate::helpers::{
+    TEST_PASSWORD, TestApp, get_cookie_value, get_random_email, get_token_claims, login_body,
+    signup_and_login,
+};
+
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0";
+
+// Logs in from another device, which doesn't share the app client's cookies.
+// Returns the auth and refresh tokens of the new session.
+async fn login_from_other_device(app: &TestApp, email: &str) -> (String, String) {
+    let response = reqwest::Client::new()
+        .post(format!("{}/login", &app.address))
+        .header("User-Agent", USER_AGENT)
+        .json(&login_body(email))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    (
+        get_cookie_value(&response, JWT_COOKIE_NAME),
+        get_cookie_value(&response, REFRESH_TOKEN_COOKIE_NAME),
+    )
+}
+
+async fn refresh_from_other_device(app: &TestApp, refresh_token: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/refresh", &app.address))
+        .header(
+            "Cookie",
+            format!("{}={}", REFRESH_TOKEN_COOKIE_NAME, refresh_token),
+        )
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn get_sessions(app: &TestApp) -> SessionsResponse {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    assert_eq!(app.get_sessions().await.status().as_u16(), 400);
+    assert_eq!(
+        app.delete_session(&uuid::Uuid::new_v4().to_string())
+            .await
+            .status()
+            .as_u16(),
+        400
+    );
+    assert_eq!(app.post_logout_all().await.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_sessions_of_the_user() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    let (other_token, _) = login_from_other_device(&app, &email).await;
+
+    // Sessions of other users are not listed
+    let other_app_user = get_random_email();
+    app.post_signup_and_verify_email(&serde_json::json!({
+        "email": other_app_user,
+        "password": TEST_PASSWORD,
+        "requires2FA": false,
+    }))
+    .await;
+    login_from_other_device(&app, &other_app_user).await;
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions[0].current);
+    assert!(!sessions[1].current);
+    assert_eq!(sessions[1].id, get_token_claims(&other_token).sid);
+    assert_eq!(sessions[1].device.as_deref(), Some("Firefox on Linux"));
+    assert_eq!(sessions[1].user_agent.as_deref(), Some(USER_AGENT));
+    assert_eq!(sessions[1].ip.as_deref(), Some("127.0.0.1"));
+    assert!(sessions[1].created_at > 0);
+    assert_eq!(sessions[1].created_at, sessions[1].last_seen_at);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_end_other_session_when_deleted() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    let (other_token, other_refresh_token) = login_from_other_device(&app, &email).await;
+    let other_session_id = get_token_claims(&other_token).sid;
+
+    let response = app.delete_session(&other_session_id).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+
+    // Neither the auth token nor the refresh token of the ended session work anymore
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = refresh_from_other_device(&app, &other_refresh_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Deleting it again reports it as missing
+    let response = app.delete_session(&other_session_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_log_out_when_current_session_deleted() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let session_id = get_sessions(&app).await.sessions[0].id.clone();
+
+    let response = app.delete_session(&session_id).await;
+    assert_eq!(response.status().as_u16(), 204);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(auth_cookie.value().is_empty());
+
+    assert_eq!(app.get_sessions().await.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_session_unknown_or_of_other_user() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let other_email = get_random_email();
+    app.post_signup_and_verify_email(&serde_json::json!({
+        "email": other_email,
+        "password": TEST_PASSWORD,
+        "requires2FA": false,
+    }))
+    .await;
+    let (other_token, _) = login_from_other_device(&app, &other_email).await;
+
+    let test_cases = [
+        "invalid".to_owned(),
+        uuid::Uuid::new_v4().to_string(),
+        get_token_claims(&other_token).sid,
+    ];
+    for session_id in test_cases {
+        let response = app.delete_session(&session_id).await;
+        assert_eq!(
+            response.status().as_u16(),
+            404,
+            "failed for input: {:?}",
+            session_id
+        );
+    }
+
+    // The other user's session is still alive
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_end_all_sessions_on_logout_all() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    let (other_token, other_refresh_token) = login_from_other_device(&app, &email).await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = refresh_from_other_device(&app, &other_refresh_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+    // The cookies of the device that logged out are cleared
+    assert_eq!(app.post_refresh().await.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_activity_on_refresh() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let session = get_sessions(&app).await.sessions.remove(0);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = get_cookie_value(&response, JWT_COOKIE_NAME);
+    assert_eq!(get_token_claims(&token).sid, session.id);
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+    assert!(sessions[0].last_seen_at >= session.last_seen_at);
+    app.clean_up().await;
+}
@@ -1,5 +1,10 @@
 use auth_service::ErrorResponse;
+use auth_service::domain::RECOVERY_CODE_COUNT;
 use auth_service::routes::SignupResponse;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
 
 use crate::helpers::TestApp;
 
@@ -39,21 +44,51 @@ async fn should_return_201_if_valid_input() {
         "password": "password123",
         "requires2FA": true
     });
+
+    // A verification email is sent to the new user
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
     let response = app.post_signup(&valid_input).await;
     assert_eq!(response.status().as_u16(), 201);
 
-    let expected_response = SignupResponse {
-        message: "User created successfully!".to_owned(),
-    };
-
     // Assert that we are getting the correct response body!
+    let response_body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to UserBody");
     assert_eq!(
-        response
-            .json::<SignupResponse>()
-            .await
-            .expect("Could not deserialize response body to UserBody"),
-        expected_response
+        response_body.message,
+        "User created successfully!".to_owned()
     );
+
+    // The user signed up with 2FA, so they get their recovery codes
+    let recovery_codes = response_body
+        .recovery_codes
+        .expect("No recovery codes in response body");
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_return_recovery_codes_without_2fa() {
+    let mut app = TestApp::new().await;
+    let valid_input = serde_json::json!({
+        "email": "some@mydomain.com",
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup_and_verify_email(&valid_input).await;
+    let response_body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse");
+    assert_eq!(response_body.recovery_codes, None);
     app.clean_up().await;
 }
 
@@ -77,7 +112,7 @@ async fn should_return_400_if_invalid_input() {
         "password": "word123",
         "requires2FA": true
     });
-    let invalid_inputs = vec![invalid_email_address, invalid_password];
+    let invalid_inputs = [invalid_email_address, invalid_password];
 
     for input in invalid_inputs.iter() {
         let response_invalid_email = app.post_signup(input).await;
@@ -108,7 +143,7 @@ async fn should_return_409_if_email_already_exists() {
         "password": "password123",
         "requires2FA": true
     });
-    let response = app.post_signup(&valid_input).await;
+    let response = app.post_signup_and_verify_email(&valid_input).await;
     assert_eq!(response.status().as_u16(), 201);
 
     let response = app.post_signup(&valid_input).await;
@@ -0,0 +1,217 @@
+use auth_service::{
+    domain::TwoFAMethod,
+    routes::{ConfirmTotpResponse, EnrollTotpResponse, TwoFactorAuthResponse},
+    utils::JWT_COOKIE_NAME,
+};
+use totp_rs::{Algorithm, TOTP};
+
+use crate::helpers::{TestApp, login_body, mount_email_server, signup_and_login};
+
+// Stands in for the user's authenticator app, giving the code of the time step `steps` away
+// from the current one
+fn code_at(secret: &str, steps: i64) -> String {
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        totp_rs::Secret::Encoded(secret.to_owned())
+            .to_bytes()
+            .unwrap(),
+        None,
+        "test".to_owned(),
+    )
+    .unwrap();
+    let now = chrono::Utc::now().timestamp();
+    totp.generate((now + steps * 30) as u64)
+}
+
+fn current_code(secret: &str) -> String {
+    code_at(secret, 0)
+}
+
+// Returns a different, well-formed code
+fn wrong_code(code: &str) -> String {
+    code.chars()
+        .map(|c| char::from_digit((c.to_digit(10).unwrap() + 1) % 10, 10).unwrap())
+        .collect()
+}
+
+async fn enroll(app: &TestApp) -> EnrollTotpResponse {
+    let response = app
+        .post_totp_enroll(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse")
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_totp_enroll(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_otpauth_uri_on_enroll() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let enrollment = enroll(&app).await;
+    assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(
+        enrollment
+            .otpauth_uri
+            .contains(&format!("secret={}", enrollment.secret))
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_confirming_without_enrollment_or_with_wrong_code() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let enrollment = enroll(&app).await;
+    let code = wrong_code(&current_code(&enrollment.secret));
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": "12345" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_totp_code_on_login_once_enabled() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_and_login(&app).await;
+
+    let enrollment = enroll(&app).await;
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": current_code(&enrollment.secret) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<ConfirmTotpResponse>()
+            .await
+            .expect("Could not deserialize response body to ConfirmTotpResponse")
+            .message,
+        "Authenticator app enabled for 2FA.".to_owned()
+    );
+
+    // No code is emailed to users of an authenticator app
+    mount_email_server(&app, 0).await;
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(response_body.two_fa_method, TwoFAMethod::Totp);
+
+    // The code that confirmed the app was used up, so the next one is given
+    let code = code_at(&enrollment.secret, 1);
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": response_body.login_attempt_id,
+            "2FACode": wrong_code(&code),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": response_body.login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_enrolling_with_wrong_password() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let response = app
+        .post_totp_enroll(&serde_json::json!({ "password": "wrongpassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_totp_enroll(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    // No secret is pending, so there is nothing to confirm
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_totp_code_reused() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_and_login(&app).await;
+
+    let enrollment = enroll(&app).await;
+    let code = current_code(&enrollment.secret);
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let next_code = code_at(&enrollment.secret, 1);
+    // Neither the code that confirmed the app, nor one that was already used to log in,
+    // can log in again
+    for (code, expected_status) in [(code, 401), (next_code.clone(), 200), (next_code, 401)] {
+        let response = app.post_login(&login_body(&random_email)).await;
+        assert_eq!(response.status().as_u16(), 206);
+        let response_body = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": random_email,
+                "loginAttemptId": response_body.login_attempt_id,
+                "2FACode": code,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), expected_status);
+    }
+    app.clean_up().await;
+}
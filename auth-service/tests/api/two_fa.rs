@@ -0,0 +1,184 @@
+use auth_service::{
+    domain::RECOVERY_CODE_COUNT,
+    routes::{Disable2FAResponse, Enable2FAResponse, MAX_TWO_FA_ATTEMPTS},
+};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{body_partial_json, method, path},
+};
+
+use crate::helpers::{
+    TestApp, get_random_email, get_two_fa_code, login_body, mount_email_server, signup_and_login,
+    signup_and_login_with_2fa,
+};
+
+// Starts the check of the second factor for disabling 2FA
+async fn request_two_fa_code(app: &TestApp) {
+    let response = app
+        .post_disable_2fa(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+}
+
+fn disable_body(code: &str) -> serde_json::Value {
+    serde_json::json!({
+        "password": "password123",
+        "2FACode": code,
+    })
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+    let body = serde_json::json!({ "password": "password123" });
+    let response = app.post_enable_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app.post_disable_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_password() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let body = serde_json::json!({ "password": "wrong-password" });
+    let response = app.post_enable_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app.post_disable_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_enable_2fa() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_and_login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .and(body_partial_json(
+            serde_json::json!({ "Subject": "2FA enabled" }),
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    mount_email_server(&app, ..).await;
+
+    let body = serde_json::json!({ "password": "password123" });
+    let response = app.post_enable_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = response
+        .json::<Enable2FAResponse>()
+        .await
+        .expect("Could not deserialize response body to Enable2FAResponse");
+    assert_eq!(response.recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    let response = app.post_enable_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_2fa_not_enabled() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let response = app.post_disable_2fa(&disable_body("123456")).await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_code_not_requested() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login_with_2fa(&app, &random_email).await;
+
+    let response = app.post_disable_2fa(&disable_body("123456")).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_disable_2fa_with_emailed_code() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login_with_2fa(&app, &random_email).await;
+
+    request_two_fa_code(&app).await;
+    let two_fa_code = get_two_fa_code(&app, &random_email).await;
+    let wrong_code = if two_fa_code == "123456" {
+        "654321"
+    } else {
+        "123456"
+    };
+    let response = app.post_disable_2fa(&disable_body(wrong_code)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_disable_2fa(&disable_body(&two_fa_code)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<Disable2FAResponse>()
+            .await
+            .expect("Could not deserialize response body to Disable2FAResponse"),
+        Disable2FAResponse {
+            message: "2FA disabled.".to_owned(),
+        }
+    );
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_disable_2fa_with_recovery_code() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    let recovery_codes = signup_and_login_with_2fa(&app, &random_email).await;
+
+    request_two_fa_code(&app).await;
+    let response = app
+        .post_disable_2fa(&disable_body(&recovery_codes[0]))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_after_too_many_incorrect_codes() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login_with_2fa(&app, &random_email).await;
+
+    request_two_fa_code(&app).await;
+    let two_fa_code = get_two_fa_code(&app, &random_email).await;
+    let wrong_code = if two_fa_code == "123456" {
+        "654321"
+    } else {
+        "123456"
+    };
+    for _ in 1..MAX_TWO_FA_ATTEMPTS {
+        let response = app.post_disable_2fa(&disable_body(wrong_code)).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    let response = app.post_disable_2fa(&disable_body(wrong_code)).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    // The code can't be used anymore, so 2FA stays on
+    let response = app.post_disable_2fa(&disable_body(&two_fa_code)).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+    app.clean_up().await;
+}
@@ -1,4 +1,8 @@
-use auth_service::{domain::Email, routes::TwoFactorAuthResponse};
+use auth_service::{
+    ErrorResponse,
+    domain::{Email, UserStatus},
+    routes::{MAX_TWO_FA_ATTEMPTS, TwoFactorAuthResponse},
+};
 use secrecy::{ExposeSecret, Secret};
 use wiremock::{
     Mock, ResponseTemplate,
@@ -10,7 +14,7 @@ use crate::helpers::{TestApp, get_random_email};
 #[tokio::test]
 async fn should_return_422_if_malformed_input() {
     let mut app = TestApp::new().await;
-    let invalid_inputs = vec![
+    let invalid_inputs = [
         serde_json::json!({
         "loginAttemptId": "string",
         "2FACode": "string"
@@ -34,7 +38,7 @@ async fn should_return_422_if_malformed_input() {
 #[tokio::test]
 async fn should_return_400_if_invalid_input() {
     let mut app = TestApp::new().await;
-    let test_cases = vec![
+    let test_cases = [
         serde_json::json!({
             "email": "not-an-email-address",
             "loginAttemptId": "1dd67e3b-94e5-46e5-aa10-31f9c7f037e5",
@@ -71,7 +75,7 @@ async fn should_return_401_if_old_code() {
         "requires2FA": true
     });
 
-    let response = app.post_signup(&signup_body).await;
+    let response = app.post_signup_and_verify_email(&signup_body).await;
 
     assert_eq!(response.status().as_u16(), 201);
 
@@ -129,7 +133,7 @@ async fn should_return_200_if_correct_code() {
         "requires2FA": true
     });
 
-    let response = app.post_signup(&signup_body).await;
+    let response = app.post_signup_and_verify_email(&signup_body).await;
 
     assert_eq!(response.status().as_u16(), 201);
 
@@ -177,6 +181,67 @@ async fn should_return_200_if_correct_code() {
     app.clean_up().await;
 }
 
+#[tokio::test]
+async fn should_return_403_if_account_suspended() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+    let response = app.post_signup_and_verify_email(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email.clone(),
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    // Suspended between checking the password and the code
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let (login_attempt_id, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .unwrap();
+    app.app_state
+        .user_store
+        .write()
+        .await
+        .set_status(&email, UserStatus::Suspended)
+        .await
+        .unwrap();
+
+    let test_case = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id.as_ref().expose_secret(),
+        "2FACode": two_fa_code.as_ref().expose_secret(),
+    });
+    let response = app.post_verify_2fa(&test_case).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Account has been suspended"
+    );
+    app.clean_up().await;
+}
+
 #[tokio::test]
 async fn should_return_401_if_same_code_twice() {
     // Make sure to assert the auth cookie gets set
@@ -191,7 +256,7 @@ async fn should_return_401_if_same_code_twice() {
         "requires2FA": true
     });
 
-    let response = app.post_signup(&signup_body).await;
+    let response = app.post_signup_and_verify_email(&signup_body).await;
 
     assert_eq!(response.status().as_u16(), 201);
     Mock::given(path("/email"))
@@ -239,3 +304,78 @@ async fn should_return_401_if_same_code_twice() {
     assert_eq!(response_2.status().as_u16(), 401);
     app.clean_up().await;
 }
+
+#[tokio::test]
+async fn should_return_403_and_end_login_attempt_after_too_many_wrong_codes() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+    let response = app.post_signup_and_verify_email(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email.clone(),
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let (_, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .unwrap();
+    let two_fa_code = two_fa_code.as_ref().expose_secret().to_owned();
+    let wrong_code = match two_fa_code.as_str() {
+        "111111" => "222222",
+        _ => "111111",
+    };
+
+    let wrong_code_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": wrong_code,
+    });
+    for _ in 1..MAX_TWO_FA_ATTEMPTS {
+        let response = app.post_verify_2fa(&wrong_code_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    let response = app.post_verify_2fa(&wrong_code_body).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many incorrect 2FA codes, log in again".to_owned()
+    );
+
+    // Not even the right code is accepted for the ended login attempt
+    let correct_code_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": two_fa_code,
+    });
+    let response = app.post_verify_2fa(&correct_code_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
@@ -1,4 +1,8 @@
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::{
+    domain::{Email, UserStatus},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use secrecy::Secret;
 
 use crate::helpers::{TestApp, get_random_email};
 
@@ -12,7 +16,7 @@ async fn should_return_200_valid_token() {
         "password": "longenough",
         "requires2FA": false,
     });
-    app.post_signup(&signup_request_body).await;
+    app.post_signup_and_verify_email(&signup_request_body).await;
 
     let login_request_body = serde_json::json!({
         "email": random_email,
@@ -22,7 +26,7 @@ async fn should_return_200_valid_token() {
     let token = login_response
         .cookies()
         .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
-        .expect(format!("Cookie with name '{}' not found", JWT_COOKIE_NAME).as_str());
+        .unwrap_or_else(|| panic!("Cookie with name '{}' not found", JWT_COOKIE_NAME));
     let token = token.value();
     let valid_token_input = serde_json::json!({
         "token": token
@@ -62,7 +66,7 @@ async fn should_return_422_if_malformed_input() {
     let irrelevant_input = serde_json::json!({
         "foo": "bar"
     });
-    let test_cases = vec![input_missing, irrelevant_input];
+    let test_cases = [input_missing, irrelevant_input];
 
     for test_case in test_cases.iter() {
         let response = app.post_login(test_case).await;
@@ -86,7 +90,7 @@ async fn should_return_401_if_banned_token() {
         "password": "longenough",
         "requires2FA": false,
     });
-    app.post_signup(&signup_request_body).await;
+    app.post_signup_and_verify_email(&signup_request_body).await;
 
     let login_request_body = serde_json::json!({
         "email": random_email,
@@ -96,7 +100,7 @@ async fn should_return_401_if_banned_token() {
     let token = login_response
         .cookies()
         .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
-        .expect(format!("Cookie with name '{}' not found", JWT_COOKIE_NAME).as_str());
+        .unwrap_or_else(|| panic!("Cookie with name '{}' not found", JWT_COOKIE_NAME));
     let token = token.value();
 
     let logout_response = app.post_logout().await;
@@ -115,3 +119,42 @@ async fn should_return_401_if_banned_token() {
     );
     app.clean_up().await;
 }
+
+#[tokio::test]
+async fn should_return_401_if_user_suspended() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let signup_request_body = serde_json::json!({
+        "email": random_email,
+        "password": "longenough",
+        "requires2FA": false,
+    });
+    app.post_signup_and_verify_email(&signup_request_body).await;
+
+    let login_request_body = serde_json::json!({
+        "email": random_email,
+        "password": "longenough",
+    });
+    let login_response = app.post_login(&login_request_body).await;
+    let token = login_response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .unwrap_or_else(|| panic!("Cookie with name '{}' not found", JWT_COOKIE_NAME));
+
+    let email = Email::parse(Secret::new(random_email)).unwrap();
+    app.app_state
+        .user_store
+        .write()
+        .await
+        .set_status(&email, UserStatus::Suspended)
+        .await
+        .unwrap();
+
+    // Services treat anything but 401 as an error of their own
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token.value() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}